@@ -6,33 +6,84 @@ use std::{error::Error, fmt};
 #[cfg(provide_any)]
 use std::any::Demand;
 
-pub struct DynError(Box<dyn Error + Send + Sync + 'static>);
+#[cfg(feature = "backtrace")]
+use std::backtrace::Backtrace;
+
+pub struct DynError {
+	error: Box<dyn Error + Send + Sync + 'static>,
+	#[cfg(feature = "backtrace")]
+	backtrace: Backtrace,
+}
 
 impl DynError {
 	pub fn new<E: Error + Send + Sync + 'static>(error: E) -> Self {
-		Self(Box::new(error))
+		Self {
+			error: Box::new(error),
+			#[cfg(feature = "backtrace")]
+			backtrace: Backtrace::capture(),
+		}
+	}
+
+	/// The backtrace captured when the error was wrapped. Errors that capture a
+	/// backtrace of their own provide that one instead (through [Error::provide]).
+	#[cfg(feature = "backtrace")]
+	#[cfg_attr(doc_cfg, doc(cfg(feature = "backtrace")))]
+	pub fn backtrace(&self) -> &Backtrace {
+		&self.backtrace
 	}
 }
 
 impl fmt::Debug for DynError {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		fmt::Debug::fmt(&*self.0, f)
+		fmt::Debug::fmt(&*self.error, f)
 	}
 }
 
 impl fmt::Display for DynError {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		fmt::Display::fmt(&*self.0, f)
+		fmt::Display::fmt(&*self.error, f)
 	}
 }
 
 impl Error for DynError {
 	fn source(&self) -> Option<&(dyn Error + 'static)> {
-		self.0.source()
+		self.error.source()
 	}
 
 	#[cfg(provide_any)]
 	fn provide<'a>(&'a self, demand: &mut Demand<'a>) {
-		self.0.provide(demand);
+		self.error.provide(demand);
+		#[cfg(feature = "backtrace")]
+		demand.provide_ref(&self.backtrace);
+	}
+}
+
+#[cfg(all(test, feature = "backtrace"))]
+mod tests {
+	use super::*;
+
+	#[derive(Debug)]
+	struct Inner;
+
+	impl fmt::Display for Inner {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			f.write_str("inner")
+		}
+	}
+
+	impl Error for Inner {}
+
+	#[test]
+	fn backtrace_is_captured_on_wrap() {
+		let error = DynError::new(Inner);
+
+		// whether a backtrace is captured depends on `RUST_BACKTRACE`
+		assert_eq!(error.backtrace().status(), Backtrace::capture().status());
+
+		#[cfg(provide_any)]
+		{
+			let error: &dyn Error = &error;
+			assert!(error.request_ref::<Backtrace>().is_some());
+		}
 	}
 }
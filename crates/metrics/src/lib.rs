@@ -2,7 +2,7 @@ use opentelemetry::metrics as otel;
 use opentelemetry::Context as OtelContext;
 use opentelemetry::{Key, KeyValue, Value};
 use std::borrow::Cow;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 mod intern {
 	use lasso::ThreadedRodeo;
@@ -86,12 +86,18 @@ impl MetricField for u16 {
 	}
 }
 
+impl MetricField for u8 {
+	fn into_value(self) -> Value {
+		Value::from(self as i64)
+	}
+}
+
 pub trait MetricFields {
 	const LENGTH: usize;
 	type Init;
 	type Keys;
 	type Values;
-	type KeyValues;
+	type KeyValues: AsRef<[KeyValue]> + PartialEq + Send + 'static;
 
 	fn intern(names: Self::Init) -> Self::Keys;
 	fn zip(keys: &Self::Keys, values: Self::Values) -> Self::KeyValues;
@@ -113,50 +119,37 @@ impl MetricFields for () {
 	}
 }
 
-impl<T1> MetricFields for (T1,)
-where
-	T1: MetricField,
-{
-	const LENGTH: usize = 1;
-	type Init = [Cow<'static, str>; 1];
-	type Keys = [Key; 1];
-	type Values = [Value; 1];
-	type KeyValues = [KeyValue; 1];
-
-	fn intern(names: Self::Init) -> Self::Keys {
-		let [n0] = names;
-		[intern::get_or_intern(n0)]
-	}
+macro_rules! impl_metric_fields {
+	($len:literal; $($t:ident $n:ident $k:ident $v:ident),+) => {
+		impl<$($t),+> MetricFields for ($($t,)+)
+		where
+			$($t: MetricField,)+
+		{
+			const LENGTH: usize = $len;
+			type Init = [Cow<'static, str>; $len];
+			type Keys = [Key; $len];
+			type Values = [Value; $len];
+			type KeyValues = [KeyValue; $len];
+
+			fn intern(names: Self::Init) -> Self::Keys {
+				let [$($n),+] = names;
+				[$(intern::get_or_intern($n)),+]
+			}
 
-	fn zip(keys: &Self::Keys, values: Self::Values) -> Self::KeyValues {
-		let [k0] = keys;
-		let [v0] = values;
-		[KeyValue::new(k0.clone(), v0)]
-	}
+			fn zip(keys: &Self::Keys, values: Self::Values) -> Self::KeyValues {
+				let [$($k),+] = keys;
+				let [$($v),+] = values;
+				[$(KeyValue::new($k.clone(), $v)),+]
+			}
+		}
+	};
 }
 
-impl<T1, T2> MetricFields for (T1, T2)
-where
-	T1: MetricField,
-	T2: MetricField,
-{
-	const LENGTH: usize = 2;
-	type Init = [Cow<'static, str>; 2];
-	type Keys = [Key; 2];
-	type Values = [Value; 2];
-	type KeyValues = [KeyValue; 2];
-
-	fn intern(names: Self::Init) -> Self::Keys {
-		let [n0, n1] = names;
-		[intern::get_or_intern(n0), intern::get_or_intern(n1)]
-	}
-
-	fn zip(keys: &Self::Keys, values: Self::Values) -> Self::KeyValues {
-		let [k0, k1] = keys;
-		let [v0, v1] = values;
-		[KeyValue::new(k0.clone(), v0), KeyValue::new(k1.clone(), v1)]
-	}
-}
+impl_metric_fields!(1; T1 n0 k0 v0);
+impl_metric_fields!(2; T1 n0 k0 v0, T2 n1 k1 v1);
+impl_metric_fields!(3; T1 n0 k0 v0, T2 n1 k1 v1, T3 n2 k2 v2);
+impl_metric_fields!(4; T1 n0 k0 v0, T2 n1 k1 v1, T3 n2 k2 v2, T4 n3 k3 v3);
+impl_metric_fields!(5; T1 n0 k0 v0, T2 n1 k1 v1, T3 n2 k2 v2, T4 n3 k3 v3, T5 n4 k4 v4);
 
 pub struct Counter<T: MetricFields> {
 	inner: otel::Counter<u64>,
@@ -168,94 +161,264 @@ impl<T: MetricFields> Counter<T> {
 		meter: &otel::Meter,
 		name: impl Into<String>,
 		description: impl Into<String>,
+		unit: Option<&'static str>,
 		field_names: <T as MetricFields>::Init,
 	) -> Self {
 		let field_names = T::intern(field_names);
 
-		let inner = meter.u64_counter(name).with_description(description).init();
+		let mut builder = meter.u64_counter(name).with_description(description);
+		if let Some(unit) = unit {
+			builder = builder.with_unit(otel::Unit::new(unit));
+		}
+
+		let inner = builder.init();
 		Self { inner, field_names }
 	}
 }
 
-impl Counter<()> {
-	pub fn add_in_context(&self, cx: &OtelContext, value: u64) {
-		self.inner.add(cx, value, &[])
-	}
+pub struct UpDownCounter<T: MetricFields> {
+	inner: otel::UpDownCounter<i64>,
+	field_names: <T as MetricFields>::Keys,
+}
 
-	pub fn add(&self, value: u64) {
-		self.add_in_context(&OtelContext::current(), value)
+impl<T: MetricFields> UpDownCounter<T> {
+	pub fn new(
+		meter: &otel::Meter,
+		name: impl Into<String>,
+		description: impl Into<String>,
+		unit: Option<&'static str>,
+		field_names: <T as MetricFields>::Init,
+	) -> Self {
+		let field_names = T::intern(field_names);
+
+		let mut builder = meter
+			.i64_up_down_counter(name)
+			.with_description(description);
+		if let Some(unit) = unit {
+			builder = builder.with_unit(otel::Unit::new(unit));
+		}
+
+		let inner = builder.init();
+		Self { inner, field_names }
 	}
 }
 
-impl<T1> Counter<(T1,)>
-where
-	T1: MetricField,
-{
-	pub fn add_in_context(&self, cx: &OtelContext, value: u64, field1: T1) {
-		let field_values = <(T1,) as MetricFields>::zip(&self.field_names, [field1.into_value()]);
-		self.inner.add(cx, value, &field_values)
+/// The last value set on a [Gauge] for each combination of labels.
+type GaugeValues<T> = Vec<(<T as MetricFields>::KeyValues, i64)>;
+
+/// A gauge that reports the last value set for each combination of labels.
+///
+/// OpenTelemetry only has asynchronous gauges, so the values are kept here and
+/// observed from a callback registered on the meter when the metrics are collected.
+pub struct Gauge<T: MetricFields> {
+	field_names: <T as MetricFields>::Keys,
+	values: Arc<Mutex<GaugeValues<T>>>,
+}
+
+impl<T: MetricFields> Gauge<T> {
+	pub fn new(
+		meter: &otel::Meter,
+		name: impl Into<String>,
+		description: impl Into<String>,
+		unit: Option<&'static str>,
+		field_names: <T as MetricFields>::Init,
+	) -> Self {
+		let field_names = T::intern(field_names);
+
+		let mut builder = meter
+			.i64_observable_gauge(name)
+			.with_description(description);
+		if let Some(unit) = unit {
+			builder = builder.with_unit(otel::Unit::new(unit));
+		}
+
+		let inner = builder.init();
+		let values = Arc::new(Mutex::new(GaugeValues::<T>::new()));
+		let observed = values.clone();
+		let registered = meter.register_callback(move |cx| {
+			let values = observed.lock().unwrap_or_else(|e| e.into_inner());
+			for (field_values, value) in values.iter() {
+				inner.observe(cx, *value, field_values.as_ref());
+			}
+		});
+
+		if let Err(e) = registered {
+			opentelemetry::global::handle_error(e);
+		}
+
+		Self {
+			field_names,
+			values,
+		}
 	}
 
-	pub fn add(&self, value: u64, field1: T1) {
-		self.add_in_context(&OtelContext::current(), value, field1)
+	fn set_values(&self, field_values: <T as MetricFields>::KeyValues, value: i64) {
+		let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+		match values.iter_mut().find(|(f, _)| *f == field_values) {
+			Some((_, current)) => *current = value,
+			None => values.push((field_values, value)),
+		}
 	}
 }
 
-impl<T1, T2> Counter<(T1, T2)>
-where
-	T1: MetricField,
-	T2: MetricField,
-{
-	pub fn add_in_context(&self, cx: &OtelContext, value: u64, field1: T1, field2: T2) {
-		let field_values = <(T1, T2) as MetricFields>::zip(
-			&self.field_names,
-			[field1.into_value(), field2.into_value()],
-		);
-		self.inner.add(cx, value, &field_values)
-	}
+pub struct Histogram<T: MetricFields> {
+	inner: otel::Histogram<f64>,
+	field_names: <T as MetricFields>::Keys,
+}
 
-	pub fn add(&self, value: u64, field1: T1, field2: T2) {
-		self.add_in_context(&OtelContext::current(), value, field1, field2)
+impl<T: MetricFields> Histogram<T> {
+	pub fn new(
+		meter: &otel::Meter,
+		name: impl Into<String>,
+		description: impl Into<String>,
+		unit: Option<&'static str>,
+		field_names: <T as MetricFields>::Init,
+	) -> Self {
+		let field_names = T::intern(field_names);
+
+		let mut builder = meter.f64_histogram(name).with_description(description);
+		if let Some(unit) = unit {
+			builder = builder.with_unit(otel::Unit::new(unit));
+		}
+
+		let inner = builder.init();
+		Self { inner, field_names }
 	}
 }
 
+macro_rules! impl_instrument_methods {
+	($($t:ident $f:ident),*) => {
+		impl<$($t),*> Counter<($($t,)*)>
+		where
+			$($t: MetricField,)*
+		{
+			#[allow(clippy::too_many_arguments)]
+			pub fn add_in_context(&self, cx: &OtelContext, value: u64, $($f: $t),*) {
+				let field_values = <($($t,)*) as MetricFields>::zip(
+					&self.field_names,
+					[$($f.into_value()),*],
+				);
+				self.inner.add(cx, value, &field_values)
+			}
+
+			pub fn add(&self, value: u64, $($f: $t),*) {
+				self.add_in_context(&OtelContext::current(), value, $($f),*)
+			}
+		}
+
+		impl<$($t),*> UpDownCounter<($($t,)*)>
+		where
+			$($t: MetricField,)*
+		{
+			#[allow(clippy::too_many_arguments)]
+			pub fn add_in_context(&self, cx: &OtelContext, value: i64, $($f: $t),*) {
+				let field_values = <($($t,)*) as MetricFields>::zip(
+					&self.field_names,
+					[$($f.into_value()),*],
+				);
+				self.inner.add(cx, value, &field_values)
+			}
+
+			pub fn add(&self, value: i64, $($f: $t),*) {
+				self.add_in_context(&OtelContext::current(), value, $($f),*)
+			}
+
+			#[allow(clippy::too_many_arguments)]
+			pub fn sub_in_context(&self, cx: &OtelContext, value: i64, $($f: $t),*) {
+				self.add_in_context(cx, -value, $($f),*)
+			}
+
+			pub fn sub(&self, value: i64, $($f: $t),*) {
+				self.sub_in_context(&OtelContext::current(), value, $($f),*)
+			}
+		}
+
+		impl<$($t),*> Histogram<($($t,)*)>
+		where
+			$($t: MetricField,)*
+		{
+			#[allow(clippy::too_many_arguments)]
+			pub fn record_in_context(&self, cx: &OtelContext, value: f64, $($f: $t),*) {
+				let field_values = <($($t,)*) as MetricFields>::zip(
+					&self.field_names,
+					[$($f.into_value()),*],
+				);
+				self.inner.record(cx, value, &field_values)
+			}
+
+			pub fn record(&self, value: f64, $($f: $t),*) {
+				self.record_in_context(&OtelContext::current(), value, $($f),*)
+			}
+		}
+
+		impl<$($t),*> Gauge<($($t,)*)>
+		where
+			$($t: MetricField,)*
+		{
+			pub fn set(&self, value: i64, $($f: $t),*) {
+				let field_values = <($($t,)*) as MetricFields>::zip(
+					&self.field_names,
+					[$($f.into_value()),*],
+				);
+				self.set_values(field_values, value)
+			}
+		}
+	};
+}
+
+impl_instrument_methods!();
+impl_instrument_methods!(T1 field1);
+impl_instrument_methods!(T1 field1, T2 field2);
+impl_instrument_methods!(T1 field1, T2 field2, T3 field3);
+impl_instrument_methods!(T1 field1, T2 field2, T3 field3, T4 field4);
+impl_instrument_methods!(T1 field1, T2 field2, T3 field3, T4 field4, T5 field5);
+
 #[doc(hidden)]
 #[macro_export]
-macro_rules! counter {
-	(@type ($($t:ty,)*)) => {
-		$crate::Counter<($($t,)*)>
+macro_rules! instrument {
+	(@type $kind:ident ($($t:ty,)*)) => {
+		$crate::$kind<($($t,)*)>
+	};
+
+	(@option) => {
+		::std::option::Option::None
+	};
+
+	(@option $value:literal) => {
+		::std::option::Option::Some(::std::convert::Into::into($value))
 	};
 }
 
 #[macro_export]
 macro_rules! metrics {
-	(@meter_type Counter(
+	(@meter_type $kind:ident(
 		$metric_name:literal,
 		$metric_description:literal
-		$(,
-			$((
-				$($metric_label:literal : $metric_label_ty:ty),*$(,)?
-			)$(,)?)?
-		)?
+		$(, unit = $metric_unit:literal)?
+		$(, (
+			$($metric_label:literal : $metric_label_ty:ty),*$(,)?
+		))?
+		$(,)?
 	)) => {
-		$crate::counter!(@type ($($($($metric_label_ty,)*)?)?))
+		$crate::instrument!(@type $kind ($($($metric_label_ty,)*)?))
 	};
 
-	(@meter_init $meter:ident Counter(
+	(@meter_init $meter:ident $kind:ident(
 		$metric_name:literal,
 		$metric_description:literal
-		$(,
-			$((
-				$($metric_label:literal : $metric_label_ty:ty),*$(,)?
-			)$(,)?)?
-		)?
+		$(, unit = $metric_unit:literal)?
+		$(, (
+			$($metric_label:literal : $metric_label_ty:ty),*$(,)?
+		))?
+		$(,)?
 	)) => {{
-		$crate::Counter::new(
+		$crate::$kind::new(
 			&$meter,
 			$metric_name,
 			$metric_description,
+			$crate::instrument!(@option $($metric_unit)?),
 			[
-				$($($(::std::borrow::Cow::from($metric_label),)*)?)?
+				$($(::std::borrow::Cow::from($metric_label),)*)?
 			],
 		)
 	}};
@@ -305,3 +468,244 @@ pub mod _export {
 	pub use opentelemetry::global::meter_with_version;
 	pub use opentelemetry::metrics as otel;
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use opentelemetry::metrics::MeterProvider;
+	use opentelemetry_sdk::export::metrics::aggregation::{
+		cumulative_temporality_selector, Count, Sum,
+	};
+	use opentelemetry_sdk::export::metrics::{InstrumentationLibraryReader, Record};
+	use opentelemetry_sdk::metrics::aggregators::{HistogramAggregator, SumAggregator};
+	use opentelemetry_sdk::metrics::controllers::{self, BasicController};
+	use opentelemetry_sdk::metrics::{processors, selectors};
+
+	metrics! {
+		struct TestMetrics {
+			publish: Counter(
+				"hass.metrics.test.publish",
+				"Number of messages published",
+				("topic": String, "qos": u8, "retained": bool),
+			),
+			payload_bytes: Counter(
+				"hass.metrics.test.payload_bytes",
+				"Number of payload bytes published",
+				unit = "By",
+				("topic": String),
+			),
+			subscriptions: UpDownCounter(
+				"hass.metrics.test.subscriptions",
+				"Number of active subscriptions",
+				("topic": String),
+			),
+			nodes: Gauge(
+				"hass.metrics.test.nodes",
+				"Number of nodes in the router",
+			),
+			publish_latency: Histogram(
+				"hass.metrics.test.publish_latency",
+				"Time it took to publish a message",
+				unit = "ms",
+				("topic": String, "retained": bool),
+			),
+			payload_size: Histogram(
+				"hass.metrics.test.payload_size",
+				"Size of published payloads",
+			),
+			queued: Gauge(
+				"hass.metrics.test.queued",
+				"Number of queued messages per topic",
+				("topic": &'static str),
+			),
+		}
+	}
+
+	fn exported_metrics() -> (BasicController, TestMetrics) {
+		let controller = controllers::basic(processors::factory(
+			selectors::simple::histogram([1.0, 10.0]),
+			cumulative_temporality_selector(),
+		))
+		.build();
+
+		let metrics = TestMetrics::from_meter(controller.meter("hass.metrics.test"));
+		(controller, metrics)
+	}
+
+	/// Collects `f` of every exported record of the instrument `name`, one per label set.
+	fn exported<R>(
+		controller: &BasicController,
+		name: &str,
+		mut f: impl FnMut(&Record) -> R,
+	) -> Vec<R> {
+		controller.collect(&OtelContext::current()).unwrap();
+
+		let mut results = Vec::new();
+		controller
+			.try_for_each(&mut |_, reader| {
+				reader.try_for_each(&cumulative_temporality_selector(), &mut |record| {
+					if record.descriptor().name() == name {
+						results.push(f(record));
+					}
+
+					Ok(())
+				})
+			})
+			.unwrap();
+
+		results
+	}
+
+	fn exported_sums(controller: &BasicController, name: &str) -> Vec<i64> {
+		exported(controller, name, |record| {
+			let sum = record
+				.aggregator()
+				.expect("should have aggregator")
+				.as_any()
+				.downcast_ref::<SumAggregator>()
+				.expect("should be a sum")
+				.sum()
+				.expect("should have a sum");
+			sum.to_i64(record.descriptor().number_kind())
+		})
+	}
+
+	/// The labels, count and sum of an exported histogram record.
+	type ExportedHistogram = (Vec<(String, Value)>, u64, f64);
+
+	/// Collects every exported histogram record of the instrument `name`.
+	fn exported_histograms(controller: &BasicController, name: &str) -> Vec<ExportedHistogram> {
+		exported(controller, name, |record| {
+			let labels = record
+				.attributes()
+				.iter()
+				.map(|(key, value)| (key.as_str().to_owned(), value.clone()))
+				.collect();
+			let histogram = record
+				.aggregator()
+				.expect("should have aggregator")
+				.as_any()
+				.downcast_ref::<HistogramAggregator>()
+				.expect("should be a histogram");
+			let count = histogram.count().expect("should have a count");
+			let sum = histogram
+				.sum()
+				.expect("should have a sum")
+				.to_f64(record.descriptor().number_kind());
+			(labels, count, sum)
+		})
+	}
+
+	fn exported_units(controller: &BasicController, name: &str) -> Vec<Option<String>> {
+		exported(controller, name, |record| {
+			record.descriptor().unit().map(str::to_owned)
+		})
+	}
+
+	#[test]
+	fn three_label_counter_zips_all_labels() {
+		let metrics = TestMetrics::global();
+		metrics.publish.add(1, "a/b".to_owned(), 1, true);
+
+		let values = [
+			"a/b".to_owned().into_value(),
+			1u8.into_value(),
+			true.into_value(),
+		];
+		let key_values =
+			<(String, u8, bool) as MetricFields>::zip(&metrics.publish.field_names, values);
+		assert_eq!(key_values.len(), 3);
+		assert_eq!(key_values[1].key.as_str(), "qos");
+		assert_eq!(key_values[2].value, Value::Bool(true));
+	}
+
+	#[test]
+	fn up_down_counter_adds_and_subtracts() {
+		let (controller, metrics) = exported_metrics();
+		metrics.subscriptions.add(2, "a/b".to_owned());
+		metrics.subscriptions.sub(1, "a/b".to_owned());
+		metrics.subscriptions.add(3, "a/b".to_owned());
+		metrics.subscriptions.sub(5, "a/b".to_owned());
+
+		assert_eq!(
+			exported_sums(&controller, "hass.metrics.test.subscriptions"),
+			[-1]
+		);
+	}
+
+	#[test]
+	fn gauge_keeps_the_last_value_per_label_set() {
+		let metrics = TestMetrics::global();
+		metrics.nodes.set(3);
+		metrics.nodes.set(5);
+		assert_eq!(metrics.nodes.values.lock().unwrap().as_slice(), &[([], 5)]);
+
+		metrics.queued.set(1, "a");
+		metrics.queued.set(2, "b");
+		metrics.queued.set(4, "a");
+		let values = metrics.queued.values.lock().unwrap();
+		assert_eq!(values.len(), 2);
+		assert_eq!(values[0].0[0].value, Value::from("a"));
+		assert_eq!(values[0].1, 4);
+		assert_eq!(values[1].1, 2);
+	}
+
+	#[test]
+	fn histogram_records_values_with_labels() {
+		let (controller, metrics) = exported_metrics();
+		for latency in [0.5, 2.0, 12.5] {
+			metrics
+				.publish_latency
+				.record(latency, "a/b".to_owned(), false);
+		}
+		metrics
+			.publish_latency
+			.record_in_context(&OtelContext::new(), 4.0, "c/d".to_owned(), true);
+		metrics.payload_size.record(128.0);
+
+		let histograms = exported_histograms(&controller, "hass.metrics.test.publish_latency");
+		let histogram = |topic: &str, retained: bool| {
+			let labels = [
+				("retained".to_owned(), Value::Bool(retained)),
+				("topic".to_owned(), Value::from(topic.to_owned())),
+			];
+			histograms
+				.iter()
+				.find(|(l, _, _)| l[..] == labels)
+				.map(|(_, count, sum)| (*count, *sum))
+		};
+		assert_eq!(histograms.len(), 2);
+		assert_eq!(histogram("a/b", false), Some((3, 15.0)));
+		assert_eq!(histogram("c/d", true), Some((1, 4.0)));
+
+		assert_eq!(
+			exported_histograms(&controller, "hass.metrics.test.payload_size"),
+			[(vec![], 1, 128.0)]
+		);
+	}
+
+	#[test]
+	fn counter_with_unit() {
+		let (controller, metrics) = exported_metrics();
+		metrics.payload_bytes.add(512, "a/b".to_owned());
+		metrics.publish.add(1, "a/b".to_owned(), 1, true);
+		metrics.publish_latency.record(2.0, "a/b".to_owned(), false);
+
+		assert_eq!(
+			exported_units(&controller, "hass.metrics.test.payload_bytes"),
+			[Some("By".to_owned())]
+		);
+		assert_eq!(
+			exported_sums(&controller, "hass.metrics.test.payload_bytes"),
+			[512]
+		);
+		assert_eq!(
+			exported_units(&controller, "hass.metrics.test.publish"),
+			[None]
+		);
+		assert_eq!(
+			exported_units(&controller, "hass.metrics.test.publish_latency"),
+			[Some("ms".to_owned())]
+		);
+	}
+}
@@ -3,24 +3,34 @@ pub(crate) mod inner;
 pub(crate) mod subscription;
 
 use self::subscription::SubscriptionToken;
-use crate::{entity::EntityTopicBuilder, HassMqttOptions};
+use crate::{
+	discovery::DiscoveryValidationError, entity::EntityTopicBuilder, trace::span, HassMqttOptions,
+};
 use futures::Stream;
 use hass_dyn_error::DynError;
-use hass_mqtt_provider::{MqttProvider, QosLevel};
+use hass_mqtt_provider::{
+	MessageSpanSampler, MqttProvider, MqttRetainHandling, MqttSubscribeBuilder, QosLevel, ServerInfo,
+};
 use pin_project::pin_project;
 use std::{
+	net::SocketAddr,
 	pin::Pin,
 	sync::Arc,
 	task::{Context, Poll},
+	time::Duration,
 };
 use thiserror::Error;
-use tracing::{field, instrument, span, Level, Span};
+use tracing::{field, Instrument, Level, Span};
+
+#[cfg(feature = "trace-payloads")]
+use crate::payload_trace::PayloadTracer;
 
 #[derive(Clone)]
 pub struct Message {
 	pub topic: Arc<str>,
 	pub payload: Arc<[u8]>,
 	pub retained: bool,
+	pub dup: bool,
 	pub span: Span,
 }
 
@@ -33,10 +43,27 @@ impl Message {
 		&self.payload
 	}
 
+	/// The payload of the message, sharing the buffer with the message (so it can be
+	/// handed off to another task without copying it).
+	pub fn payload_arc(&self) -> Arc<[u8]> {
+		self.payload.clone()
+	}
+
+	/// Consumes the message, returning its payload without copying it.
+	pub fn into_payload(self) -> Arc<[u8]> {
+		self.payload
+	}
+
 	pub fn retained(&self) -> bool {
 		self.retained
 	}
 
+	/// Whether the message may be a redelivery of a message that was received before
+	/// (the MQTT DUP flag), so handlers that aren't idempotent can skip it.
+	pub fn dup(&self) -> bool {
+		self.dup
+	}
+
 	pub fn span(&self) -> &Span {
 		&self.span
 	}
@@ -47,6 +74,7 @@ impl Message {
 pub(crate) struct Subscription {
 	pub(crate) topic: Arc<str>,
 	pub(crate) qos: QosLevel,
+	pub(crate) granted_qos: QosLevel,
 	pub(crate) token: SubscriptionToken,
 	#[pin]
 	pub(crate) stream: flume::r#async::RecvStream<'static, Message>,
@@ -62,11 +90,27 @@ impl Stream for Subscription {
 
 #[derive(Clone)]
 pub struct HassMqttClient {
-	client_id: Arc<str>,
+	pub(crate) client_id: Arc<str>,
+	pub(crate) discovery_qos: QosLevel,
+	pub(crate) discovery_retain: bool,
+	pub(crate) default_command_qos: QosLevel,
+	broker_addrs: Arc<[SocketAddr]>,
+	server_info: Arc<ServerInfo>,
+	stats: tokio::sync::watch::Receiver<ClientStats>,
 	sender: flume::Sender<command::Command>,
+	message_span_sampler: MessageSpanSampler,
+	#[cfg(feature = "trace-payloads")]
+	payload_tracer: Option<PayloadTracer>,
 }
 
 impl HassMqttClient {
+	/// Sends a command to the MQTT thread and waits for its result.
+	///
+	/// The result channel doubles as the cancellation flag of the command: if the
+	/// returned future is dropped before it completes, the command is considered
+	/// cancelled. A cancelled command that has not started running yet is skipped
+	/// by the MQTT thread, and the result of a cancelled command that was already
+	/// running is dropped (which releases anything it holds, like subscriptions).
 	pub(crate) async fn command<T>(&self, cmd: T) -> command::CommandResult<T>
 	where
 		T: command::ClientCommand,
@@ -104,20 +148,116 @@ impl ConnectError {
 }
 
 impl HassMqttClient {
-	#[instrument(
-		level = Level::DEBUG,
-		name = "HassMqttClient::new",
-		skip_all,
-		fields(
-			provider.name = T::NAME,
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(
+			level = Level::DEBUG,
+			name = "HassMqttClient::new",
+			skip_all,
+			fields(
+				provider.name = T::NAME,
+			)
+			err,
 		)
-		err,
 	)]
 	pub async fn new<T: MqttProvider>(options: HassMqttOptions) -> Result<Self, ConnectError> {
-		let (sender, client_id) = inner::spawn::<T>(options)
+		let message_span_sampler = options.mqtt.message_span_sampler.clone();
+		#[cfg(feature = "trace-payloads")]
+		let payload_tracer = options.mqtt.payload_tracer;
+		let discovery_qos = options.discovery_qos;
+		let discovery_retain = options.discovery_retain;
+		let default_command_qos = options.default_command_qos;
+		let inner::SpawnedClient {
+			sender,
+			client_id,
+			broker_addrs,
+			server_info,
+			stats,
+		} = inner::spawn::<T>(options)
 			.await
 			.map_err(ConnectError::new)?;
-		Ok(Self { sender, client_id })
+		Ok(Self {
+			sender,
+			client_id,
+			broker_addrs,
+			server_info: Arc::new(server_info),
+			stats,
+			discovery_qos,
+			discovery_retain,
+			default_command_qos,
+			message_span_sampler,
+			#[cfg(feature = "trace-payloads")]
+			payload_tracer,
+		})
+	}
+
+	/// The addresses the host of the broker resolved to when the client connected, for
+	/// diagnostics. Empty if the provider doesn't resolve the host itself.
+	pub fn broker_addrs(&self) -> &[SocketAddr] {
+		&self.broker_addrs
+	}
+
+	/// The properties the broker announced when the client connected (MQTT5 only).
+	pub fn server_info(&self) -> &ServerInfo {
+		&self.server_info
+	}
+}
+
+/// A snapshot of the health of the connection of a [HassMqttClient] to the broker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientStats {
+	/// How many times the client connected to the broker, including the initial
+	/// connection.
+	pub connects: u64,
+	/// How many times the connection to the broker was lost.
+	pub disconnects: u64,
+	/// Why the connection to the broker was last lost, if it ever was.
+	pub last_disconnect_reason: Option<Arc<str>>,
+	/// Whether the client is connected to the broker.
+	pub connected: bool,
+}
+
+impl HassMqttClient {
+	/// A snapshot of the health of the connection to the broker.
+	pub fn stats(&self) -> ClientStats {
+		self.stats.borrow().clone()
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("timed out after {timeout:?} waiting for the connection to the MQTT broker")]
+pub struct TimeoutError {
+	timeout: Duration,
+}
+
+impl TimeoutError {
+	/// How long the client waited for the connection.
+	pub fn timeout(&self) -> Duration {
+		self.timeout
+	}
+}
+
+impl HassMqttClient {
+	/// Waits until the client is connected to the broker, resolving immediately if it
+	/// already is. The client is connected once [HassMqttClient::new] returns, so this
+	/// only waits while the client is reconnecting after losing the connection.
+	///
+	/// Fails if the client doesn't connect within `timeout`, or if the client was shut
+	/// down (it won't connect again).
+	pub async fn wait_connected(&self, timeout: Duration) -> Result<(), TimeoutError> {
+		let mut stats = self.stats.clone();
+		let wait = async move {
+			while !stats.borrow_and_update().connected {
+				stats.changed().await?;
+			}
+
+			Ok::<_, tokio::sync::watch::error::RecvError>(())
+		};
+
+		match tokio::time::timeout(timeout, wait).await {
+			Ok(Ok(())) => Ok(()),
+			Ok(Err(_)) | Err(_) => Err(TimeoutError { timeout }),
+		}
 	}
 }
 
@@ -137,48 +277,253 @@ impl HassMqttClient {
 }
 
 #[derive(Debug, Error)]
-#[error("failed to publish MQTT message to '{topic}'")]
-pub struct PublishMessageError {
-	topic: Arc<str>,
-	retained: bool,
-	qos: QosLevel,
-	#[cfg_attr(provide_any, backtrace)]
-	source: DynError,
+pub enum PublishMessageError {
+	/// The payload is larger than the maximum packet size announced by the broker,
+	/// so the message was not sent.
+	#[error("payload of MQTT message to '{topic}' is too large ({size} bytes, the broker accepts at most {max} bytes)")]
+	PayloadTooLarge {
+		topic: Arc<str>,
+		size: usize,
+		max: u32,
+	},
+
+	#[error("failed to publish MQTT message to '{topic}'")]
+	Publish {
+		topic: Arc<str>,
+		retained: bool,
+		qos: QosLevel,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
 }
 
 impl HassMqttClient {
-	#[instrument(
-		level = Level::DEBUG,
-		name = "HassMqttClient::publish_message",
-		skip_all,
-		fields(
-			client.id = %self.client_id,
-			message.topic = %topic,
-			message.retained = retained,
-			message.qos = %qos,
-			message.payload.len = payload.len(),
-		))]
+	/// Decides whether the next published message is traced. This is done once, where the
+	/// message enters the client, and the decision is passed down with the message.
+	pub(crate) fn sample_message(&self) -> bool {
+		self.message_span_sampler.sample()
+	}
+
 	pub(crate) async fn publish_message(
 		&self,
 		topic: Arc<str>,
 		payload: Arc<[u8]>,
 		retained: bool,
 		qos: QosLevel,
+		sampled: bool,
 	) -> Result<(), PublishMessageError> {
+		// not using #[instrument] so that unsampled messages skip span creation
+		let span = MessageSpanSampler::span_if(sampled, || {
+			span!(
+				Level::DEBUG,
+				"HassMqttClient::publish_message",
+				client.id = %self.client_id,
+				message.topic = %topic,
+				message.retained = retained,
+				message.qos = %qos,
+				message.payload.len = payload.len(),
+			)
+		});
+
+		#[cfg(feature = "trace-payloads")]
+		if let Some(tracer) = &self.payload_tracer {
+			span.in_scope(|| tracer.trace(&payload));
+		}
+
 		self
-			.command(command::publish(topic.clone(), payload, retained, qos))
+			._publish_message(topic, payload, retained, qos, sampled)
+			.instrument(span)
 			.await
-			.map_err(|source| PublishMessageError {
-				topic,
+	}
+
+	async fn _publish_message(
+		&self,
+		topic: Arc<str>,
+		payload: Arc<[u8]>,
+		retained: bool,
+		qos: QosLevel,
+		sampled: bool,
+	) -> Result<(), PublishMessageError> {
+		self
+			.command(command::publish(
+				topic.clone(),
+				payload,
 				retained,
 				qos,
-				source: DynError::new(source),
+				sampled,
+			))
+			.await
+			.map_err(|source| match source {
+				command::PublishCommandError::PayloadTooLarge { size, max, .. } => {
+					PublishMessageError::PayloadTooLarge { topic, size, max }
+				}
+				source => PublishMessageError::Publish {
+					topic,
+					retained,
+					qos,
+					source: DynError::new(source),
+				},
 			})?;
 
 		Ok(())
 	}
 }
 
+#[derive(Debug, Error)]
+pub enum PublishDiscoveryError {
+	#[error("failed to serialize discovery document for '{topic}'")]
+	Serialize {
+		topic: Arc<str>,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+
+	/// The discovery document failed to validate, so it was not sent.
+	#[error("invalid discovery document for '{topic}'")]
+	Invalid {
+		topic: Arc<str>,
+		source: DiscoveryValidationError,
+	},
+
+	/// Another discovery document published in this session uses the same `unique_id`.
+	/// Home Assistant refuses to create an entity with a `unique_id` that is already in
+	/// use, so the document was not sent.
+	#[error("unique_id '{unique_id}' of the discovery document for '{topic}' is already used by the discovery document for '{existing_topic}'")]
+	DuplicateUniqueId {
+		unique_id: Arc<str>,
+		topic: Arc<str>,
+		existing_topic: Arc<str>,
+	},
+
+	#[error("failed to publish discovery document to '{topic}'")]
+	Publish {
+		topic: Arc<str>,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+}
+
+impl HassMqttClient {
+	/// Publishes a discovery document, making sure its `unique_id` is not
+	/// used by any other discovery document published by this client. Republishing a
+	/// document to the same topic is allowed, and an empty payload releases the
+	/// `unique_id` of the document it removes.
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(
+			level = Level::DEBUG,
+			name = "HassMqttClient::publish_discovery",
+			skip_all,
+			fields(
+				client.id = %self.client_id,
+				discovery.topic = %topic,
+				discovery.unique_id = unique_id.as_deref(),
+				message.retained = retained,
+				message.qos = %qos,
+				message.payload.len = payload.len(),
+			))
+	)]
+	pub(crate) async fn publish_discovery(
+		&self,
+		topic: Arc<str>,
+		unique_id: Option<Arc<str>>,
+		payload: Arc<[u8]>,
+		retained: bool,
+		qos: QosLevel,
+	) -> Result<(), PublishDiscoveryError> {
+		self
+			.command(command::publish_discovery(
+				topic.clone(),
+				unique_id,
+				payload,
+				retained,
+				qos,
+			))
+			.await
+			.map_err(|source| match source {
+				command::PublishDiscoveryCommandError::DuplicateUniqueId {
+					unique_id,
+					existing_topic,
+					..
+				} => PublishDiscoveryError::DuplicateUniqueId {
+					unique_id,
+					topic,
+					existing_topic,
+				},
+				source => PublishDiscoveryError::Publish {
+					topic,
+					source: DynError::new(source),
+				},
+			})
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("failed to republish discovery documents")]
+pub struct RepublishDiscoveryError {
+	#[cfg_attr(provide_any, backtrace)]
+	source: DynError,
+}
+
+impl HassMqttClient {
+	/// Publishes every discovery document published by this client again, for instance
+	/// after Home Assistant restarted and lost the documents that were not retained.
+	/// Documents are republished with the QoS level and retain flag they were first
+	/// published with, and documents that removed their entity are not republished.
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(
+			level = Level::DEBUG,
+			name = "HassMqttClient::republish_discovery",
+			skip_all,
+			fields(
+				client.id = %self.client_id,
+			))
+	)]
+	pub async fn republish_discovery(&self) -> Result<(), RepublishDiscoveryError> {
+		self
+			.command(command::republish_discovery())
+			.await
+			.map_err(|source| RepublishDiscoveryError {
+				source: DynError::new(source),
+			})
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("failed to publish availability")]
+pub struct SetAvailableError {
+	#[cfg_attr(provide_any, backtrace)]
+	source: DynError,
+}
+
+impl HassMqttClient {
+	/// Publishes the `online` (or `offline`) availability of the node, for instance to
+	/// mark the node offline during planned maintenance while staying connected.
+	///
+	/// The `offline` last will is still published by the broker when the connection is
+	/// lost, and while the node is marked offline, the `online` availability is not
+	/// published again after reconnecting.
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(
+			level = Level::DEBUG,
+			name = "HassMqttClient::set_available",
+			skip(self),
+			fields(
+				client.id = %self.client_id,
+			))
+	)]
+	pub async fn set_available(&self, available: bool) -> Result<(), SetAvailableError> {
+		self
+			.command(command::set_available(available))
+			.await
+			.map_err(|source| SetAvailableError {
+				source: DynError::new(source),
+			})
+	}
+}
+
 #[derive(Debug, Error)]
 #[error("failed to subscribe to MQTT topic '{topic}'")]
 pub struct SubscribeError {
@@ -188,23 +533,59 @@ pub struct SubscribeError {
 	source: DynError,
 }
 
+/// Options of a subscription at the broker. Options that are not set are left to the
+/// provider (and broker) defaults.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct SubscribeOptions {
+	pub(crate) no_local: Option<bool>,
+	pub(crate) retain_handling: Option<MqttRetainHandling>,
+}
+
+impl SubscribeOptions {
+	/// Sets the options on a subscribe builder of the provider.
+	pub(crate) fn apply<B: MqttSubscribeBuilder>(&self, mut builder: B) -> B {
+		if let Some(no_local) = self.no_local {
+			builder = builder.no_local(no_local);
+		}
+
+		if let Some(retain_handling) = self.retain_handling {
+			builder = builder.retain_handling(retain_handling);
+		}
+
+		builder
+	}
+}
+
 impl HassMqttClient {
-	#[instrument(
-		level = Level::DEBUG,
-		name = "HassMqttClient::subscribe",
-		skip_all,
-		fields(
-			client.id = %self.client_id,
-			subscription.topic = %topic,
-			subscription.qos,
-		))]
+	/// Subscribes to `topic`. When `command_owner` (`domain.entity_id`) is set, the topic
+	/// is the command topic of that entity, and is checked for collisions with the topics
+	/// of other entities. The `options` are only used if the topic is not subscribed to yet.
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(
+			level = Level::DEBUG,
+			name = "HassMqttClient::subscribe",
+			skip_all,
+			fields(
+				client.id = %self.client_id,
+				subscription.topic = %topic,
+				subscription.qos,
+			))
+	)]
 	pub(crate) async fn subscribe(
 		&self,
 		topic: Arc<str>,
 		qos: QosLevel,
+		command_owner: Option<Arc<str>>,
+		options: SubscribeOptions,
 	) -> Result<Subscription, SubscribeError> {
 		let result = self
-			.command(command::subscribe(topic.clone(), qos))
+			.command(command::subscribe(
+				topic.clone(),
+				qos,
+				command_owner,
+				options,
+			))
 			.await
 			.map_err(|source| SubscribeError {
 				topic: topic.clone(),
@@ -215,10 +596,73 @@ impl HassMqttClient {
 		Ok(Subscription {
 			topic,
 			qos,
+			granted_qos: result.granted_qos,
 			token: result.token,
 			stream: result.receiver.into_stream(),
 		})
 	}
+
+	/// Changes the QoS level of the subscription to `topic`, returning the granted QoS
+	/// level. The subscription is shared by every consumer of the topic.
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(
+			level = Level::DEBUG,
+			name = "HassMqttClient::set_qos",
+			skip_all,
+			fields(
+				client.id = %self.client_id,
+				subscription.topic = %topic,
+				subscription.qos = %qos,
+			))
+	)]
+	pub(crate) async fn set_qos(
+		&self,
+		topic: Arc<str>,
+		qos: QosLevel,
+	) -> Result<QosLevel, SubscribeError> {
+		self
+			.command(command::set_qos(topic.clone(), qos))
+			.await
+			.map_err(|source| SubscribeError {
+				topic,
+				qos,
+				source: DynError::new(source),
+			})
+	}
+}
+
+impl HassMqttClient {
+	/// Registers the state topic of the entity `owner` (`domain.entity_id`), so subscribing
+	/// to it as a command topic is detected as a collision.
+	pub(crate) async fn register_state_topic(&self, topic: Arc<str>, owner: Arc<str>) {
+		// if the MQTT thread is no longer running, nothing can subscribe to the topic anyway
+		let _ = self.command(command::state_topic(topic, owner)).await;
+	}
+}
+
+impl HassMqttClient {
+	/// Lists the topics the client is currently subscribed to, together with the QoS
+	/// granted by the broker. Meant for introspection and debugging.
+	///
+	/// If the MQTT thread is no longer running, there are no subscriptions and an
+	/// empty list is returned.
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(
+			level = Level::DEBUG,
+			name = "HassMqttClient::subscriptions",
+			skip_all,
+			fields(
+				client.id = %self.client_id,
+			))
+	)]
+	pub async fn subscriptions(&self) -> Vec<(Arc<str>, QosLevel)> {
+		self
+			.command(command::subscriptions())
+			.await
+			.unwrap_or_default()
+	}
 }
 
 impl HassMqttOptions {
@@ -233,4 +677,1855 @@ impl HassMqttOptions {
 
 		self.build::<PahoMqtt>().await
 	}
+
+	#[cfg(feature = "rumqttc")]
+	#[cfg_attr(doc_cfg, doc(cfg(feature = "rumqttc")))]
+	pub async fn build_rumqttc(self) -> Result<HassMqttClient, ConnectError> {
+		use hass_mqtt_provider_rumqttc::RumqttcMqtt;
+
+		self.build::<RumqttcMqtt>().await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mock::{MockMessage, MockMqtt};
+	use futures::{FutureExt, StreamExt};
+	use hass_mqtt_proto::{Cover, Light, Sensor};
+	#[cfg(feature = "tracing")]
+	use std::sync::Mutex;
+	use std::time::Duration;
+	#[cfg(feature = "tracing")]
+	use tracing::{span, subscriber::Subscriber};
+	#[cfg(feature = "tracing")]
+	use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+	/// Records the names of all spans created.
+	#[cfg(feature = "tracing")]
+	#[derive(Clone, Default)]
+	struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+	#[cfg(feature = "tracing")]
+	impl SpanNames {
+		fn count(&self, name: &str) -> usize {
+			self
+				.0
+				.lock()
+				.unwrap()
+				.iter()
+				.filter(|n| **n == name)
+				.count()
+		}
+	}
+
+	#[cfg(feature = "tracing")]
+	impl<S: Subscriber> Layer<S> for SpanNames {
+		fn on_new_span(&self, attrs: &span::Attributes<'_>, _id: &span::Id, _ctx: Context<'_, S>) {
+			self.0.lock().unwrap().push(attrs.metadata().name());
+		}
+	}
+
+	/// Records the `message.payload` field of all events.
+	#[cfg(feature = "trace-payloads")]
+	#[derive(Clone, Default)]
+	struct TracedPayloads(Arc<Mutex<Vec<String>>>);
+
+	#[cfg(feature = "trace-payloads")]
+	impl tracing::field::Visit for TracedPayloads {
+		fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+			if field.name() == "message.payload" {
+				self.0.lock().unwrap().push(format!("{value:?}"));
+			}
+		}
+	}
+
+	#[cfg(feature = "trace-payloads")]
+	impl<S: Subscriber> Layer<S> for TracedPayloads {
+		fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+			event.record(&mut self.clone());
+		}
+	}
+
+	#[tokio::test]
+	async fn explicit_client_id_is_used() {
+		let client = HassMqttOptions::new("localhost", "explicit-client-id")
+			.node_id("kitchen")
+			.client_id("custom-client")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+
+		assert_eq!(&*client.client_id, "custom-client");
+		// panics unless the provider created the client with the explicit id
+		MockMqtt::state("custom-client");
+	}
+
+	#[tokio::test]
+	async fn broker_addrs_are_resolved_at_connect() {
+		let client = HassMqttOptions::new("localhost", "broker-addrs")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+
+		assert!(!client.broker_addrs().is_empty());
+		assert!(client
+			.broker_addrs()
+			.iter()
+			.all(|addr| addr.ip().is_loopback()));
+	}
+
+	#[tokio::test]
+	async fn assigned_client_id_is_captured() {
+		MockMqtt::set_server_info(
+			"assigned-client-id",
+			ServerInfo {
+				assigned_client_id: Some("broker-assigned-1".into()),
+				..ServerInfo::default()
+			},
+		);
+		let client = HassMqttOptions::new("localhost", "assigned-client-id")
+			.client_id("assigned-client-id")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+
+		assert_eq!(
+			client.server_info().assigned_client_id.as_deref(),
+			Some("broker-assigned-1")
+		);
+	}
+
+	#[tokio::test]
+	async fn dropped_subscribe_does_not_leave_subscription() {
+		let client = HassMqttOptions::new("localhost", "dropped-subscribe")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		// hold up the MQTT thread so the next command stays queued
+		let gate = state.gate("blocking");
+		let blocking = tokio::spawn({
+			let client = client.clone();
+			async move {
+				client
+					.subscribe(
+						"blocking".into(),
+						QosLevel::AtMostOnce,
+						None,
+						SubscribeOptions::default(),
+					)
+					.await
+					.map(|_| ())
+			}
+		});
+		gate.started().await;
+
+		// queue a subscribe, then drop it before it is run
+		let dropped = client.subscribe(
+			"dropped".into(),
+			QosLevel::AtMostOnce,
+			None,
+			SubscribeOptions::default(),
+		);
+		assert!(dropped.now_or_never().is_none());
+
+		gate.release();
+		blocking.await.unwrap().expect("should subscribe");
+
+		// commands run in order, so once this completes the dropped one has been handled
+		let _kept = client
+			.subscribe(
+				"kept".into(),
+				QosLevel::AtMostOnce,
+				None,
+				SubscribeOptions::default(),
+			)
+			.await
+			.expect("should subscribe");
+
+		assert!(!state.subscribe_log().iter().any(|t| &**t == "dropped"));
+		assert!(!state.subscriptions().iter().any(|(t, _)| &**t == "dropped"));
+	}
+
+	#[tokio::test]
+	async fn publish_larger_than_max_packet_size_fails() {
+		let client = HassMqttOptions::new("localhost", "max-packet-size")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+		state.set_max_packet_size(Some(8));
+
+		client
+			.publish_message(
+				"small".into(),
+				Arc::from(&b"12345678"[..]),
+				false,
+				QosLevel::AtMostOnce,
+				true,
+			)
+			.await
+			.expect("should publish");
+
+		let err = client
+			.publish_message(
+				"large".into(),
+				Arc::from(&b"123456789"[..]),
+				false,
+				QosLevel::AtMostOnce,
+				true,
+			)
+			.await
+			.expect_err("should not publish");
+
+		assert!(matches!(
+			err,
+			PublishMessageError::PayloadTooLarge {
+				size: 9,
+				max: 8,
+				..
+			}
+		));
+		assert!(!state.published().iter().any(|m| m.topic == "large"));
+	}
+
+	#[tokio::test]
+	async fn subscriptions_lists_current_routes() {
+		let client = HassMqttOptions::new("localhost", "list-subscriptions")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+
+		let _first = client
+			.subscribe(
+				"first".into(),
+				QosLevel::AtMostOnce,
+				None,
+				SubscribeOptions::default(),
+			)
+			.await
+			.expect("should subscribe");
+		let _second = client
+			.subscribe(
+				"second".into(),
+				QosLevel::ExactlyOnce,
+				None,
+				SubscribeOptions::default(),
+			)
+			.await
+			.expect("should subscribe");
+
+		let mut subscriptions = client.subscriptions().await;
+		subscriptions.sort_by(|(a, _), (b, _)| a.cmp(b));
+		assert_eq!(
+			subscriptions,
+			vec![
+				(Arc::from("first"), QosLevel::AtMostOnce),
+				(Arc::from("second"), QosLevel::ExactlyOnce),
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn granted_qos_is_threaded_to_command_topic() {
+		let client = HassMqttOptions::new("localhost", "granted-qos")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client
+			.entity("light", "kitchen")
+			.await
+			.expect("should create entity");
+
+		state.grant_qos("kitchen/downgraded", QosLevel::AtMostOnce);
+		let downgraded = entity
+			.command_topic()
+			.topic("kitchen/downgraded")
+			.qos(QosLevel::ExactlyOnce)
+			.await
+			.expect("should subscribe");
+		assert_eq!(downgraded.granted_qos(), QosLevel::AtMostOnce);
+
+		let requested = entity
+			.command_topic()
+			.topic("kitchen/requested")
+			.qos(QosLevel::ExactlyOnce)
+			.await
+			.expect("should subscribe");
+		assert_eq!(requested.granted_qos(), QosLevel::ExactlyOnce);
+	}
+
+	#[tokio::test]
+	async fn set_qos_keeps_receiving_messages() {
+		let client = HassMqttOptions::new("localhost", "set-qos")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client
+			.entity("light", "kitchen")
+			.await
+			.expect("should create entity");
+		let mut command = entity
+			.command_topic()
+			.topic("kitchen/set")
+			.qos(QosLevel::AtMostOnce)
+			.await
+			.expect("should subscribe");
+
+		// hold up the resubscribe, and receive a message while it's in flight
+		let gate = state.gate("kitchen/set");
+		let set_qos = tokio::spawn(async move {
+			let granted_qos = command.set_qos(QosLevel::ExactlyOnce).await;
+			(command, granted_qos)
+		});
+		gate.started().await;
+		state.receive(MockMessage {
+			topic: "kitchen/set".into(),
+			payload: b"ON".to_vec(),
+			..Default::default()
+		});
+		gate.release();
+
+		let (mut command, granted_qos) = set_qos.await.unwrap();
+		assert_eq!(
+			granted_qos.expect("should change qos"),
+			QosLevel::ExactlyOnce
+		);
+		assert_eq!(command.granted_qos(), QosLevel::ExactlyOnce);
+		assert_eq!(
+			state.subscriptions(),
+			vec![(Arc::from("kitchen/set"), QosLevel::ExactlyOnce)]
+		);
+
+		let message = tokio::time::timeout(Duration::from_secs(5), command.next())
+			.await
+			.expect("should receive the message")
+			.expect("should not end");
+		assert_eq!(message.payload(), b"ON");
+	}
+
+	#[tokio::test]
+	async fn object_id_is_used_in_entity_topics() {
+		let client = HassMqttOptions::new("localhost", "object-id")
+			.node_id("node")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+
+		let entity = client
+			.entity("light", "kitchen")
+			.object_id("Ceiling Light")
+			.await
+			.expect("should create entity");
+		let command = entity
+			.command_topic()
+			.await
+			.expect("should subscribe");
+
+		assert_eq!(
+			&*entity.discovery_topic(),
+			"homeassistant/light/node/ceiling-light/config"
+		);
+		assert!(command.topic().ends_with("/node/light/ceiling-light/set"));
+	}
+
+	#[tokio::test]
+	async fn default_command_qos_is_used_without_qos() {
+		let client = HassMqttOptions::new("localhost", "default-command-qos")
+			.default_command_qos(QosLevel::AtLeastOnce)
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client
+			.entity("light", "kitchen")
+			.await
+			.expect("should create entity");
+		let command = entity
+			.command_topic()
+			.topic("kitchen/set")
+			.await
+			.expect("should subscribe");
+		let explicit = entity
+			.command_topic()
+			.topic("kitchen/explicit")
+			.qos(QosLevel::ExactlyOnce)
+			.await
+			.expect("should subscribe");
+
+		assert_eq!(command.granted_qos(), QosLevel::AtLeastOnce);
+		assert_eq!(explicit.granted_qos(), QosLevel::ExactlyOnce);
+		assert_eq!(
+			state.subscriptions(),
+			vec![
+				(Arc::from("kitchen/set"), QosLevel::AtLeastOnce),
+				(Arc::from("kitchen/explicit"), QosLevel::ExactlyOnce),
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn entity_exposes_discovery_topic() {
+		let client = HassMqttOptions::new("localhost", "entity-discovery-topic")
+			.node_id("kitchen")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+
+		let entity = client
+			.entity("light", "ceiling")
+			.await
+			.expect("should create entity");
+		assert_eq!(
+			&*entity.discovery_topic(),
+			"homeassistant/light/kitchen/ceiling/config"
+		);
+
+		let entity = client
+			.entity("light", "pendant")
+			.with_topic("custom/light/pendant/config")
+			.await
+			.expect("should create entity");
+		assert_eq!(&*entity.discovery_topic(), "custom/light/pendant/config");
+	}
+
+	#[tokio::test]
+	async fn invalid_documents_are_not_published() {
+		let client = HassMqttOptions::new("localhost", "invalid-document")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client
+			.entity("cover", "garage")
+			.await
+			.expect("should create entity");
+		let cover = Cover::new()
+			.command_topic("garage/set")
+			.set_position_topic("garage/position/set");
+
+		let err = entity
+			.publish_document(&cover)
+			.await
+			.expect_err("should be invalid");
+		let PublishDiscoveryError::Invalid { ref source, .. } = err else {
+			panic!("expected an invalid document error, got {err:?}");
+		};
+
+		assert_eq!(source.invalidities(), ["PositionTopicMissing"]);
+		assert_eq!(
+			source.to_string(),
+			"discovery document is invalid: PositionTopicMissing"
+		);
+		assert!(state.published().is_empty());
+	}
+
+	#[tokio::test]
+	async fn fan_out_delivers_every_message_to_each_consumer() {
+		let client = HassMqttOptions::new("localhost", "fan-out")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client
+			.entity("light", "kitchen")
+			.await
+			.expect("should create entity");
+		let mut first = entity
+			.command_topic()
+			.topic("kitchen/set")
+			.await
+			.expect("should subscribe");
+		let mut second = first.fan_out().await.expect("should fan out");
+		assert_eq!(second.topic(), first.topic());
+		assert_eq!(state.subscribe_log(), vec![Arc::from("kitchen/set")]);
+
+		for payload in [&b"ON"[..], &b"OFF"[..]] {
+			state.receive(MockMessage {
+				topic: "kitchen/set".into(),
+				payload: payload.to_vec(),
+				..Default::default()
+			});
+		}
+
+		for consumer in [&mut first, &mut second] {
+			for expected in [&b"ON"[..], &b"OFF"[..]] {
+				let message = tokio::time::timeout(Duration::from_secs(5), consumer.next())
+					.await
+					.expect("should route message")
+					.unwrap();
+				assert_eq!(message.payload(), expected);
+			}
+		}
+
+		// dropping one consumer leaves the other subscribed
+		drop(first);
+		state.receive(MockMessage {
+			topic: "kitchen/set".into(),
+			payload: b"ON".to_vec(),
+			..Default::default()
+		});
+		let message = tokio::time::timeout(Duration::from_secs(5), second.next())
+			.await
+			.expect("should route message")
+			.unwrap();
+		assert_eq!(message.payload(), b"ON");
+		assert_eq!(state.subscriptions().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn wildcard_command_topic_receives_matching_messages() {
+		let client = HassMqttOptions::new("localhost", "wildcard-routing")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client
+			.entity("light", "kitchen")
+			.await
+			.expect("should create entity");
+		let mut command = entity
+			.command_topic()
+			.topic("kitchen/+/set")
+			.await
+			.expect("should subscribe");
+
+		for topic in [
+			"kitchen/ceiling/set",
+			"kitchen/ceiling/state",
+			"kitchen/table/set",
+		] {
+			state.receive(MockMessage {
+				topic: topic.into(),
+				payload: b"ON".to_vec(),
+				..Default::default()
+			});
+		}
+
+		for expected in ["kitchen/ceiling/set", "kitchen/table/set"] {
+			let message = tokio::time::timeout(Duration::from_secs(5), command.next())
+				.await
+				.expect("should route message")
+				.unwrap();
+			assert_eq!(message.topic(), expected);
+		}
+	}
+
+	#[tokio::test]
+	async fn decoded_command_topic_yields_errors_for_malformed_payloads() {
+		// `LightState` borrows from the payload, so decode into an owned equivalent
+		#[derive(Debug, PartialEq, serde::Deserialize)]
+		struct LightState {
+			state: hass_mqtt_proto::entity::OnOff,
+			#[serde(default)]
+			brightness: Option<u16>,
+			#[serde(default)]
+			effect: Option<String>,
+		}
+
+		let client = HassMqttOptions::new("localhost", "decoded-commands")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client
+			.entity("light", "kitchen")
+			.await
+			.expect("should create entity");
+		let command = entity
+			.command_topic()
+			.topic("kitchen/set")
+			.await
+			.expect("should subscribe");
+		let decoded = command.decoded::<LightState>();
+
+		for payload in [
+			&br#"{"state":"ON","brightness":128}"#[..],
+			&br#"{"state":"ON","effect":"#[..],
+			&br#"{"state":"OFF","effect":"none"}"#[..],
+		] {
+			state.receive(MockMessage {
+				topic: "kitchen/set".into(),
+				payload: payload.to_vec(),
+				..Default::default()
+			});
+		}
+
+		let decoded = tokio::time::timeout(Duration::from_secs(5), decoded.take(3).collect::<Vec<_>>())
+			.await
+			.expect("should route messages");
+		let [first, malformed, last] = <[_; 3]>::try_from(decoded).unwrap();
+		assert_eq!(
+			first.expect("should decode"),
+			LightState {
+				state: hass_mqtt_proto::entity::OnOff::On,
+				brightness: Some(128),
+				effect: None,
+			}
+		);
+		let err = malformed.expect_err("should fail to decode");
+		assert_eq!(err.topic(), "kitchen/set");
+		assert_eq!(
+			last.expect("should decode"),
+			LightState {
+				state: hass_mqtt_proto::entity::OnOff::Off,
+				brightness: None,
+				effect: Some("none".into()),
+			}
+		);
+	}
+
+	#[tokio::test]
+	async fn wildcard_subscription_is_kept_until_last_command_topic_drops() {
+		let client = HassMqttOptions::new("localhost", "wildcard-unsubscribe")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client
+			.entity("light", "kitchen")
+			.await
+			.expect("should create entity");
+		let first = entity
+			.command_topic()
+			.topic("kitchen/+/set")
+			.await
+			.expect("should subscribe");
+		let second = entity
+			.command_topic()
+			.topic("kitchen/+/set")
+			.await
+			.expect("should subscribe");
+		let all = entity
+			.command_topic()
+			.topic("kitchen/#")
+			.await
+			.expect("should subscribe");
+
+		let subscribed = || {
+			let mut topics = state
+				.subscriptions()
+				.into_iter()
+				.map(|(topic, _)| topic)
+				.collect::<Vec<_>>();
+			topics.sort();
+			topics
+		};
+		let wait_for = |expected: Vec<Arc<str>>| {
+			let subscribed = &subscribed;
+			async move {
+				tokio::time::timeout(Duration::from_secs(5), async {
+					while subscribed() != expected {
+						tokio::time::sleep(Duration::from_millis(10)).await;
+					}
+				})
+				.await
+				.expect("should unsubscribe");
+			}
+		};
+
+		assert_eq!(
+			subscribed(),
+			vec![Arc::from("kitchen/#"), Arc::from("kitchen/+/set")]
+		);
+
+		// the other consumer of the filter keeps the subscription at the broker
+		drop(first);
+		drop(all);
+		wait_for(vec![Arc::from("kitchen/+/set")]).await;
+		assert_eq!(client.subscriptions().await.len(), 1);
+
+		drop(second);
+		wait_for(vec![]).await;
+	}
+
+	#[tokio::test]
+	async fn duplicate_messages_are_flagged() {
+		let client = HassMqttOptions::new("localhost", "duplicate-messages")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client
+			.entity("light", "kitchen")
+			.await
+			.expect("should create entity");
+		let mut command = entity
+			.command_topic()
+			.topic("kitchen/set")
+			.await
+			.expect("should subscribe");
+
+		for dup in [false, true] {
+			state.receive(MockMessage {
+				topic: "kitchen/set".into(),
+				payload: b"ON".to_vec(),
+				qos: Some(QosLevel::AtLeastOnce),
+				dup,
+				..Default::default()
+			});
+		}
+
+		for expected in [false, true] {
+			let message = tokio::time::timeout(Duration::from_secs(5), command.next())
+				.await
+				.expect("should route message")
+				.unwrap();
+			assert_eq!(message.dup(), expected);
+		}
+	}
+
+	#[tokio::test]
+	async fn reconnect_resubscribes_routes() {
+		let client = HassMqttOptions::new("localhost", "reconnect")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let mut command = client
+			.subscribe(
+				"app/light/set".into(),
+				QosLevel::AtLeastOnce,
+				None,
+				SubscribeOptions::default(),
+			)
+			.await
+			.expect("should subscribe");
+		let _plain = client
+			.subscribe(
+				"app/status".into(),
+				QosLevel::ExactlyOnce,
+				None,
+				SubscribeOptions::default(),
+			)
+			.await
+			.expect("should subscribe");
+
+		state.reconnect();
+
+		tokio::time::timeout(Duration::from_secs(5), async {
+			while state.subscriptions().len() < 2 {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("should resubscribe");
+
+		let mut subscriptions = state.subscriptions();
+		subscriptions.sort_by(|(a, _), (b, _)| a.cmp(b));
+		assert_eq!(
+			subscriptions,
+			vec![
+				(Arc::from("app/light/set"), QosLevel::AtLeastOnce),
+				(Arc::from("app/status"), QosLevel::ExactlyOnce),
+			]
+		);
+
+		state.receive(MockMessage {
+			topic: "app/light/set".into(),
+			payload: b"ON".to_vec(),
+			..Default::default()
+		});
+		let message = tokio::time::timeout(Duration::from_secs(5), command.next())
+			.await
+			.expect("should route message")
+			.unwrap();
+		assert_eq!(message.topic(), "app/light/set");
+	}
+
+	#[tokio::test]
+	async fn availability_is_published_with_configured_payloads() {
+		let client = HassMqttOptions::new("localhost", "availability-payloads")
+			.node_id("kitchen")
+			.online_payload("up")
+			.offline_payload("down")
+			.availability_qos(QosLevel::AtLeastOnce)
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		// the online message and the last will
+		assert_eq!(state.online_message.payload, b"up");
+		assert_eq!(state.offline_message.payload, b"down");
+		assert_eq!(state.offline_message.qos, Some(QosLevel::AtLeastOnce));
+		assert!(state.offline_message.retain);
+
+		client
+			.set_available(false)
+			.await
+			.expect("should publish availability");
+		let published = state.published();
+		let message = published.last().unwrap();
+		assert_eq!(&*message.topic, "availability-payloads/kitchen/available");
+		assert_eq!(message.payload, b"down");
+		assert_eq!(message.qos, Some(QosLevel::AtLeastOnce));
+
+		let entity = client.entity("light", "ceiling").await.unwrap();
+		let availability = entity.availability();
+		assert_eq!(availability.payload_available.as_deref(), Some("up"));
+		assert_eq!(availability.payload_not_available.as_deref(), Some("down"));
+	}
+
+	#[tokio::test]
+	async fn availability_is_published_with_configured_qos() {
+		let client = HassMqttOptions::new("localhost", "availability-qos")
+			.availability_qos(QosLevel::AtLeastOnce)
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		// the online message and the last will
+		assert_eq!(state.online_message.qos, Some(QosLevel::AtLeastOnce));
+		assert_eq!(state.offline_message.qos, Some(QosLevel::AtLeastOnce));
+
+		let _command = client
+			.subscribe(
+				"app/light/set".into(),
+				QosLevel::AtMostOnce,
+				None,
+				SubscribeOptions::default(),
+			)
+			.await
+			.expect("should subscribe");
+
+		// the online message published after reconnecting
+		state.reconnect();
+		tokio::time::timeout(Duration::from_secs(5), async {
+			while state.subscribe_log().len() < 2 {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("should resubscribe");
+		client.subscriptions().await;
+
+		let online = state
+			.published()
+			.into_iter()
+			.filter(|m| m.topic == state.online_message.topic)
+			.collect::<Vec<_>>();
+		assert_eq!(online.len(), 1);
+		assert_eq!(online[0].qos, Some(QosLevel::AtLeastOnce));
+	}
+
+	#[tokio::test]
+	async fn reconnect_republishes_online() {
+		for (republish, app) in [(true, "republish-online"), (false, "no-republish-online")] {
+			let client = HassMqttOptions::new("localhost", app)
+				.republish_online_on_reconnect(republish)
+				.build::<MockMqtt>()
+				.await
+				.expect("should connect");
+			let state = MockMqtt::state(&client.client_id);
+			let online = |m: &MockMessage| {
+				m.topic == state.online_message.topic && m.payload == state.online_message.payload
+			};
+
+			let _command = client
+				.subscribe(
+					"app/light/set".into(),
+					QosLevel::AtLeastOnce,
+					None,
+					SubscribeOptions::default(),
+				)
+				.await
+				.expect("should subscribe");
+
+			state.reconnect();
+			state.reconnect();
+
+			// the online message is published right after resubscribing, by the same handler,
+			// which has completed once a later command has run
+			tokio::time::timeout(Duration::from_secs(5), async {
+				while state.subscribe_log().len() < 3 {
+					tokio::time::sleep(Duration::from_millis(10)).await;
+				}
+			})
+			.await
+			.expect("should resubscribe");
+			client.subscriptions().await;
+
+			let published = state.published();
+			let republished = published.iter().filter(|m| online(m)).collect::<Vec<_>>();
+			if republish {
+				assert_eq!(republished.len(), 2);
+				assert!(republished.iter().all(|m| m.retain));
+				assert_eq!(&*republished[0].payload, b"online");
+			} else {
+				assert!(republished.is_empty());
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn duplicate_unique_id_is_rejected() {
+		let client = HassMqttOptions::new("localhost", "duplicate-unique-id")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let first = client
+			.entity("sensor", "first")
+			.await
+			.expect("should create entity");
+		let second = client
+			.entity("sensor", "second")
+			.await
+			.expect("should create entity");
+
+		let document = Sensor::new("app/state").unique_id("temperature");
+		first
+			.publish_discovery(&document)
+			.await
+			.expect("should publish");
+		// republishing the document of the same entity is fine
+		first
+			.publish_discovery(&document)
+			.await
+			.expect("should publish");
+
+		let err = second
+			.publish_discovery(&document)
+			.await
+			.expect_err("should reject duplicate unique_id");
+		assert!(matches!(
+			err,
+			PublishDiscoveryError::DuplicateUniqueId { ref unique_id, .. } if &**unique_id == "temperature"
+		));
+		assert_eq!(
+			state
+				.published()
+				.iter()
+				.filter(|m| m.topic.ends_with("/config"))
+				.count(),
+			2
+		);
+	}
+
+	#[tokio::test]
+	async fn discovery_builder_publishes_payload() {
+		let client = HassMqttOptions::new("localhost", "discovery-builder")
+			.node_id("kitchen")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+		let entity = client.entity("sensor", "temperature").await.unwrap();
+
+		entity
+			.discovery()
+			.publish(&b"{}"[..])
+			.await
+			.expect("should publish");
+		entity
+			.discovery()
+			.retain(false)
+			.qos(QosLevel::ExactlyOnce)
+			.publish(&b"{}"[..])
+			.await
+			.expect("should publish");
+
+		let published = state.published();
+		assert_eq!(
+			published[published.len() - 2..]
+				.iter()
+				.map(|m| (&*m.topic, m.retain, m.qos))
+				.collect::<Vec<_>>(),
+			[
+				(
+					"homeassistant/sensor/kitchen/temperature/config",
+					true,
+					Some(QosLevel::AtLeastOnce)
+				),
+				(
+					"homeassistant/sensor/kitchen/temperature/config",
+					false,
+					Some(QosLevel::ExactlyOnce)
+				),
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn discovery_builder_payload_is_republished() {
+		let client = HassMqttOptions::new("localhost", "discovery-builder-republish")
+			.node_id("kitchen")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+		let entity = client.entity("sensor", "temperature").await.unwrap();
+
+		entity
+			.discovery()
+			.retain(false)
+			.publish(&br#"{"state_topic":"kitchen/temperature"}"#[..])
+			.await
+			.expect("should publish");
+
+		let published = state.published().len();
+		client
+			.republish_discovery()
+			.await
+			.expect("should republish");
+		assert_eq!(
+			state
+				.published()
+				.split_off(published)
+				.iter()
+				.map(|m| (&*m.topic, &*m.payload, m.retain))
+				.collect::<Vec<_>>(),
+			[(
+				"homeassistant/sensor/kitchen/temperature/config",
+				&br#"{"state_topic":"kitchen/temperature"}"#[..],
+				false
+			)]
+		);
+	}
+
+	#[tokio::test]
+	async fn unpublish_removes_discovery_document() {
+		let client = HassMqttOptions::new("localhost", "unpublish")
+			.node_id("kitchen")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let first = client.entity("sensor", "first").await.unwrap();
+		let second = client.entity("sensor", "second").await.unwrap();
+		let document = Sensor::new("app/state").unique_id("temperature");
+		first
+			.publish_discovery(&document)
+			.await
+			.expect("should publish");
+
+		first.unpublish().await.expect("should unpublish");
+		let published = state.published();
+		let message = published.last().unwrap();
+		assert_eq!(&*message.topic, "homeassistant/sensor/kitchen/first/config");
+		assert!(message.payload.is_empty());
+		assert!(message.retain);
+		assert_eq!(message.qos, Some(QosLevel::AtLeastOnce));
+
+		// the unique_id is released, and the document is not republished
+		second
+			.publish_discovery(&document)
+			.await
+			.expect("should publish with released unique_id");
+		let published = state.published().len();
+		client
+			.republish_discovery()
+			.await
+			.expect("should republish");
+		assert_eq!(
+			state
+				.published()
+				.split_off(published)
+				.iter()
+				.map(|m| &*m.topic)
+				.collect::<Vec<_>>(),
+			["homeassistant/sensor/kitchen/second/config"]
+		);
+	}
+
+	#[tokio::test]
+	async fn activate_subscribes_before_publishing_discovery() {
+		let client = HassMqttOptions::new("localhost", "activate")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client.entity("light", "kitchen").await.unwrap();
+		let gate = state.gate("app/kitchen/set");
+		let activate = tokio::spawn(async move {
+			let document = Light::new("app/kitchen/set");
+			entity
+				.activate(&document, QosLevel::AtLeastOnce)
+				.await
+				.map(|command_topic| command_topic.granted_qos())
+		});
+
+		gate.started().await;
+		assert!(!state
+			.published()
+			.iter()
+			.any(|m| m.topic.ends_with("/config")));
+
+		gate.release();
+		let granted_qos = activate.await.unwrap().expect("should activate the entity");
+		assert_eq!(granted_qos, QosLevel::AtLeastOnce);
+
+		let discovery = state
+			.published()
+			.into_iter()
+			.find(|m| m.topic.ends_with("/config"))
+			.expect("should publish the discovery document");
+		assert_eq!(
+			serde_json::from_slice::<serde_json::Value>(&discovery.payload).unwrap()["command_topic"],
+			"app/kitchen/set"
+		);
+	}
+
+	#[tokio::test]
+	async fn activate_requires_command_topic() {
+		let client = HassMqttOptions::new("localhost", "activate-without-command-topic")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client.entity("sensor", "temperature").await.unwrap();
+		let result = entity
+			.activate(&Sensor::new("app/temperature"), QosLevel::AtMostOnce)
+			.await;
+		assert!(matches!(
+			result,
+			Err(crate::ActivateEntityError::MissingCommandTopic { .. })
+		));
+		assert!(state
+			.published()
+			.iter()
+			.all(|m| !m.topic.ends_with("/config")));
+	}
+
+	#[tokio::test]
+	async fn republish_discovery_publishes_documents_again() {
+		let client = HassMqttOptions::new("localhost", "republish-discovery")
+			.node_id("kitchen")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let ceiling = client.entity("light", "ceiling").await.unwrap();
+		let pendant = client.entity("light", "pendant").await.unwrap();
+		let removed = client.entity("light", "removed").await.unwrap();
+		ceiling
+			.publish_discovery(&Light::new("kitchen/ceiling/set"))
+			.await
+			.expect("should publish");
+		pendant
+			.publish_discovery(&Light::new("kitchen/pendant/set"))
+			.retain(false)
+			.await
+			.expect("should publish");
+		removed
+			.publish_discovery(&Light::new("kitchen/removed/set"))
+			.await
+			.expect("should publish");
+		// an empty document removes the entity
+		client
+			.publish_discovery(
+				removed.discovery_topic(),
+				None,
+				Arc::from(&b""[..]),
+				true,
+				QosLevel::AtLeastOnce,
+			)
+			.await
+			.expect("should remove");
+
+		let published = state.published().len();
+		client
+			.republish_discovery()
+			.await
+			.expect("should republish");
+
+		let mut republished = state.published().split_off(published);
+		republished.sort_by(|a, b| a.topic.cmp(&b.topic));
+		assert_eq!(
+			republished
+				.iter()
+				.map(|m| (&*m.topic, m.retain))
+				.collect::<Vec<_>>(),
+			vec![
+				("homeassistant/light/kitchen/ceiling/config", true),
+				("homeassistant/light/kitchen/pendant/config", false),
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn set_available_publishes_availability() {
+		let client = HassMqttOptions::new("localhost", "set-available")
+			.node_id("kitchen")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		for (available, payload) in [(false, &b"offline"[..]), (true, &b"online"[..])] {
+			client
+				.set_available(available)
+				.await
+				.expect("should publish availability");
+
+			let published = state.published();
+			let message = published.last().unwrap();
+			assert_eq!(&*message.topic, "set-available/kitchen/available");
+			assert_eq!(message.payload, payload);
+			assert!(message.retain);
+			assert_eq!(message.qos, Some(QosLevel::ExactlyOnce));
+		}
+	}
+
+	#[tokio::test]
+	async fn reconnect_keeps_node_offline() {
+		let client = HassMqttOptions::new("localhost", "reconnect-offline")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let _command = client
+			.subscribe(
+				"app/light/set".into(),
+				QosLevel::AtLeastOnce,
+				None,
+				SubscribeOptions::default(),
+			)
+			.await
+			.expect("should subscribe");
+		client
+			.set_available(false)
+			.await
+			.expect("should publish availability");
+		let published = state.published().len();
+
+		state.reconnect();
+		// the online message would be published right after resubscribing
+		tokio::time::timeout(Duration::from_secs(5), async {
+			while state.subscribe_log().len() < 2 {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("should resubscribe");
+		client.subscriptions().await;
+		assert_eq!(state.published().len(), published);
+	}
+
+	#[tokio::test]
+	async fn wait_connected_waits_for_reconnect() {
+		let client = HassMqttOptions::new("localhost", "wait-connected")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		client
+			.wait_connected(Duration::from_millis(50))
+			.await
+			.expect("should be connected");
+
+		state.lose_connection();
+		let mut stats = client.stats.clone();
+		while stats.borrow_and_update().connected {
+			stats.changed().await.unwrap();
+		}
+		let err = client
+			.wait_connected(Duration::from_millis(50))
+			.await
+			.expect_err("should time out");
+		assert_eq!(err.timeout(), Duration::from_millis(50));
+
+		let wait = tokio::spawn({
+			let client = client.clone();
+			async move { client.wait_connected(Duration::from_secs(5)).await }
+		});
+		state.connect();
+		wait.await.unwrap().expect("should resolve once connected");
+	}
+
+	#[tokio::test]
+	async fn stats_track_connects_and_disconnects() {
+		let client = HassMqttOptions::new("localhost", "stats")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		assert_eq!(
+			client.stats(),
+			ClientStats {
+				connects: 1,
+				disconnects: 0,
+				last_disconnect_reason: None,
+				connected: true,
+			}
+		);
+
+		state.reconnect();
+		state.disconnect("server shutting down");
+		let mut stats = client.stats.clone();
+		while stats.borrow_and_update().disconnects < 2 {
+			stats.changed().await.unwrap();
+		}
+		assert_eq!(
+			client.stats(),
+			ClientStats {
+				connects: 2,
+				disconnects: 2,
+				last_disconnect_reason: Some(Arc::from("server shutting down")),
+				connected: false,
+			}
+		);
+
+		state.connect();
+		client
+			.wait_connected(Duration::from_secs(5))
+			.await
+			.expect("should reconnect");
+		assert_eq!(client.stats().connects, 3);
+		assert!(client.stats().connected);
+	}
+
+	#[tokio::test]
+	async fn discovery_qos_is_used_by_default() {
+		let client = HassMqttOptions::new("localhost", "discovery-qos")
+			.discovery_qos(QosLevel::ExactlyOnce)
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let default = client
+			.entity("sensor", "default")
+			.await
+			.expect("should create entity");
+		let explicit = client
+			.entity("sensor", "explicit")
+			.await
+			.expect("should create entity");
+
+		default
+			.publish_discovery(&Sensor::new("app/default"))
+			.await
+			.expect("should publish");
+		explicit
+			.publish_discovery(&Sensor::new("app/explicit"))
+			.qos(QosLevel::AtMostOnce)
+			.await
+			.expect("should publish");
+
+		let discovery = state
+			.published()
+			.into_iter()
+			.filter(|m| m.topic.ends_with("/config"))
+			.map(|m| (m.topic, m.qos, m.retain))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			discovery,
+			vec![
+				(
+					"homeassistant/sensor/default/default/config".to_string(),
+					Some(QosLevel::ExactlyOnce),
+					true
+				),
+				(
+					"homeassistant/sensor/default/explicit/config".to_string(),
+					Some(QosLevel::AtMostOnce),
+					true
+				),
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn discovery_can_be_published_non_retained() {
+		let client = HassMqttOptions::new("localhost", "discovery-retain")
+			.discovery_retain(false)
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let default = client
+			.entity("sensor", "default")
+			.await
+			.expect("should create entity");
+		let explicit = client
+			.entity("sensor", "explicit")
+			.await
+			.expect("should create entity");
+
+		default
+			.publish_discovery(&Sensor::new("app/default"))
+			.await
+			.expect("should publish");
+		explicit
+			.publish_discovery(&Sensor::new("app/explicit"))
+			.retain(true)
+			.await
+			.expect("should publish");
+
+		let discovery = state
+			.published()
+			.into_iter()
+			.filter(|m| m.topic.ends_with("/config"))
+			.map(|m| (m.topic, m.retain))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			discovery,
+			vec![
+				(
+					"homeassistant/sensor/default/default/config".to_string(),
+					false
+				),
+				(
+					"homeassistant/sensor/default/explicit/config".to_string(),
+					true
+				),
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn publish_value_serializes_value_as_json() {
+		let client = HassMqttOptions::new("localhost", "publish-value")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client
+			.entity("sensor", "temperature")
+			.await
+			.expect("should create entity");
+		let state_topic = entity.state_topic().await.expect("should create topic");
+		state_topic
+			.publish_value(&21.5f64, QosLevel::AtLeastOnce)
+			.await
+			.expect("should publish");
+
+		let published = state.published();
+		let message = published
+			.iter()
+			.find(|m| *m.topic == *state_topic.topic())
+			.expect("should publish to state topic");
+		assert_eq!(message.payload, b"21.5");
+		assert!(message.retain);
+		assert_eq!(message.qos, Some(QosLevel::AtLeastOnce));
+	}
+
+	#[test]
+	fn payload_arc_shares_the_payload() {
+		let message = Message {
+			topic: "app/light/set".into(),
+			payload: Arc::from(&b"ON"[..]),
+			retained: false,
+			dup: false,
+			span: Span::none(),
+		};
+
+		let payload = message.payload_arc();
+		assert!(Arc::ptr_eq(&payload, &message.payload));
+		assert_eq!(Arc::strong_count(&payload), 2);
+
+		let owned = message.into_payload();
+		assert!(Arc::ptr_eq(&payload, &owned));
+		assert_eq!(Arc::strong_count(&payload), 2);
+		assert_eq!(&*owned, b"ON");
+	}
+
+	#[tokio::test]
+	async fn thread_is_configured_from_options() {
+		let client = HassMqttOptions::new("localhost", "thread")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+		assert_eq!(state.thread_name.as_deref(), Some("mqtt-thread-hass"));
+
+		let client = HassMqttOptions::new("localhost", "custom-thread")
+			.thread_name_prefix("worker")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+		assert_eq!(
+			state.thread_name.as_deref(),
+			Some("worker-custom-thread-hass")
+		);
+	}
+
+	#[test]
+	fn thread_stack_size_is_applied() {
+		// more than the 2 MiB spawned threads get by default
+		const STACK_USE: usize = 8 * 1024 * 1024;
+
+		let options = HassMqttOptions::new("localhost", "custom-thread")
+			.thread_name_prefix("worker")
+			.thread_stack_size(4 * STACK_USE);
+		let name = inner::thread_builder(&options)
+			.spawn(|| {
+				let buffer = std::hint::black_box([1u8; STACK_USE]);
+				assert_eq!(buffer[STACK_USE - 1], 1);
+				std::thread::current().name().map(String::from)
+			})
+			.expect("should spawn thread")
+			.join()
+			.expect("should not overflow the stack");
+
+		assert_eq!(name.as_deref(), Some("worker-custom-thread-hass"));
+	}
+
+	/// The messages of an error and all its sources.
+	fn error_chain(error: &dyn std::error::Error) -> Vec<String> {
+		let mut chain = vec![error.to_string()];
+		let mut source = error.source();
+		while let Some(error) = source {
+			chain.push(error.to_string());
+			source = error.source();
+		}
+		chain
+	}
+
+	#[tokio::test]
+	async fn command_topic_forwards_subscribe_options() {
+		let client = HassMqttOptions::new("localhost", "subscribe-options")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client.entity("light", "kitchen").await.unwrap();
+		let _command = entity
+			.command_topic()
+			.topic("kitchen/set")
+			.retain_handling(crate::MqttRetainHandling::SendRetainedOnNew)
+			.no_local(true)
+			.await
+			.expect("should subscribe");
+
+		let expected = Some((
+			Some(true),
+			Some(crate::MqttRetainHandling::SendRetainedOnNew),
+		));
+		assert_eq!(state.subscribe_options("kitchen/set"), expected);
+
+		// the options are used again when resubscribing after a reconnect
+		state.reconnect();
+		tokio::time::timeout(Duration::from_secs(5), async {
+			while state.subscribe_log().len() < 2 {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("should resubscribe");
+		assert_eq!(state.subscribe_options("kitchen/set"), expected);
+
+		let entity = client.entity("light", "hallway").await.unwrap();
+		let _command = entity
+			.command_topic()
+			.topic("hallway/set")
+			.await
+			.expect("should subscribe");
+		assert_eq!(state.subscribe_options("hallway/set"), Some((None, None)));
+	}
+
+	#[tokio::test]
+	async fn publish_times_out_on_unresponsive_broker() {
+		let client = HassMqttOptions::new("localhost", "publish-timeout")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client.entity("sensor", "temperature").await.unwrap();
+		let state_topic = entity.state_topic().await.unwrap();
+		let gate = state.gate(state_topic.topic());
+
+		let result = state_topic
+			.publish(&b"21.5"[..], true, QosLevel::AtLeastOnce)
+			.timeout(Duration::from_millis(50))
+			.await;
+		assert!(matches!(
+			result,
+			Err(crate::EntityPublishError::TimedOut { timeout, .. }) if timeout == Duration::from_millis(50)
+		));
+
+		gate.release();
+	}
+
+	#[tokio::test]
+	async fn retain_is_dropped_when_broker_does_not_support_it() {
+		MockMqtt::set_server_info(
+			"retain-unavailable",
+			ServerInfo {
+				retain_available: Some(false),
+				..ServerInfo::default()
+			},
+		);
+		let client = HassMqttOptions::new("localhost", "retain-unavailable")
+			.client_id("retain-unavailable")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client.entity("sensor", "temperature").await.unwrap();
+		let state_topic = entity.state_topic().await.unwrap();
+		state_topic
+			.publish(&b"21.5"[..], true, QosLevel::AtLeastOnce)
+			.await
+			.expect("should publish");
+
+		let published = state
+			.published()
+			.into_iter()
+			.find(|m| *m.topic == *state_topic.topic())
+			.expect("state should be published");
+		assert!(!published.retain);
+	}
+
+	#[tokio::test]
+	async fn retain_availability_is_refreshed_on_reconnect() {
+		let client = HassMqttOptions::new("localhost", "retain-refresh")
+			.client_id("retain-refresh")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		// the broker reconnected to doesn't support retained messages
+		MockMqtt::set_server_info(
+			"retain-refresh",
+			ServerInfo {
+				retain_available: Some(false),
+				..ServerInfo::default()
+			},
+		);
+		state.reconnect();
+		let online = tokio::time::timeout(Duration::from_secs(5), async {
+			loop {
+				if let Some(online) = state
+					.published()
+					.into_iter()
+					.find(|m| m.topic == state.online_message.topic)
+				{
+					break online;
+				}
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("should republish online");
+		assert!(!online.retain);
+
+		let entity = client.entity("sensor", "temperature").await.unwrap();
+		let state_topic = entity.state_topic().await.unwrap();
+		state_topic
+			.publish(&b"21.5"[..], true, QosLevel::AtLeastOnce)
+			.await
+			.expect("should publish");
+
+		let published = state
+			.published()
+			.into_iter()
+			.find(|m| *m.topic == *state_topic.topic())
+			.expect("state should be published");
+		assert!(!published.retain);
+	}
+
+	#[tokio::test]
+	async fn subscribe_times_out_on_unresponsive_broker() {
+		let client = HassMqttOptions::new("localhost", "subscribe-timeout")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let entity = client.entity("light", "kitchen").await.unwrap();
+		let gate = state.gate("kitchen/set");
+
+		let result = entity
+			.command_topic()
+			.topic("kitchen/set")
+			.timeout(Duration::from_millis(50))
+			.await;
+		assert!(matches!(
+			result,
+			Err(crate::EntitySubscribeError::TimedOut { ref topic, .. }) if &**topic == "kitchen/set"
+		));
+
+		gate.release();
+	}
+
+	#[tokio::test]
+	async fn command_topic_collisions_are_rejected() {
+		let client = HassMqttOptions::new("localhost", "topic-collisions")
+			.topic_collisions(crate::TopicCollisions::Reject)
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+
+		let first = client
+			.entity("light", "first")
+			.await
+			.expect("should create entity");
+		let second = client
+			.entity("light", "second")
+			.await
+			.expect("should create entity");
+
+		let command = first
+			.command_topic()
+			.topic("lights/set")
+			.await
+			.expect("should subscribe");
+		let err = match second.command_topic().topic("lights/set").await {
+			Ok(_) => panic!("should reject the command topic of another entity"),
+			Err(err) => err,
+		};
+		assert!(error_chain(&err)
+			.iter()
+			.any(|m| m == "topic 'lights/set' is already the command topic of entity light.first"));
+
+		// more consumers of the command topic of the same entity are fine
+		command.fan_out().await.expect("should fan out");
+
+		let state = first.state_topic().await.unwrap();
+		let err = match second.command_topic().topic(state.topic()).await {
+			Ok(_) => panic!("should reject the state topic of an entity"),
+			Err(err) => err,
+		};
+		assert!(error_chain(&err)
+			.iter()
+			.any(|m| m.ends_with("is already the state topic of entity light.first")));
+	}
+
+	#[tokio::test]
+	async fn command_topic_collisions_are_allowed_by_default() {
+		let client = HassMqttOptions::new("localhost", "topic-collisions-warn")
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+
+		let first = client.entity("light", "first").await.unwrap();
+		let second = client.entity("light", "second").await.unwrap();
+		let _first = first
+			.command_topic()
+			.topic("lights/set")
+			.await
+			.expect("should subscribe");
+		let _second = second
+			.command_topic()
+			.topic("lights/set")
+			.await
+			.expect("should subscribe, with a warning");
+	}
+
+	#[cfg(feature = "tracing")]
+	#[tokio::test]
+	async fn unsampled_messages_skip_spans() {
+		let spans = SpanNames::default();
+		let _guard =
+			tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+		for (rate, app) in [(0, "span-sample-rate-0"), (1, "span-sample-rate-1")] {
+			let client = HassMqttOptions::new("localhost", app)
+				.message_span_sample_rate(rate)
+				.build::<MockMqtt>()
+				.await
+				.expect("should connect");
+
+			let entity = client.entity("sensor", "temperature").await.unwrap();
+			let state_topic = entity.state_topic().await.unwrap();
+			for _ in 0..3 {
+				state_topic
+					.publish(&b"21.5"[..], false, QosLevel::AtMostOnce)
+					.await
+					.expect("should publish");
+			}
+
+			let expected = if rate == 0 { 0 } else { 3 };
+			assert_eq!(spans.count("StateTopic::publish"), expected);
+			assert_eq!(spans.count("HassMqttClient::publish_message"), expected);
+			spans.0.lock().unwrap().clear();
+		}
+	}
+
+	#[cfg(feature = "tracing")]
+	#[tokio::test]
+	async fn spans_of_a_message_are_sampled_together() {
+		let spans = SpanNames::default();
+		let _guard =
+			tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+		let client = HassMqttOptions::new("localhost", "span-sample-rate-2")
+			.message_span_sample_rate(2)
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+		let entity = client.entity("sensor", "temperature").await.unwrap();
+		let state_topic = entity.state_topic().await.unwrap();
+
+		let mut traced = 0;
+		for _ in 0..4 {
+			spans.0.lock().unwrap().clear();
+			state_topic
+				.publish(&b"21.5"[..], false, QosLevel::AtMostOnce)
+				.await
+				.expect("should publish");
+
+			// the provider creates its span on the client thread, so it reports its decision
+			let provider_sampled = *state.published_sampled().last().unwrap();
+			let expected = usize::from(provider_sampled);
+			assert_eq!(spans.count("StateTopic::publish"), expected);
+			assert_eq!(spans.count("HassMqttClient::publish_message"), expected);
+			traced += expected;
+		}
+
+		assert_eq!(traced, 2);
+	}
+
+	#[cfg(feature = "trace-payloads")]
+	#[tokio::test]
+	async fn payloads_are_traced_when_enabled() {
+		let payloads = TracedPayloads::default();
+		let _guard =
+			tracing::subscriber::set_default(tracing_subscriber::registry().with(payloads.clone()));
+
+		for (max_bytes, app) in [(None, "trace-payloads-off"), (Some(4), "trace-payloads-on")] {
+			let mut options = HassMqttOptions::new("localhost", app);
+			if let Some(max_bytes) = max_bytes {
+				options = options.trace_payloads(max_bytes);
+			}
+
+			let client = options.build::<MockMqtt>().await.expect("should connect");
+			let entity = client.entity("sensor", "temperature").await.unwrap();
+			let state_topic = entity.state_topic().await.unwrap();
+			state_topic
+				.publish(&b"21.5 C"[..], false, QosLevel::AtMostOnce)
+				.await
+				.expect("should publish");
+
+			let traced = std::mem::take(&mut *payloads.0.lock().unwrap());
+			match max_bytes {
+				None => assert!(traced.is_empty(), "traced {traced:?}"),
+				Some(_) => assert!(traced.iter().any(|p| p == "21.5"), "traced {traced:?}"),
+			}
+		}
+	}
 }
@@ -1,17 +1,30 @@
 mod entity;
 mod publish;
+mod publish_discovery;
+mod republish_discovery;
+mod set_available;
+mod set_qos;
+mod state_topic;
 mod subscribe;
+mod subscriptions;
 
-use super::{inner::InnerClient, QosLevel};
+use super::{inner::InnerClient, QosLevel, SubscribeOptions};
+use crate::trace::event;
 use async_trait::async_trait;
 use hass_mqtt_provider::MqttClient;
 use std::sync::Arc;
 use tokio::sync::oneshot;
-use tracing::{Instrument, Span};
+use tracing::{Instrument, Level, Span};
 
 pub(super) use entity::EntityCommand;
-pub(super) use publish::PublishCommand;
+pub(super) use publish::{PublishCommand, PublishCommandError};
+pub(super) use publish_discovery::{PublishDiscoveryCommand, PublishDiscoveryCommandError};
+pub(super) use republish_discovery::RepublishDiscoveryCommand;
+pub(super) use set_available::SetAvailableCommand;
+pub(super) use set_qos::SetQosCommand;
+pub(super) use state_topic::StateTopicCommand;
 pub(super) use subscribe::SubscribeCommand;
+pub(super) use subscriptions::SubscriptionsCommand;
 
 #[async_trait(?Send)]
 pub(crate) trait ClientCommand {
@@ -70,10 +83,17 @@ macro_rules! commands {
 				match self {
 					$(
 						Self::$variant(command, tx, span) => {
-							let result = command.run(client).instrument(span).await;
+							if tx.is_closed() {
+								event!(parent: &span, Level::DEBUG, command = stringify!($variant), "command cancelled before it was run");
+								return;
+							}
+
+							let result = command.run(client).instrument(span.clone()).await;
 
-							if let Err(_err) = tx.send(result) {
-								// TODO: log
+							if tx.send(result).is_err() {
+								// the result is dropped here, which releases anything it holds
+								// (for instance subscription tokens)
+								event!(parent: &span, Level::DEBUG, command = stringify!($variant), "command cancelled while it was running");
 							}
 						}
 					)*
@@ -87,16 +107,23 @@ commands! {
 	pub(crate) enum Command {
 		EntityCommand,
 		PublishCommand,
+		PublishDiscoveryCommand,
+		RepublishDiscoveryCommand,
+		SetAvailableCommand,
+		SetQosCommand,
+		StateTopicCommand,
 		SubscribeCommand,
+		SubscriptionsCommand,
 	}
 }
 
 pub(crate) fn entity(
 	domain: Arc<str>,
 	entity_id: Arc<str>,
+	object_id: Option<Arc<str>>,
 	topic: Option<Arc<str>>,
 ) -> EntityCommand {
-	EntityCommand::new(domain, entity_id, topic)
+	EntityCommand::new(domain, entity_id, object_id, topic)
 }
 
 pub(crate) fn publish(
@@ -104,10 +131,46 @@ pub(crate) fn publish(
 	payload: Arc<[u8]>,
 	retained: bool,
 	qos: QosLevel,
+	sampled: bool,
 ) -> PublishCommand {
-	PublishCommand::new(topic, payload, retained, qos)
+	PublishCommand::new(topic, payload, retained, qos, sampled)
+}
+
+pub(crate) fn publish_discovery(
+	topic: Arc<str>,
+	unique_id: Option<Arc<str>>,
+	payload: Arc<[u8]>,
+	retained: bool,
+	qos: QosLevel,
+) -> PublishDiscoveryCommand {
+	PublishDiscoveryCommand::new(topic, unique_id, payload, retained, qos)
+}
+
+pub(crate) fn republish_discovery() -> RepublishDiscoveryCommand {
+	RepublishDiscoveryCommand::new()
+}
+
+pub(crate) fn set_available(available: bool) -> SetAvailableCommand {
+	SetAvailableCommand::new(available)
+}
+
+pub(crate) fn set_qos(topic: Arc<str>, qos: QosLevel) -> SetQosCommand {
+	SetQosCommand::new(topic, qos)
+}
+
+pub(crate) fn state_topic(topic: Arc<str>, owner: Arc<str>) -> StateTopicCommand {
+	StateTopicCommand::new(topic, owner)
+}
+
+pub(crate) fn subscribe(
+	topic: Arc<str>,
+	qos: QosLevel,
+	command_owner: Option<Arc<str>>,
+	options: SubscribeOptions,
+) -> SubscribeCommand {
+	SubscribeCommand::new(topic, qos, command_owner, options)
 }
 
-pub(crate) fn subscribe(topic: Arc<str>, qos: QosLevel) -> SubscribeCommand {
-	SubscribeCommand::new(topic, qos)
+pub(crate) fn subscriptions() -> SubscriptionsCommand {
+	SubscriptionsCommand::new()
 }
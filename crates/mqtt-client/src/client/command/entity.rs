@@ -1,5 +1,8 @@
 use super::{ClientCommand, InnerClient};
-use crate::topics::EntityTopicsConfig;
+use crate::{
+	client::{subscription::SubscriptionToken, QosLevel, SubscribeOptions},
+	topics::EntityTopicsConfig,
+};
 use async_trait::async_trait;
 use hass_dyn_error::DynError;
 use hass_mqtt_provider::MqttClient;
@@ -9,14 +12,21 @@ use thiserror::Error;
 pub(crate) struct EntityCommand {
 	domain: Arc<str>,
 	entity_id: Arc<str>,
+	object_id: Option<Arc<str>>,
 	topic: Option<Arc<str>>,
 }
 
 impl EntityCommand {
-	pub(crate) fn new(domain: Arc<str>, entity_id: Arc<str>, topic: Option<Arc<str>>) -> Self {
+	pub(crate) fn new(
+		domain: Arc<str>,
+		entity_id: Arc<str>,
+		object_id: Option<Arc<str>>,
+		topic: Option<Arc<str>>,
+	) -> Self {
 		Self {
 			domain,
 			entity_id,
+			object_id,
 			topic,
 		}
 	}
@@ -24,6 +34,7 @@ impl EntityCommand {
 
 pub(crate) struct EntityCommandResult {
 	pub topics: EntityTopicsConfig,
+	pub discovery_subscription: Option<SubscriptionToken>,
 }
 
 #[derive(Debug, Error)]
@@ -44,12 +55,40 @@ impl ClientCommand for EntityCommand {
 		&self,
 		client: &mut InnerClient<T>,
 	) -> Result<Self::Result, Self::Error> {
-		let topics_config = client
-			.topics
-			.entity(&self.domain, &self.entity_id, self.topic.clone());
+		let topics_config = client.topics.entity_with_discovery_topic(
+			&self.domain,
+			&self.entity_id,
+			self.object_id.as_deref(),
+			self.topic.clone(),
+		);
+
+		let discovery_subscription = match client.retained_discovery_validation.clone() {
+			None => None,
+			Some(validation) => {
+				let (sender, receiver) = flume::unbounded();
+				let subscribed = client
+					.subscribe_route(
+						topics_config.discovery_topic(),
+						QosLevel::AtMostOnce,
+						None,
+						SubscribeOptions::default(),
+						sender,
+					)
+					.await
+					.map_err(|source| self.create_error(source))?;
+
+				tokio::task::spawn_local(validation.watch(
+					self.domain.clone(),
+					self.entity_id.clone(),
+					receiver,
+				));
+				Some(subscribed.key)
+			}
+		};
 
 		Ok(EntityCommandResult {
 			topics: topics_config,
+			discovery_subscription,
 		})
 	}
 
@@ -1,37 +1,60 @@
 use super::{ClientCommand, InnerClient};
 use crate::client::QosLevel;
+use crate::trace::event;
 use async_trait::async_trait;
 use hass_dyn_error::DynError;
-use hass_mqtt_provider::{MqttBuildableMessage, MqttClient, MqttMessageBuilder};
+use hass_mqtt_provider::{
+	MqttBuildableMessage, MqttClient, MqttMessageBuilder, MqttPublishBuilder,
+};
 use std::sync::Arc;
 use thiserror::Error;
+use tracing::Level;
 
+#[derive(Clone)]
 pub(crate) struct PublishCommand {
 	topic: Arc<str>,
 	payload: Arc<[u8]>,
 	retained: bool,
 	qos: QosLevel,
+	/// Whether the message is traced, as decided by the client when it was published.
+	sampled: bool,
 }
 
 impl PublishCommand {
-	pub fn new(topic: Arc<str>, payload: Arc<[u8]>, retained: bool, qos: QosLevel) -> Self {
+	pub fn new(
+		topic: Arc<str>,
+		payload: Arc<[u8]>,
+		retained: bool,
+		qos: QosLevel,
+		sampled: bool,
+	) -> Self {
 		Self {
 			topic,
 			payload,
 			retained,
 			qos,
+			sampled,
 		}
 	}
 }
 
 #[derive(Debug, Error)]
-#[error("failed to publish MQTT message for topic '{topic}'")]
-pub(crate) struct PublishCommandError {
-	topic: Arc<str>,
-	retained: bool,
-	qos: QosLevel,
-	#[cfg_attr(provide_any, backtrace)]
-	source: DynError,
+pub(crate) enum PublishCommandError {
+	#[error("payload of MQTT message for topic '{topic}' is too large ({size} bytes, the broker accepts at most {max} bytes)")]
+	PayloadTooLarge {
+		topic: Arc<str>,
+		size: usize,
+		max: u32,
+	},
+
+	#[error("failed to publish MQTT message for topic '{topic}'")]
+	Publish {
+		topic: Arc<str>,
+		retained: bool,
+		qos: QosLevel,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
 }
 
 #[async_trait(?Send)]
@@ -43,10 +66,30 @@ impl ClientCommand for PublishCommand {
 		&self,
 		client: &mut InnerClient<T>,
 	) -> Result<Self::Result, Self::Error> {
+		if let Some(max) = client.client.max_packet_size() {
+			let size = self.payload.len();
+			if size > max as usize {
+				return Err(PublishCommandError::PayloadTooLarge {
+					topic: self.topic.clone(),
+					size,
+					max,
+				});
+			}
+		}
+
+		let retain = self.retained && client.retain_available();
+		if self.retained && !retain {
+			event!(
+				Level::WARN,
+				mqtt.topic = %self.topic,
+				"broker does not support retained messages, publishing without retain",
+			);
+		}
+
 		let msg = <T::Message as MqttBuildableMessage>::builder()
 			.topic(&*self.topic)
 			.payload(&*self.payload)
-			.retain(self.retained)
+			.retain(retain)
 			.qos(self.qos)
 			.build()
 			.map_err(|source| self.create_error(source))?;
@@ -54,12 +97,13 @@ impl ClientCommand for PublishCommand {
 		client
 			.client
 			.publish(msg)
+			.sampled(self.sampled)
 			.await
 			.map_err(|source| self.create_error(source))
 	}
 
 	fn create_error(&self, source: impl std::error::Error + Send + Sync + 'static) -> Self::Error {
-		PublishCommandError {
+		PublishCommandError::Publish {
 			topic: self.topic.clone(),
 			retained: self.retained,
 			qos: self.qos,
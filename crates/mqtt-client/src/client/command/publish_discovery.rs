@@ -0,0 +1,108 @@
+use super::{ClientCommand, InnerClient, PublishCommand, PublishCommandError};
+use crate::client::QosLevel;
+use async_trait::async_trait;
+use hass_mqtt_provider::MqttClient;
+use std::sync::Arc;
+use thiserror::Error;
+
+pub(crate) struct PublishDiscoveryCommand {
+	unique_id: Option<Arc<str>>,
+	publish: PublishCommand,
+	topic: Arc<str>,
+	remove: bool,
+}
+
+impl PublishDiscoveryCommand {
+	pub(crate) fn new(
+		topic: Arc<str>,
+		unique_id: Option<Arc<str>>,
+		payload: Arc<[u8]>,
+		retained: bool,
+		qos: QosLevel,
+	) -> Self {
+		let remove = payload.is_empty();
+		PublishDiscoveryCommand {
+			unique_id,
+			// discovery documents are always traced, like HassMqttClient::publish_discovery
+			publish: PublishCommand::new(topic.clone(), payload, retained, qos, true),
+			topic,
+			remove,
+		}
+	}
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum PublishDiscoveryCommandError {
+	#[error("unique_id '{unique_id}' of the discovery document on '{topic}' is already used by the discovery document on '{existing_topic}'")]
+	DuplicateUniqueId {
+		unique_id: Arc<str>,
+		topic: Arc<str>,
+		existing_topic: Arc<str>,
+	},
+
+	#[error("failed to publish discovery document on '{topic}'")]
+	Publish {
+		topic: Arc<str>,
+		source: PublishCommandError,
+	},
+}
+
+#[async_trait(?Send)]
+impl ClientCommand for PublishDiscoveryCommand {
+	type Result = ();
+	type Error = PublishDiscoveryCommandError;
+
+	async fn run<T: MqttClient>(
+		&self,
+		client: &mut InnerClient<T>,
+	) -> Result<Self::Result, Self::Error> {
+		if let Some(unique_id) = &self.unique_id {
+			match client.unique_ids.get(unique_id) {
+				Some(existing_topic) if *existing_topic != self.topic => {
+					return Err(PublishDiscoveryCommandError::DuplicateUniqueId {
+						unique_id: unique_id.clone(),
+						topic: self.topic.clone(),
+						existing_topic: existing_topic.clone(),
+					});
+				}
+				_ => (),
+			}
+		}
+
+		self
+			.publish
+			.run(client)
+			.await
+			.map_err(|source| PublishDiscoveryCommandError::Publish {
+				topic: self.topic.clone(),
+				source,
+			})?;
+
+		// a document on the same topic replaces the previous one (and an empty document
+		// removes the entity), which releases the unique_id of the previous document
+		client.unique_ids.retain(|_, topic| *topic != self.topic);
+		if let (Some(unique_id), false) = (&self.unique_id, self.remove) {
+			client
+				.unique_ids
+				.insert(unique_id.clone(), self.topic.clone());
+		}
+
+		// remembered so the documents can be republished when Home Assistant lost them
+		if self.remove {
+			client.discovery_documents.remove(&self.topic);
+		} else {
+			client
+				.discovery_documents
+				.insert(self.topic.clone(), self.publish.clone());
+		}
+
+		Ok(())
+	}
+
+	fn create_error(&self, source: impl std::error::Error + Send + Sync + 'static) -> Self::Error {
+		PublishDiscoveryCommandError::Publish {
+			topic: self.topic.clone(),
+			source: self.publish.create_error(source),
+		}
+	}
+}
@@ -0,0 +1,62 @@
+use super::{ClientCommand, InnerClient, PublishCommandError};
+use async_trait::async_trait;
+use hass_dyn_error::DynError;
+use hass_mqtt_provider::MqttClient;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Publishes the discovery documents published in this session again.
+pub(crate) struct RepublishDiscoveryCommand;
+
+impl RepublishDiscoveryCommand {
+	pub(crate) fn new() -> Self {
+		RepublishDiscoveryCommand
+	}
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum RepublishDiscoveryCommandError {
+	#[error("failed to republish discovery document on '{topic}'")]
+	Publish {
+		topic: Arc<str>,
+		source: PublishCommandError,
+	},
+
+	#[error("failed to republish discovery documents")]
+	Command {
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+}
+
+#[async_trait(?Send)]
+impl ClientCommand for RepublishDiscoveryCommand {
+	type Result = ();
+	type Error = RepublishDiscoveryCommandError;
+
+	async fn run<T: MqttClient>(
+		&self,
+		client: &mut InnerClient<T>,
+	) -> Result<Self::Result, Self::Error> {
+		let documents = client
+			.discovery_documents
+			.iter()
+			.map(|(topic, publish)| (topic.clone(), publish.clone()))
+			.collect::<Vec<_>>();
+
+		for (topic, publish) in documents {
+			publish
+				.run(client)
+				.await
+				.map_err(|source| RepublishDiscoveryCommandError::Publish { topic, source })?;
+		}
+
+		Ok(())
+	}
+
+	fn create_error(&self, source: impl std::error::Error + Send + Sync + 'static) -> Self::Error {
+		RepublishDiscoveryCommandError::Command {
+			source: DynError::new(source),
+		}
+	}
+}
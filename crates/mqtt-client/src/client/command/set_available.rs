@@ -0,0 +1,61 @@
+use super::{ClientCommand, InnerClient, PublishCommand};
+use async_trait::async_trait;
+use hass_dyn_error::DynError;
+use hass_mqtt_provider::MqttClient;
+use thiserror::Error;
+
+/// Publishes the `online` or `offline` availability of the node.
+pub(crate) struct SetAvailableCommand {
+	available: bool,
+}
+
+impl SetAvailableCommand {
+	pub(crate) fn new(available: bool) -> Self {
+		SetAvailableCommand { available }
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("failed to publish availability of node (available: {available})")]
+pub(crate) struct SetAvailableCommandError {
+	available: bool,
+	#[cfg_attr(provide_any, backtrace)]
+	source: DynError,
+}
+
+#[async_trait(?Send)]
+impl ClientCommand for SetAvailableCommand {
+	type Result = ();
+	type Error = SetAvailableCommandError;
+
+	async fn run<T: MqttClient>(
+		&self,
+		client: &mut InnerClient<T>,
+	) -> Result<Self::Result, Self::Error> {
+		let payload = client
+			.topics
+			.availability_payload(self.available)
+			.as_bytes()
+			.into();
+
+		client.available = self.available;
+		PublishCommand::new(
+			client.topics.available().into(),
+			payload,
+			true,
+			client.topics.availability_qos(),
+			// availability changes are always traced, like HassMqttClient::set_available
+			true,
+		)
+		.run(client)
+		.await
+		.map_err(|source| self.create_error(source))
+	}
+
+	fn create_error(&self, source: impl std::error::Error + Send + Sync + 'static) -> Self::Error {
+		SetAvailableCommandError {
+			available: self.available,
+			source: DynError::new(source),
+		}
+	}
+}
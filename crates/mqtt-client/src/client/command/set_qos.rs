@@ -0,0 +1,57 @@
+use super::{ClientCommand, InnerClient};
+use crate::client::QosLevel;
+use async_trait::async_trait;
+use hass_dyn_error::DynError;
+use hass_mqtt_provider::MqttClient;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Changes the QoS level of the subscription backing the route of a topic.
+pub(crate) struct SetQosCommand {
+	topic: Arc<str>,
+	qos: QosLevel,
+}
+
+impl SetQosCommand {
+	pub(crate) fn new(topic: Arc<str>, qos: QosLevel) -> Self {
+		SetQosCommand { topic, qos }
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("topic is not subscribed to")]
+struct NotSubscribedError;
+
+#[derive(Debug, Error)]
+#[error("failed to change QoS level of subscription to MQTT topic '{topic}' to {qos}")]
+pub(crate) struct SetQosCommandError {
+	topic: Arc<str>,
+	qos: QosLevel,
+	#[cfg_attr(provide_any, backtrace)]
+	source: DynError,
+}
+
+#[async_trait(?Send)]
+impl ClientCommand for SetQosCommand {
+	type Result = QosLevel;
+	type Error = SetQosCommandError;
+
+	async fn run<T: MqttClient>(
+		&self,
+		client: &mut InnerClient<T>,
+	) -> Result<Self::Result, Self::Error> {
+		client
+			.set_route_qos(&self.topic, self.qos)
+			.await
+			.map_err(|source| self.create_error(source))?
+			.ok_or_else(|| self.create_error(NotSubscribedError))
+	}
+
+	fn create_error(&self, source: impl std::error::Error + Send + Sync + 'static) -> Self::Error {
+		SetQosCommandError {
+			topic: self.topic.clone(),
+			qos: self.qos,
+			source: DynError::new(source),
+		}
+	}
+}
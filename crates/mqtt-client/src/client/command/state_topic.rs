@@ -0,0 +1,50 @@
+use super::{ClientCommand, InnerClient};
+use async_trait::async_trait;
+use hass_dyn_error::DynError;
+use hass_mqtt_provider::MqttClient;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Registers the state topic of an entity, so subscribing to it can be detected as a
+/// topic collision.
+pub(crate) struct StateTopicCommand {
+	topic: Arc<str>,
+	owner: Arc<str>,
+}
+
+impl StateTopicCommand {
+	pub(crate) fn new(topic: Arc<str>, owner: Arc<str>) -> Self {
+		StateTopicCommand { topic, owner }
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("failed to register state topic '{topic}'")]
+pub(crate) struct StateTopicCommandError {
+	topic: Arc<str>,
+	#[cfg_attr(provide_any, backtrace)]
+	source: DynError,
+}
+
+#[async_trait(?Send)]
+impl ClientCommand for StateTopicCommand {
+	type Result = ();
+	type Error = StateTopicCommandError;
+
+	async fn run<T: MqttClient>(
+		&self,
+		client: &mut InnerClient<T>,
+	) -> Result<Self::Result, Self::Error> {
+		client
+			.state_topics
+			.insert(self.topic.clone(), self.owner.clone());
+		Ok(())
+	}
+
+	fn create_error(&self, source: impl std::error::Error + Send + Sync + 'static) -> Self::Error {
+		StateTopicCommandError {
+			topic: self.topic.clone(),
+			source: DynError::new(source),
+		}
+	}
+}
@@ -1,8 +1,5 @@
 use super::{ClientCommand, InnerClient};
-use crate::{
-	client::{subscription::SubscriptionToken, Message, QosLevel},
-	router::RouterEntry,
-};
+use crate::client::{subscription::SubscriptionToken, Message, QosLevel, SubscribeOptions};
 use async_trait::async_trait;
 use hass_dyn_error::DynError;
 use hass_mqtt_provider::MqttClient;
@@ -12,16 +9,29 @@ use thiserror::Error;
 pub(crate) struct SubscribeCommand {
 	topic: Arc<str>,
 	qos: QosLevel,
+	command_owner: Option<Arc<str>>,
+	options: SubscribeOptions,
 }
 
 impl SubscribeCommand {
-	pub(crate) fn new(topic: Arc<str>, qos: QosLevel) -> Self {
-		SubscribeCommand { topic, qos }
+	pub(crate) fn new(
+		topic: Arc<str>,
+		qos: QosLevel,
+		command_owner: Option<Arc<str>>,
+		options: SubscribeOptions,
+	) -> Self {
+		SubscribeCommand {
+			topic,
+			qos,
+			command_owner,
+			options,
+		}
 	}
 }
 
 pub(crate) struct SubscribeCommandResult {
 	pub token: SubscriptionToken,
+	pub granted_qos: QosLevel,
 	pub receiver: flume::Receiver<Message>,
 }
 
@@ -43,22 +53,29 @@ impl ClientCommand for SubscribeCommand {
 		&self,
 		client: &mut InnerClient<T>,
 	) -> Result<Self::Result, Self::Error> {
-		let (sender, receiver) = flume::unbounded();
-		let route_id = match client.router.entry(self.topic.clone()) {
-			RouterEntry::Occupied(entry) => entry.insert(sender),
-			RouterEntry::Vacant(entry) => {
-				let key = client
-					.client
-					.subscribe(self.topic.clone(), self.qos)
-					.await
-					.map_err(|source| self.create_error(source))?;
+		if let Some(owner) = &self.command_owner {
+			client
+				.check_topic_collision(&self.topic, owner)
+				.map_err(|source| self.create_error(source))?;
+		}
 
-				entry.insert(key, sender)
-			}
-		};
+		let (sender, receiver) = flume::unbounded();
+		let subscribed = client
+			.subscribe_route(
+				self.topic.clone(),
+				self.qos,
+				self.command_owner.clone(),
+				self.options,
+				sender,
+			)
+			.await
+			.map_err(|source| self.create_error(source))?;
 
-		let token = client.subscriptions.insert(route_id);
-		Ok(SubscribeCommandResult { token, receiver })
+		Ok(SubscribeCommandResult {
+			token: subscribed.key,
+			granted_qos: subscribed.granted_qos,
+			receiver,
+		})
 	}
 
 	fn create_error(&self, source: impl std::error::Error + Send + Sync + 'static) -> Self::Error {
@@ -0,0 +1,47 @@
+use super::{ClientCommand, InnerClient};
+use crate::client::QosLevel;
+use async_trait::async_trait;
+use hass_dyn_error::DynError;
+use hass_mqtt_provider::MqttClient;
+use std::sync::Arc;
+use thiserror::Error;
+
+pub(crate) struct SubscriptionsCommand;
+
+impl SubscriptionsCommand {
+	pub(crate) fn new() -> Self {
+		SubscriptionsCommand
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("failed to list MQTT subscriptions")]
+pub(crate) struct SubscriptionsCommandError {
+	#[cfg_attr(provide_any, backtrace)]
+	source: DynError,
+}
+
+#[async_trait(?Send)]
+impl ClientCommand for SubscriptionsCommand {
+	type Result = Vec<(Arc<str>, QosLevel)>;
+	type Error = SubscriptionsCommandError;
+
+	async fn run<T: MqttClient>(
+		&self,
+		client: &mut InnerClient<T>,
+	) -> Result<Self::Result, Self::Error> {
+		Ok(
+			client
+				.router
+				.routes()
+				.map(|(topic, subscription)| (topic.clone(), subscription.granted_qos))
+				.collect(),
+		)
+	}
+
+	fn create_error(&self, source: impl std::error::Error + Send + Sync + 'static) -> Self::Error {
+		SubscriptionsCommandError {
+			source: DynError::new(source),
+		}
+	}
+}
@@ -1,22 +1,32 @@
+use crate::trace::{event, span};
 use crate::{
-	client::{command::Command, subscription::Subscriptions, Message},
+	client::{
+		command::{Command, PublishCommand},
+		subscription::{SubscriptionToken, Subscriptions},
+		ClientStats, Message, SubscribeOptions,
+	},
+	discovery::RetainedDiscoveryValidation,
 	mqtt::{HassMqttConnection, MqttProviderExt},
-	router::Router,
+	router::{Router, RouterEntry},
 	topics::TopicsConfig,
-	HassMqttOptions,
+	HassMqttOptions, TopicCollisions,
 };
 use futures::{pin_mut, StreamExt};
 use hass_dyn_error::DynError;
 use hass_mqtt_provider::{
-	MqttClient, MqttDisconnectBuilder, MqttMessage, MqttProvider, MqttReceivedMessage,
+	MqttClient, MqttConnectionEvent, MqttDisconnectBuilder, MqttMessage, MqttProvider,
+	MqttReceivedMessage, MqttSubscribeBuilder, QosLevel, ServerInfo, Subscribed,
 };
 use opentelemetry::trace::{SpanContext, TraceContextExt};
-use std::{sync::Arc, thread, time::Duration};
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc, thread, time::Duration};
 use thiserror::Error;
-use tokio::{select, task::LocalSet};
-use tracing::{field, instrument, span, Instrument, Level, Span};
+use tokio::{select, sync::watch, task::LocalSet};
+use tracing::{field, Instrument, Level, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+#[cfg(feature = "trace-payloads")]
+use crate::payload_trace::PayloadTracer;
+
 type RouteId = generational_arena::Index;
 
 #[derive(Debug, Error)]
@@ -60,37 +70,241 @@ impl ConnectError {
 	}
 }
 
+/// The subscription at the broker backing a route.
+pub(super) struct RouteSubscription<K> {
+	pub(super) key: K,
+	pub(super) qos: QosLevel,
+	pub(super) granted_qos: QosLevel,
+	/// The entity (`domain.entity_id`) this is the command topic of, if any.
+	pub(super) command_owner: Option<Arc<str>>,
+	pub(super) options: SubscribeOptions,
+}
+
+#[derive(Debug, Error)]
+#[error("topic '{topic}' is already the {kind} topic of entity {entity}")]
+pub(crate) struct TopicCollisionError {
+	topic: Arc<str>,
+	kind: &'static str,
+	entity: Arc<str>,
+}
+
 pub(crate) struct InnerClient<T: MqttClient> {
 	pub(super) client: T,
 	pub(super) topics: TopicsConfig,
-	pub(super) router: Router<T::SubscriptionKey, flume::Sender<Message>>,
+	pub(super) router: Router<RouteSubscription<T::SubscriptionKey>, flume::Sender<Message>>,
 	pub(super) subscriptions: Subscriptions,
 	pub(super) span_context: SpanContext,
+	pub(super) retained_discovery_validation: Option<RetainedDiscoveryValidation>,
+	/// The `unique_id`s of the discovery documents published in this session, mapped to
+	/// the topic of the document.
+	pub(super) unique_ids: BTreeMap<Arc<str>, Arc<str>>,
+	/// The discovery documents published in this session (that didn't remove their
+	/// entity), by topic, so they can be republished.
+	pub(super) discovery_documents: BTreeMap<Arc<str>, PublishCommand>,
+	/// Set when the connection to the broker is lost, so the routes are subscribed
+	/// again once the client reconnects.
+	connection_lost: bool,
+	/// The connection health of the client, for [HassMqttClient::stats] and
+	/// [HassMqttClient::wait_connected].
+	///
+	/// [HassMqttClient::stats]: super::HassMqttClient::stats
+	/// [HassMqttClient::wait_connected]: super::HassMqttClient::wait_connected
+	stats: watch::Sender<ClientStats>,
+	/// Whether the `online` availability is published again after reconnecting.
+	republish_online: bool,
+	/// Whether the node is available, as last set with [HassMqttClient::set_available].
+	/// The `online` availability isn't published after reconnecting while it's not.
+	///
+	/// [HassMqttClient::set_available]: super::HassMqttClient::set_available
+	pub(super) available: bool,
+	/// The state topics of the entities, mapped to the entity (`domain.entity_id`).
+	pub(super) state_topics: BTreeMap<Arc<str>, Arc<str>>,
+	topic_collisions: TopicCollisions,
+	/// Whether the broker accepts retained messages, as announced when the client last
+	/// connected. Retained publishes are downgraded to non-retained ones when it doesn't.
+	retain_available: bool,
+	#[cfg(feature = "trace-payloads")]
+	payload_tracer: Option<PayloadTracer>,
 }
 
 impl<T: MqttClient> InnerClient<T> {
-	fn new(client: T, topics: TopicsConfig, span_context: SpanContext) -> Self {
+	#[allow(clippy::too_many_arguments)]
+	fn new(
+		client: T,
+		topics: TopicsConfig,
+		span_context: SpanContext,
+		retained_discovery_validation: Option<RetainedDiscoveryValidation>,
+		republish_online: bool,
+		topic_collisions: TopicCollisions,
+		ordered_delivery: bool,
+		expected_entities: Option<usize>,
+	) -> Self {
+		let router = match expected_entities {
+			Some(capacity) => Router::with_capacity(capacity),
+			None => Router::new(),
+		};
+		let retain_available = client.server_info().retain_available != Some(false);
 		InnerClient {
 			client,
 			topics,
-			router: Router::new(),
+			router: match ordered_delivery {
+				true => router.ordered(),
+				false => router,
+			},
 			subscriptions: Subscriptions::new(),
 			span_context,
+			retained_discovery_validation,
+			unique_ids: BTreeMap::new(),
+			discovery_documents: BTreeMap::new(),
+			connection_lost: false,
+			// the client is created once the initial connection is established
+			stats: watch::channel(ClientStats {
+				connects: 1,
+				disconnects: 0,
+				last_disconnect_reason: None,
+				connected: true,
+			})
+			.0,
+			republish_online,
+			available: true,
+			state_topics: BTreeMap::new(),
+			topic_collisions,
+			retain_available,
+			#[cfg(feature = "trace-payloads")]
+			payload_tracer: None,
 		}
 	}
 
+	/// Whether the broker accepts retained messages (assumed unless it said otherwise).
+	pub(super) fn retain_available(&self) -> bool {
+		self.retain_available
+	}
+
+	/// Watches the connection health of the client.
+	pub(super) fn stats(&self) -> watch::Receiver<ClientStats> {
+		self.stats.subscribe()
+	}
+
+	/// Routes messages on `topic` to `sender`, subscribing to the topic if nobody
+	/// else is subscribed to it yet. The granted QoS level is the one granted by the
+	/// broker when the topic was first subscribed to, and so are the `options`.
+	pub(super) async fn subscribe_route(
+		&mut self,
+		topic: Arc<str>,
+		qos: QosLevel,
+		command_owner: Option<Arc<str>>,
+		options: SubscribeOptions,
+		sender: flume::Sender<Message>,
+	) -> Result<Subscribed<SubscriptionToken>, <T::SubscribeBuilder<'_> as MqttSubscribeBuilder>::Error>
+	{
+		let (route_id, granted_qos) = match self.router.entry(topic.clone()) {
+			RouterEntry::Occupied(mut entry) => {
+				let data = entry.data_mut();
+				if data.command_owner.is_none() {
+					data.command_owner = command_owner;
+				}
+				let granted_qos = data.granted_qos;
+				(entry.insert(sender), granted_qos)
+			}
+			RouterEntry::Vacant(entry) => {
+				let Subscribed { key, granted_qos } =
+					options.apply(self.client.subscribe(topic, qos)).await?;
+				let subscription = RouteSubscription {
+					key,
+					qos,
+					granted_qos,
+					command_owner,
+					options,
+				};
+				(entry.insert(subscription, sender), granted_qos)
+			}
+		};
+
+		Ok(Subscribed {
+			key: self.subscriptions.insert(route_id),
+			granted_qos,
+		})
+	}
+
+	/// Subscribes to the topic of the route `topic` again with `qos`, returning the granted
+	/// QoS level, or `None` if there is no such route. Subscribing to a topic filter that
+	/// is already subscribed to replaces the subscription at the broker, so the route
+	/// stays registered (and keeps receiving messages) throughout.
+	pub(super) async fn set_route_qos(
+		&mut self,
+		topic: &str,
+		qos: QosLevel,
+	) -> Result<Option<QosLevel>, <T::SubscribeBuilder<'_> as MqttSubscribeBuilder>::Error> {
+		let Some((topic, subscription)) = self
+			.router
+			.routes_mut()
+			.find(|(route, _)| &***route == topic)
+		else {
+			return Ok(None);
+		};
+
+		let builder = self.client.subscribe(topic.clone(), qos);
+		let Subscribed { key, granted_qos } = subscription.options.apply(builder).await?;
+		subscription.key = key;
+		subscription.qos = qos;
+		subscription.granted_qos = granted_qos;
+		Ok(Some(granted_qos))
+	}
+
+	/// Checks whether subscribing to `topic` as the command topic of `owner` collides with
+	/// the command topic of another entity, or with the state topic of an entity.
+	pub(super) fn check_topic_collision(
+		&self,
+		topic: &Arc<str>,
+		owner: &Arc<str>,
+	) -> Result<(), TopicCollisionError> {
+		if self.topic_collisions == TopicCollisions::Ignore {
+			return Ok(());
+		}
+
+		let collision = match self.state_topics.get(topic) {
+			Some(entity) => Some(("state", entity)),
+			None => self
+				.router
+				.routes()
+				.find(|(route, _)| *route == topic)
+				.and_then(|(_, subscription)| subscription.command_owner.as_ref())
+				.filter(|entity| *entity != owner)
+				.map(|entity| ("command", entity)),
+		};
+
+		let Some((kind, entity)) = collision else {
+			return Ok(());
+		};
+
+		let error = TopicCollisionError {
+			topic: topic.clone(),
+			kind,
+			entity: entity.clone(),
+		};
+		if self.topic_collisions == TopicCollisions::Reject {
+			return Err(error);
+		}
+
+		event!(Level::WARN, mqtt.topic = %topic, entity = %owner, "{}", error);
+		Ok(())
+	}
+
 	async fn run(mut self, receiver: flume::Receiver<Command>) {
 		let receiver = receiver.into_stream().fuse();
 		let messages = self.client.messages().fuse();
+		let connection_events = self.client.connection_events().fuse();
 
 		pin_mut!(receiver);
 		pin_mut!(messages);
+		pin_mut!(connection_events);
 
 		loop {
 			select! {
 				tok = self.subscriptions.dropped() => self.handle_unsubscribe(tok).await,
 				Some(cmd) = receiver.next() => self.handle_command(cmd).await,
 				Some(msg) = messages.next() => self.handle_message(msg).await,
+				Some(event) = connection_events.next() => self.handle_connection_event(event).await,
 				else => break,
 			}
 		}
@@ -105,12 +319,109 @@ impl<T: MqttClient> InnerClient<T> {
 
 	async fn handle_unsubscribe(&mut self, tok: RouteId) {
 		// TODO: Trace?
-		if let Some((_, Some(key))) = self.router.remove(tok) {
+		self.remove_route(tok).await;
+	}
+
+	/// Removes the consumer `id` from its route. The subscription at the broker is shared
+	/// by every consumer of the route (command topics that fan out, or that subscribe to
+	/// the same topic filter), so it is only unsubscribed when the last one is removed.
+	async fn remove_route(&mut self, id: RouteId) {
+		if let Some((_, Some(RouteSubscription { key, .. }))) = self.router.remove(id) {
 			// TODO: Log error
 			let _ = self.client.unsubscribe(key).await;
 		}
 	}
 
+	async fn handle_connection_event(&mut self, event: MqttConnectionEvent) {
+		match event {
+			MqttConnectionEvent::ConnectionLost => {
+				self.handle_connection_lost(Arc::from("connection lost"))
+			}
+			MqttConnectionEvent::Disconnected { reason } => self.handle_connection_lost(reason),
+			// the initial connection is established before the routes exist, so only
+			// reconnects need to resubscribe
+			MqttConnectionEvent::Connected if self.connection_lost => {
+				self.connection_lost = false;
+				// the broker might have changed (like when connecting to another server of a
+				// cluster), so the retain flag follows what it announced on this connection
+				self.retain_available = self.client.server_info().retain_available != Some(false);
+				self.stats.send_modify(|stats| {
+					stats.connects += 1;
+					stats.connected = true;
+				});
+				self.resubscribe().await;
+				if self.republish_online && self.available {
+					self.publish_online().await;
+				}
+			}
+			MqttConnectionEvent::Connected => (),
+		}
+	}
+
+	/// Records that the connection was lost. Providers may report the same disconnect
+	/// more than once (for instance when the broker closes the connection), so it's only
+	/// counted once, but the last reason is kept.
+	fn handle_connection_lost(&mut self, reason: Arc<str>) {
+		let was_connected = !self.connection_lost;
+		self.connection_lost = true;
+		self.stats.send_modify(|stats| {
+			if was_connected {
+				stats.disconnects += 1;
+			}
+			stats.last_disconnect_reason = Some(reason);
+			stats.connected = false;
+		});
+	}
+
+	/// Subscribes to the topics of all routes again, so the subscriptions at the broker
+	/// match the router after a reconnect.
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(level = Level::DEBUG, name = "InnerClient::resubscribe", skip_all)
+	)]
+	async fn resubscribe(&mut self) {
+		for (topic, subscription) in self.router.routes_mut() {
+			let builder = self.client.subscribe(topic.clone(), subscription.qos);
+			match subscription.options.apply(builder).await {
+				Ok(Subscribed { key, granted_qos }) => {
+					subscription.key = key;
+					subscription.granted_qos = granted_qos;
+				}
+				Err(e) => {
+					event!(
+						Level::ERROR,
+						mqtt.topic = %topic,
+						"failed to resubscribe to topic: {:#}",
+						e,
+					);
+				}
+			}
+		}
+	}
+
+	/// Publishes the `online` availability of the node, replacing the `offline` last will
+	/// the broker published when the connection was lost.
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(level = Level::DEBUG, name = "InnerClient::publish_online", skip_all)
+	)]
+	async fn publish_online(&mut self) {
+		let message = match self
+			.topics
+			.online_message::<T::Message>(self.retain_available)
+		{
+			Ok(message) => message,
+			Err(e) => {
+				event!(Level::ERROR, "failed to create online message: {:#}", e);
+				return;
+			}
+		};
+
+		if let Err(e) = self.client.publish(message).await {
+			event!(Level::ERROR, "failed to publish online message: {:#}", e);
+		}
+	}
+
 	async fn handle_command(&mut self, cmd: Command) {
 		// TODO: Trace?
 		cmd.run(self).await
@@ -129,10 +440,16 @@ impl<T: MqttClient> InnerClient<T> {
 		let message_span = msg.span().clone();
 		message_span.add_link(self.span_context.clone());
 
+		#[cfg(feature = "trace-payloads")]
+		if let Some(tracer) = &self.payload_tracer {
+			message_span.in_scope(|| tracer.trace(msg.payload()));
+		}
+
 		let message = Message {
 			topic: topic.into(),
 			payload: msg.payload().into(),
 			retained: msg.retained(),
+			dup: msg.dup(),
 			span: message_span,
 		};
 
@@ -143,29 +460,54 @@ impl<T: MqttClient> InnerClient<T> {
 			}
 		}
 
-		for key in to_remove {
-			self.router.remove(key);
+		for id in to_remove {
+			self.remove_route(id).await;
 		}
 	}
 }
 
-#[instrument(
-	level = Level::DEBUG,
-	name = "InnerClient::spawn"
-	skip_all,
-	fields(
-		provider.name = %P::NAME,
+/// The MQTT thread started by [spawn], and what is known about the connection.
+pub(super) struct SpawnedClient {
+	pub(super) sender: flume::Sender<Command>,
+	pub(super) client_id: Arc<str>,
+	pub(super) broker_addrs: Arc<[SocketAddr]>,
+	pub(super) server_info: ServerInfo,
+	pub(super) stats: watch::Receiver<ClientStats>,
+}
+
+/// The builder of the thread running the client, named after the application and using
+/// the configured stack size.
+pub(super) fn thread_builder(options: &HassMqttOptions) -> thread::Builder {
+	let builder = thread::Builder::new().name(format!(
+		"{}-{}-hass",
+		options.thread_name_prefix.as_deref().unwrap_or("mqtt"),
+		options.application_name.slug()
+	));
+	match options.thread_stack_size {
+		Some(size) => builder.stack_size(size),
+		None => builder,
+	}
+}
+
+#[cfg_attr(
+	feature = "tracing",
+	tracing::instrument(
+		level = Level::DEBUG,
+		name = "InnerClient::spawn"
+		skip_all,
+		fields(
+			provider.name = %P::NAME,
+		)
 	)
 )]
 pub(super) async fn spawn<P: MqttProvider>(
 	options: HassMqttOptions,
-) -> Result<(flume::Sender<Command>, Arc<str>), ConnectError> {
+) -> Result<SpawnedClient, ConnectError> {
 	let span = Span::current();
 	let spawn_span_cx = span.context().span().span_context().clone();
 	let (result_sender, result_receiver) = tokio::sync::oneshot::channel();
 
-	thread::Builder::new()
-		.name(format!("mqtt-{}-hass", options.application_name.slug()))
+	thread_builder(&options)
 		.spawn({
 			let parent = span;
 
@@ -199,34 +541,57 @@ pub(super) async fn spawn<P: MqttProvider>(
 				let local_guard = local.enter();
 
 				let Ok(client) = local.block_on(&rt, {
-				let span = span.exit();
-				let span_clone = span.clone();
-				async move {
-					let HassMqttConnection {
-						topics,
-						client: mqtt_client,
-						client_id,
-					} = match <P as MqttProviderExt>::create_client(&options)
-						.await
-						.map_err(ConnectError::connect)
-					{
-						Ok(c) => c,
-						Err(e) => {
-							let _ = result_sender.send(Err(e));
-							return Err(());
+					let span = span.exit();
+					let span_clone = span.clone();
+					async move {
+						let HassMqttConnection {
+							topics,
+							client: mqtt_client,
+							client_id,
+						} = match <P as MqttProviderExt>::create_client(&options)
+							.await
+							.map_err(ConnectError::connect)
+						{
+							Ok(c) => c,
+							Err(e) => {
+								let _ = result_sender.send(Err(e));
+								return Err(());
+							}
+						};
+
+						span_clone.record("client.id", &client_id);
+						let broker_addrs = Arc::from(mqtt_client.broker_addrs());
+						let server_info = mqtt_client.server_info();
+						#[allow(unused_mut)]
+						let mut client = InnerClient::new(
+							mqtt_client,
+							topics,
+							spawn_span_cx,
+							options.retained_discovery_validation.clone(),
+							options.republish_online,
+							options.topic_collisions,
+							options.ordered_delivery,
+							options.expected_entities,
+						);
+
+						#[cfg(feature = "trace-payloads")]
+						{
+							client.payload_tracer = options.mqtt.payload_tracer;
 						}
-					};
-
-					span_clone.record("client.id", &client_id);
-					let client = InnerClient::new(mqtt_client, topics, spawn_span_cx);
 
-					let _ = result_sender.send(Ok((sender, client_id.into())));
-					Ok(client)
-				}
-				.instrument(span)
-			}) else {
-				return;
-			};
+						let _ = result_sender.send(Ok(SpawnedClient {
+							sender,
+							client_id: client_id.into(),
+							broker_addrs,
+							server_info,
+							stats: client.stats(),
+						}));
+						Ok(client)
+					}
+					.instrument(span)
+				}) else {
+					return;
+				};
 
 				// run forever
 				local.block_on(&rt, client.run(receiver));
@@ -238,7 +603,7 @@ pub(super) async fn spawn<P: MqttProvider>(
 		.map_err(ConnectError::spawn_thread)?;
 
 	match result_receiver.await {
-		Ok(Ok(sender)) => Ok(sender),
+		Ok(Ok(spawned)) => Ok(spawned),
 		Ok(Err(e)) => Err(e),
 		Err(e) => Err(ConnectError::connect(e)),
 	}
@@ -0,0 +1,357 @@
+use crate::{client::Message, trace::event};
+use hass_dyn_error::DynError;
+use hass_mqtt_proto::{
+	BinarySensor, Button, Climate, Cover, DeviceTracker, Fan, Light, Lock, Number, Select, Sensor,
+	Switch,
+};
+use semval::Validate;
+use serde::Deserialize;
+use std::{fmt, sync::Arc};
+use thiserror::Error;
+use tracing::Level;
+
+#[derive(Debug, Error)]
+pub enum DiscoveryDocumentError {
+	#[error("discovery document is not valid JSON")]
+	Parse {
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+
+	#[error(transparent)]
+	Invalid { source: DiscoveryValidationError },
+}
+
+/// The invalidities found when validating a discovery document, rendered as strings
+/// (like `StateTopicMissing`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveryValidationError {
+	invalidities: Vec<String>,
+}
+
+impl DiscoveryValidationError {
+	pub(crate) fn new<T: fmt::Debug>(invalidities: impl IntoIterator<Item = T>) -> Self {
+		DiscoveryValidationError {
+			invalidities: invalidities
+				.into_iter()
+				.map(|invalidity| format!("{invalidity:?}"))
+				.collect(),
+		}
+	}
+
+	pub fn invalidities(&self) -> &[String] {
+		&self.invalidities
+	}
+}
+
+impl fmt::Display for DiscoveryValidationError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"discovery document is invalid: {}",
+			self.invalidities.join(", ")
+		)
+	}
+}
+
+impl std::error::Error for DiscoveryValidationError {}
+
+/// A retained discovery document that was replayed by the broker, but which
+/// failed to validate.
+#[derive(Debug)]
+pub struct InvalidDiscoveryDocument {
+	pub topic: Arc<str>,
+	pub domain: Arc<str>,
+	pub entity_id: Arc<str>,
+	pub payload: Arc<[u8]>,
+	pub error: DiscoveryDocumentError,
+}
+
+type InvalidDiscoveryHandler = Arc<dyn Fn(&InvalidDiscoveryDocument) + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub(crate) struct RetainedDiscoveryValidation {
+	handler: Option<InvalidDiscoveryHandler>,
+}
+
+impl RetainedDiscoveryValidation {
+	pub(crate) fn new() -> Self {
+		Self::default()
+	}
+
+	pub(crate) fn with_handler(
+		handler: impl Fn(&InvalidDiscoveryDocument) + Send + Sync + 'static,
+	) -> Self {
+		RetainedDiscoveryValidation {
+			handler: Some(Arc::new(handler)),
+		}
+	}
+
+	/// Validates the retained messages received on the discovery topic of an entity
+	/// until the subscription is dropped.
+	pub(crate) async fn watch(
+		self,
+		domain: Arc<str>,
+		entity_id: Arc<str>,
+		messages: flume::Receiver<Message>,
+	) {
+		while let Ok(message) = messages.recv_async().await {
+			// only replayed documents are of interest, and an empty payload
+			// means the entity has been removed
+			if !message.retained || message.payload.is_empty() {
+				continue;
+			}
+
+			let Err(error) = validate_discovery_document(&domain, &message.payload) else {
+				continue;
+			};
+
+			event!(
+				parent: &message.span,
+				Level::WARN,
+				discovery.topic = %message.topic,
+				entity.domain = %domain,
+				entity.id = %entity_id,
+				error = %error,
+				"received invalid retained discovery document"
+			);
+
+			if let Some(handler) = &self.handler {
+				handler(&InvalidDiscoveryDocument {
+					topic: message.topic,
+					domain: domain.clone(),
+					entity_id: entity_id.clone(),
+					payload: message.payload,
+					error,
+				});
+			}
+		}
+	}
+}
+
+pub(crate) fn validate_discovery_document(
+	domain: &str,
+	payload: &[u8],
+) -> Result<(), DiscoveryDocumentError> {
+	fn validate<'a, T>(payload: &'a [u8]) -> Result<(), DiscoveryDocumentError>
+	where
+		T: Deserialize<'a> + Validate,
+		T::Invalidity: fmt::Debug,
+	{
+		let document: T = parse(payload)?;
+		document
+			.validate()
+			.map_err(|invalidities| DiscoveryDocumentError::Invalid {
+				source: DiscoveryValidationError::new(invalidities),
+			})
+	}
+
+	fn parse<'a, T: Deserialize<'a>>(payload: &'a [u8]) -> Result<T, DiscoveryDocumentError> {
+		serde_json::from_slice(payload).map_err(|source| DiscoveryDocumentError::Parse {
+			source: DynError::new(source),
+		})
+	}
+
+	match domain {
+		"binary_sensor" => validate::<BinarySensor>(payload),
+		"button" => validate::<Button>(payload),
+		"climate" => validate::<Climate>(payload),
+		"cover" => validate::<Cover>(payload),
+		"device_tracker" => validate::<DeviceTracker>(payload),
+		"fan" => validate::<Fan>(payload),
+		"light" => validate::<Light>(payload),
+		"lock" => validate::<Lock>(payload),
+		"number" => validate::<Number>(payload),
+		"select" => validate::<Select>(payload),
+		"sensor" => validate::<Sensor>(payload),
+		"switch" => validate::<Switch>(payload),
+		// no document type is known for the domain, so only check that it's a JSON object
+		_ => parse::<serde_json::Map<String, serde_json::Value>>(payload).map(|_| ()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		mock::{MockMessage, MockMqtt},
+		HassMqttOptions,
+	};
+	use std::time::Duration;
+	use tracing::Span;
+
+	/// Runs a retained discovery document for `domain` through the retained
+	/// document validation, and returns the invalidities it was reported with.
+	async fn retained_invalidities(domain: &str, payload: &[u8]) -> Vec<String> {
+		let (sender, reports) = flume::unbounded();
+		let validation = RetainedDiscoveryValidation::with_handler(move |document| {
+			let invalidities = match &document.error {
+				DiscoveryDocumentError::Invalid { source } => source.invalidities().to_vec(),
+				error => panic!("document should fail to validate: {error}"),
+			};
+			let _ = sender.send(invalidities);
+		});
+
+		let (messages, receiver) = flume::unbounded();
+		messages
+			.send(Message {
+				topic: format!("homeassistant/{domain}/default/test/config").into(),
+				payload: payload.into(),
+				retained: true,
+				dup: false,
+				span: Span::none(),
+			})
+			.unwrap();
+		drop(messages);
+		validation
+			.watch(domain.into(), "test".into(), receiver)
+			.await;
+
+		reports.try_recv().expect("should report invalid document")
+	}
+
+	#[test]
+	fn valid_document() {
+		assert!(validate_discovery_document("sensor", br#"{"state_topic":"app/state"}"#).is_ok());
+	}
+
+	#[test]
+	fn malformed_document() {
+		assert!(matches!(
+			validate_discovery_document("sensor", br#"{"state_topic":"#),
+			Err(DiscoveryDocumentError::Parse { .. })
+		));
+		assert!(matches!(
+			validate_discovery_document("unknown", b"[]"),
+			Err(DiscoveryDocumentError::Parse { .. })
+		));
+	}
+
+	#[test]
+	fn invalid_document() {
+		let Err(DiscoveryDocumentError::Invalid { source }) =
+			validate_discovery_document("sensor", br#"{"name":"","state_topic":"app/state"}"#)
+		else {
+			panic!("should be invalid");
+		};
+		assert!(!source.invalidities().is_empty());
+		assert_eq!(
+			DiscoveryDocumentError::Invalid {
+				source: source.clone()
+			}
+			.to_string(),
+			source.to_string()
+		);
+	}
+
+	#[tokio::test]
+	async fn invalid_retained_document_is_reported() {
+		let (sender, receiver) = flume::unbounded();
+		let client = HassMqttOptions::new("localhost", "retained-discovery")
+			.on_invalid_retained_discovery(move |document| {
+				let _ = sender.send((document.topic.clone(), document.payload.clone()));
+			})
+			.build::<MockMqtt>()
+			.await
+			.expect("should connect");
+		let state = MockMqtt::state(&client.client_id);
+
+		let _entity = client
+			.entity("sensor", "temperature")
+			.await
+			.expect("should create entity");
+		let topic = "homeassistant/sensor/default/temperature/config";
+		assert!(state.subscriptions().iter().any(|(t, _)| &**t == topic));
+
+		// valid and non-retained documents are not reported
+		state.receive(MockMessage {
+			topic: topic.into(),
+			payload: br#"{"state_topic":"app/state"}"#.to_vec(),
+			retain: true,
+			..Default::default()
+		});
+		state.receive(MockMessage {
+			topic: topic.into(),
+			payload: b"not json".to_vec(),
+			retain: false,
+			..Default::default()
+		});
+		state.receive(MockMessage {
+			topic: topic.into(),
+			payload: b"{\"state_topic\":".to_vec(),
+			retain: true,
+			..Default::default()
+		});
+
+		let (reported_topic, payload) =
+			tokio::time::timeout(Duration::from_secs(5), receiver.recv_async())
+				.await
+				.expect("should report invalid document")
+				.unwrap();
+
+		assert_eq!(&*reported_topic, topic);
+		assert_eq!(&*payload, b"{\"state_topic\":");
+		assert!(receiver.is_empty());
+	}
+
+	#[tokio::test]
+	async fn invalid_retained_climate_document_is_reported() {
+		let invalidities = retained_invalidities(
+			"climate",
+			br#"{"temperature_command_topic":"hvac/temperature/set","min_temp":25.0,"max_temp":18.0}"#,
+		)
+		.await;
+
+		assert_eq!(invalidities, ["ModesMissing", "TemperatureRangeInverted"]);
+	}
+
+	#[tokio::test]
+	async fn invalid_retained_fan_document_is_reported() {
+		let invalidities = retained_invalidities(
+			"fan",
+			br#"{"command_topic":"bedroom/fan/set","speed_range_min":5,"speed_range_max":2}"#,
+		)
+		.await;
+		assert_eq!(invalidities, ["SpeedRangeInverted"]);
+
+		let invalidities = retained_invalidities(
+			"fan",
+			br#"{"command_topic":"bedroom/fan/set","percentage_command_topic":"bedroom/fan/speed/set","speed_range_max":3}"#,
+		)
+		.await;
+		assert_eq!(invalidities, ["SpeedRangeMissing"]);
+	}
+
+	#[tokio::test]
+	async fn invalid_retained_lock_document_is_reported() {
+		let invalidities = retained_invalidities(
+			"lock",
+			br#"{"command_topic":"door/lock/set","value_template":"{{ value_json.state }}"}"#,
+		)
+		.await;
+
+		assert_eq!(invalidities, ["StateTopicMissing"]);
+	}
+
+	#[tokio::test]
+	async fn invalid_retained_number_document_is_reported() {
+		let invalidities = retained_invalidities(
+			"number",
+			br#"{"command_topic":"heater/level/set","min":10.0,"max":1.0,"step":0.0}"#,
+		)
+		.await;
+
+		assert_eq!(invalidities, ["RangeInverted", "StepNotPositive"]);
+	}
+
+	#[tokio::test]
+	async fn invalid_retained_select_document_is_reported() {
+		let invalidities = retained_invalidities(
+			"select",
+			br#"{"command_topic":"heater/mode/set","options":[]}"#,
+		)
+		.await;
+
+		assert_eq!(invalidities, ["OptionsEmpty"]);
+	}
+}
@@ -0,0 +1,197 @@
+use crate::client::Message;
+use hass_dyn_error::DynError;
+use serde::de::DeserializeOwned;
+use std::{fmt, str::FromStr, sync::Arc};
+use thiserror::Error;
+use tracing::Span;
+
+/// The encoding of message payloads, as configured by the `encoding` field of
+/// entity documents.
+///
+/// Home Assistant defaults to `utf-8`, and uses an empty encoding to disable
+/// decoding of payloads altogether. Payloads of topics with an empty encoding are
+/// passed through as [raw bytes](DecodedPayload::Raw).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadEncoding {
+	#[default]
+	Utf8,
+	Raw,
+}
+
+impl PayloadEncoding {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			PayloadEncoding::Utf8 => "utf-8",
+			PayloadEncoding::Raw => "",
+		}
+	}
+
+	pub fn decode<'a>(&self, payload: &'a [u8]) -> Result<DecodedPayload<'a>, std::str::Utf8Error> {
+		match self {
+			PayloadEncoding::Utf8 => std::str::from_utf8(payload).map(DecodedPayload::Text),
+			PayloadEncoding::Raw => Ok(DecodedPayload::Raw(payload)),
+		}
+	}
+}
+
+impl fmt::Display for PayloadEncoding {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported payload encoding '{encoding}'")]
+pub struct UnsupportedEncodingError {
+	encoding: String,
+}
+
+impl FromStr for PayloadEncoding {
+	type Err = UnsupportedEncodingError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if s.is_empty() {
+			Ok(PayloadEncoding::Raw)
+		} else if s.eq_ignore_ascii_case("utf-8") || s.eq_ignore_ascii_case("utf8") {
+			Ok(PayloadEncoding::Utf8)
+		} else {
+			Err(UnsupportedEncodingError { encoding: s.into() })
+		}
+	}
+}
+
+/// A message payload decoded using a [PayloadEncoding].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodedPayload<'a> {
+	/// The payload decoded as text.
+	Text(&'a str),
+
+	/// The raw payload, for topics with decoding disabled.
+	Raw(&'a [u8]),
+}
+
+impl<'a> DecodedPayload<'a> {
+	pub fn as_text(&self) -> Option<&'a str> {
+		match self {
+			DecodedPayload::Text(text) => Some(text),
+			DecodedPayload::Raw(_) => None,
+		}
+	}
+
+	pub fn as_bytes(&self) -> &'a [u8] {
+		match self {
+			DecodedPayload::Text(text) => text.as_bytes(),
+			DecodedPayload::Raw(bytes) => bytes,
+		}
+	}
+}
+
+#[derive(Debug, Error)]
+#[error("failed to decode payload of message on '{topic}' as {encoding}")]
+pub struct PayloadDecodeError {
+	topic: Arc<str>,
+	encoding: PayloadEncoding,
+	#[cfg_attr(provide_any, backtrace)]
+	source: DynError,
+}
+
+/// An error decoding the JSON payload of a message into a type, see
+/// [CommandTopic::decoded](crate::CommandTopic::decoded).
+#[derive(Debug, Error)]
+#[error("failed to decode JSON payload of message on '{topic}'")]
+pub struct DecodeError {
+	topic: Arc<str>,
+	span: Span,
+	#[cfg_attr(provide_any, backtrace)]
+	source: DynError,
+}
+
+impl DecodeError {
+	pub fn topic(&self) -> &str {
+		&self.topic
+	}
+
+	/// The span of the message that failed to decode.
+	pub fn span(&self) -> &Span {
+		&self.span
+	}
+}
+
+impl Message {
+	/// Decodes the JSON payload of the message into a `T`.
+	pub(crate) fn decode_json<T: DeserializeOwned>(self) -> Result<T, DecodeError> {
+		let result = self.span.in_scope(|| serde_json::from_slice(&self.payload));
+		result.map_err(|source| DecodeError {
+			topic: self.topic,
+			span: self.span,
+			source: DynError::new(source),
+		})
+	}
+
+	/// Decodes the payload of the message using `encoding`.
+	pub fn decode_payload(
+		&self,
+		encoding: PayloadEncoding,
+	) -> Result<DecodedPayload<'_>, PayloadDecodeError> {
+		encoding
+			.decode(&self.payload)
+			.map_err(|source| PayloadDecodeError {
+				topic: self.topic.clone(),
+				encoding,
+				source: DynError::new(source),
+			})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tracing::Span;
+
+	fn message(payload: &[u8]) -> Message {
+		Message {
+			topic: "app/default/light/kitchen/set".into(),
+			payload: payload.into(),
+			retained: false,
+			dup: false,
+			span: Span::none(),
+		}
+	}
+
+	#[test]
+	fn parse_encoding() {
+		assert_eq!(
+			"utf-8".parse::<PayloadEncoding>().unwrap(),
+			PayloadEncoding::Utf8
+		);
+		assert_eq!(
+			"UTF8".parse::<PayloadEncoding>().unwrap(),
+			PayloadEncoding::Utf8
+		);
+		assert_eq!("".parse::<PayloadEncoding>().unwrap(), PayloadEncoding::Raw);
+		assert!("latin-1".parse::<PayloadEncoding>().is_err());
+	}
+
+	#[test]
+	fn decode_utf8() {
+		let message = message("på".as_bytes());
+		assert_eq!(
+			message.decode_payload(PayloadEncoding::Utf8).unwrap(),
+			DecodedPayload::Text("på")
+		);
+	}
+
+	#[test]
+	fn decode_invalid_utf8() {
+		let message = message(&[0xff, 0xfe]);
+		assert!(message.decode_payload(PayloadEncoding::Utf8).is_err());
+	}
+
+	#[test]
+	fn decode_raw() {
+		let message = message(&[0xff, 0xfe]);
+		let payload = message.decode_payload(PayloadEncoding::Raw).unwrap();
+		assert_eq!(payload, DecodedPayload::Raw(&[0xff, 0xfe]));
+		assert_eq!(payload.as_text(), None);
+	}
+}
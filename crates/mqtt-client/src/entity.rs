@@ -1,25 +1,35 @@
+use crate::trace::span;
 use crate::{
-	client::{HassMqttClient, Message, Subscription},
+	client::{
+		subscription::SubscriptionToken, HassMqttClient, Message, PublishDiscoveryError,
+		SubscribeOptions, Subscription,
+	},
+	discovery::DiscoveryValidationError,
+	encoding::{DecodeError, DecodedPayload, PayloadDecodeError, PayloadEncoding},
 	topics::EntityTopicsConfig,
 };
-use futures::{future::BoxFuture, FutureExt, Stream};
+use futures::{future::BoxFuture, FutureExt, Stream, StreamExt};
 use hass_dyn_error::DynError;
-use hass_mqtt_provider::QosLevel;
+use hass_mqtt_proto::{Availability, Document};
+use hass_mqtt_provider::{MessageSpanSampler, MqttRetainHandling, QosLevel};
 use opentelemetry::trace::{SpanContext, TraceContextExt};
 use pin_project::pin_project;
+use serde::{de::DeserializeOwned, Serialize};
 use std::{
 	convert::Infallible,
-	future::{self, IntoFuture},
+	future::{Future, IntoFuture},
 	sync::Arc,
+	time::Duration,
 };
 use thiserror::Error;
-use tracing::{instrument, span, Instrument, Level, Span};
+use tracing::{Instrument, Level, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 pub struct EntityTopicBuilder<'a> {
 	client: &'a HassMqttClient,
 	domain: Arc<str>,
 	entity_id: Arc<str>,
+	object_id: Option<Arc<str>>,
 	topic: Option<Arc<str>>,
 	span: Span,
 }
@@ -35,11 +45,25 @@ impl<'a> EntityTopicBuilder<'a> {
 			client,
 			domain,
 			entity_id,
+			object_id: None,
 			topic: None,
 			span,
 		}
 	}
 
+	/// Uses the slug of `object_id` instead of the `entity_id` in the topics of the
+	/// entity (its discovery, state and command topics).
+	///
+	/// Home Assistant generates the `entity_id` of the entity from the `object_id` of the
+	/// discovery document when it's set, so set the same `object_id` on the document to
+	/// keep the topics consistent with the entity in Home Assistant.
+	pub fn object_id(self, object_id: impl Into<Arc<str>>) -> Self {
+		EntityTopicBuilder {
+			object_id: Some(object_id.into()),
+			..self
+		}
+	}
+
 	pub fn with_topic(self, topic: impl Into<Arc<str>>) -> Self {
 		let topic = topic.into();
 		self
@@ -72,6 +96,7 @@ impl<'a> IntoFuture for EntityTopicBuilder<'a> {
 			client,
 			domain,
 			entity_id,
+			object_id,
 			topic,
 			span,
 		} = self;
@@ -82,6 +107,7 @@ impl<'a> IntoFuture for EntityTopicBuilder<'a> {
 				.command(crate::client::command::entity(
 					domain.clone(),
 					entity_id.clone(),
+					object_id,
 					topic.clone(),
 				))
 				.await
@@ -95,6 +121,7 @@ impl<'a> IntoFuture for EntityTopicBuilder<'a> {
 			Ok(EntityTopic::new(
 				client.clone(),
 				result.topics,
+				result.discovery_subscription,
 				span_context,
 			))
 		}
@@ -106,6 +133,7 @@ impl<'a> IntoFuture for EntityTopicBuilder<'a> {
 pub struct EntityTopic {
 	client: HassMqttClient,
 	topics: EntityTopicsConfig,
+	_discovery_subscription: Option<SubscriptionToken>,
 	span_context: SpanContext,
 }
 
@@ -113,15 +141,38 @@ impl EntityTopic {
 	pub(crate) fn new(
 		client: HassMqttClient,
 		topics: EntityTopicsConfig,
+		discovery_subscription: Option<SubscriptionToken>,
 		span_context: SpanContext,
 	) -> Self {
 		EntityTopic {
 			client,
 			topics,
+			_discovery_subscription: discovery_subscription,
 			span_context,
 		}
 	}
 
+	/// The availability of the node this entity belongs to. The client publishes `online`
+	/// to this topic when it connects, and the broker publishes `offline` (as the last
+	/// will of the client) when it disconnects, unless other payloads are set with
+	/// [HassMqttOptions::online_payload](crate::HassMqttOptions::online_payload) and
+	/// [HassMqttOptions::offline_payload](crate::HassMqttOptions::offline_payload).
+	///
+	/// Use it when building the `availability` list of the entity document, for instance
+	/// with [AvailabilityList](hass_mqtt_proto::AvailabilityList).
+	pub fn availability(&self) -> Availability<'static> {
+		self.topics.availability()
+	}
+
+	/// The topic the discovery document of this entity is published to. This is the topic
+	/// given to [EntityTopicBuilder::with_topic], or the topic rendered from the discovery
+	/// topic template of the client (`{prefix}/{domain}/{node_id}/{entity_id}/config` by
+	/// default, where `{entity_id}` is the [object_id](EntityTopicBuilder::object_id) if
+	/// it's set).
+	pub fn discovery_topic(&self) -> Arc<str> {
+		self.topics.discovery_topic()
+	}
+
 	pub fn state_topic(&self) -> StateTopicBuilder {
 		let span = span!(
 			Level::DEBUG,
@@ -138,12 +189,24 @@ impl EntityTopic {
 }
 
 #[derive(Debug, Error)]
-#[error("failed to publish message on behalf of entity {domain}.{entity_id}")]
-pub struct EntityPublishError {
-	domain: Arc<str>,
-	entity_id: Arc<str>,
-	#[cfg_attr(provide_any, backtrace)]
-	source: DynError,
+pub enum EntityPublishError {
+	#[error("failed to publish message on behalf of entity {domain}.{entity_id}")]
+	Publish {
+		domain: Arc<str>,
+		entity_id: Arc<str>,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+
+	/// The message was not published within the timeout set on the builder.
+	#[error(
+		"timed out publishing message on behalf of entity {domain}.{entity_id} after {timeout:?}"
+	)]
+	TimedOut {
+		domain: Arc<str>,
+		entity_id: Arc<str>,
+		timeout: Duration,
+	},
 }
 
 impl EntityTopic {
@@ -156,17 +219,23 @@ impl EntityTopic {
 		self._publish(payload.into(), retained, qos).await
 	}
 
-	#[instrument(
-		level = Level::DEBUG,
-		name = "EntityTopic::publish",
-		skip_all,
-		fields(
-			entity.topic,
-			message.retained = retained,
-			message.qos = %qos,
-			message.payload.len = payload.len(),
-		)
-	)]
+	/// Publishes an already serialized discovery document to the discovery topic of the
+	/// entity, retained and with [QosLevel::AtLeastOnce] unless set otherwise on the
+	/// returned builder. Like documents published with
+	/// [publish_discovery](Self::publish_discovery), the payload is published again by
+	/// [republish_discovery](crate::HassMqttClient::republish_discovery).
+	///
+	/// Unlike [publish_discovery](Self::publish_discovery), the payload is published as is:
+	/// it's not validated, its `unique_id` is not checked for duplicates, and the QoS level
+	/// and retain flag configured on [HassMqttOptions](crate::HassMqttOptions) are not used.
+	pub fn discovery(&self) -> DiscoveryPublishBuilder<'_> {
+		DiscoveryPublishBuilder {
+			entity: self,
+			retained: true,
+			qos: QosLevel::AtLeastOnce,
+		}
+	}
+
 	async fn _publish(
 		&self,
 		payload: Arc<[u8]>,
@@ -174,12 +243,44 @@ impl EntityTopic {
 		qos: QosLevel,
 	) -> Result<(), EntityPublishError> {
 		let topic = self.topics.discovery_topic();
+		let sampled = self.client.sample_message();
+
+		// not using #[instrument] so that unsampled messages skip span creation
+		let span = MessageSpanSampler::span_if(sampled, || {
+			span!(
+				Level::DEBUG,
+				"EntityTopic::publish",
+				entity.topic = %topic,
+				message.retained = retained,
+				message.qos = %qos,
+				message.payload.len = payload.len(),
+			)
+		});
+
+		self
+			.client
+			.publish_message(topic, payload, retained, qos, sampled)
+			.instrument(span)
+			.await
+			.map_err(|source| EntityPublishError::Publish {
+				domain: self.topics.domain.clone(),
+				entity_id: self.topics.entity_id.clone(),
+				source: DynError::new(source),
+			})
+	}
+
+	/// Removes the entity from Home Assistant, by publishing an empty retained message to
+	/// its [discovery topic](Self::discovery_topic). This releases the `unique_id` of the
+	/// discovery document, and the document is no longer republished by
+	/// [republish_discovery](crate::HassMqttClient::republish_discovery).
+	pub async fn unpublish(&self) -> Result<(), EntityPublishError> {
+		let topic = self.topics.discovery_topic();
 
 		self
 			.client
-			.publish_message(topic, payload, retained, qos)
+			.publish_discovery(topic, None, Arc::from(&[][..]), true, QosLevel::AtLeastOnce)
 			.await
-			.map_err(|source| EntityPublishError {
+			.map_err(|source| EntityPublishError::Publish {
 				domain: self.topics.domain.clone(),
 				entity_id: self.topics.entity_id.clone(),
 				source: DynError::new(source),
@@ -187,14 +288,248 @@ impl EntityTopic {
 	}
 }
 
+pub struct DiscoveryPublishBuilder<'a> {
+	entity: &'a EntityTopic,
+	retained: bool,
+	qos: QosLevel,
+}
+
+impl<'a> DiscoveryPublishBuilder<'a> {
+	pub fn retain(self, retain: bool) -> Self {
+		DiscoveryPublishBuilder {
+			retained: retain,
+			..self
+		}
+	}
+
+	pub fn qos(self, qos: QosLevel) -> Self {
+		DiscoveryPublishBuilder { qos, ..self }
+	}
+
+	pub async fn publish(self, payload: impl Into<Arc<[u8]>>) -> Result<(), EntityPublishError> {
+		let entity = self.entity;
+		let topic = entity.topics.discovery_topic();
+
+		entity
+			.client
+			.publish_discovery(topic, None, payload.into(), self.retained, self.qos)
+			.await
+			.map_err(|source| EntityPublishError::Publish {
+				domain: entity.topics.domain.clone(),
+				entity_id: entity.topics.entity_id.clone(),
+				source: DynError::new(source),
+			})
+	}
+}
+
+impl EntityTopic {
+	/// Publishes the discovery document of the entity. Fails with
+	/// [PublishDiscoveryError::DuplicateUniqueId] if the `unique_id` of the document is
+	/// already used by another entity of this client.
+	///
+	/// The document is published with the QoS level and retain flag configured with
+	/// [HassMqttOptions::discovery_qos](crate::HassMqttOptions::discovery_qos) and
+	/// [HassMqttOptions::discovery_retain](crate::HassMqttOptions::discovery_retain),
+	/// unless set otherwise on the returned builder.
+	pub fn publish_discovery<'a, D>(&'a self, document: &'a D) -> PublishDiscoveryBuilder<'a, D>
+	where
+		D: Serialize + ?Sized,
+	{
+		PublishDiscoveryBuilder {
+			entity: self,
+			document,
+			invalid: None,
+			qos: None,
+			retain: None,
+		}
+	}
+
+	/// Validates the discovery document and publishes it like
+	/// [publish_discovery](Self::publish_discovery). Fails with
+	/// [PublishDiscoveryError::Invalid], listing all invalidities of the document, if it
+	/// doesn't validate.
+	pub fn publish_document<'a, D>(&'a self, document: &'a D) -> PublishDiscoveryBuilder<'a, D>
+	where
+		D: Document + Serialize,
+	{
+		PublishDiscoveryBuilder {
+			invalid: document.validate().err().map(DiscoveryValidationError::new),
+			..self.publish_discovery(document)
+		}
+	}
+}
+
 #[derive(Debug, Error)]
-#[error("failed to subscribe to command topic '{topic}' for entity {domain}.{entity_id}")]
-pub struct EntitySubscribeError {
-	domain: Arc<str>,
-	entity_id: Arc<str>,
-	topic: Arc<str>,
-	#[cfg_attr(provide_any, backtrace)]
-	source: DynError,
+pub enum ActivateEntityError {
+	#[error("failed to serialize discovery document of entity {domain}.{entity_id}")]
+	Serialize {
+		domain: Arc<str>,
+		entity_id: Arc<str>,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+
+	/// The discovery document has no `command_topic` to subscribe to.
+	#[error("discovery document of entity {domain}.{entity_id} has no command_topic")]
+	MissingCommandTopic {
+		domain: Arc<str>,
+		entity_id: Arc<str>,
+	},
+
+	#[error("failed to subscribe to the command topic of entity {domain}.{entity_id}")]
+	Subscribe {
+		domain: Arc<str>,
+		entity_id: Arc<str>,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+
+	#[error("failed to publish discovery document of entity {domain}.{entity_id}")]
+	PublishDiscovery {
+		domain: Arc<str>,
+		entity_id: Arc<str>,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+}
+
+impl EntityTopic {
+	/// Subscribes to the `command_topic` of the discovery document (with `qos`), and only
+	/// then publishes the document. This way no command is missed that Home Assistant sends
+	/// right after discovering the entity, which can happen when the document is published
+	/// before subscribing to the command topic.
+	pub async fn activate<D>(
+		&self,
+		document: &D,
+		qos: QosLevel,
+	) -> Result<CommandTopic, ActivateEntityError>
+	where
+		D: Serialize + ?Sized,
+	{
+		let domain = &self.topics.domain;
+		let entity_id = &self.topics.entity_id;
+
+		let document =
+			serde_json::to_value(document).map_err(|source| ActivateEntityError::Serialize {
+				domain: domain.clone(),
+				entity_id: entity_id.clone(),
+				source: DynError::new(source),
+			})?;
+
+		let topic = document
+			.get("command_topic")
+			.and_then(|topic| topic.as_str())
+			.ok_or_else(|| ActivateEntityError::MissingCommandTopic {
+				domain: domain.clone(),
+				entity_id: entity_id.clone(),
+			})?;
+
+		let command_topic = self
+			.command_topic()
+			.topic(topic)
+			.qos(qos)
+			.await
+			.map_err(|source| ActivateEntityError::Subscribe {
+				domain: domain.clone(),
+				entity_id: entity_id.clone(),
+				source: DynError::new(source),
+			})?;
+
+		self.publish_discovery(&document).await.map_err(|source| {
+			ActivateEntityError::PublishDiscovery {
+				domain: domain.clone(),
+				entity_id: entity_id.clone(),
+				source: DynError::new(source),
+			}
+		})?;
+
+		Ok(command_topic)
+	}
+}
+
+pub struct PublishDiscoveryBuilder<'a, D: ?Sized> {
+	entity: &'a EntityTopic,
+	document: &'a D,
+	invalid: Option<DiscoveryValidationError>,
+	qos: Option<QosLevel>,
+	retain: Option<bool>,
+}
+
+impl<'a, D: ?Sized> PublishDiscoveryBuilder<'a, D> {
+	pub fn qos(self, qos: QosLevel) -> Self {
+		PublishDiscoveryBuilder {
+			qos: Some(qos),
+			..self
+		}
+	}
+
+	pub fn retain(self, retain: bool) -> Self {
+		PublishDiscoveryBuilder {
+			retain: Some(retain),
+			..self
+		}
+	}
+}
+
+impl<'a, D> IntoFuture for PublishDiscoveryBuilder<'a, D>
+where
+	D: Serialize + ?Sized,
+{
+	type Output = Result<(), PublishDiscoveryError>;
+	type IntoFuture = BoxFuture<'a, Self::Output>;
+
+	fn into_future(self) -> Self::IntoFuture {
+		let client = &self.entity.client;
+		let topic = self.entity.topics.discovery_topic();
+		let qos = self.qos.unwrap_or(client.discovery_qos);
+		let retained = self.retain.unwrap_or(client.discovery_retain);
+
+		// the document is serialized up front, so it doesn't need to be sent to the future
+		let serialized = serde_json::to_value(self.document).and_then(|document| {
+			let unique_id = document
+				.get("unique_id")
+				.and_then(|unique_id| unique_id.as_str())
+				.map(Arc::from);
+			serde_json::to_vec(&document).map(|payload| (unique_id, payload))
+		});
+
+		async move {
+			if let Some(source) = self.invalid {
+				return Err(PublishDiscoveryError::Invalid { topic, source });
+			}
+
+			let (unique_id, payload) = serialized.map_err(|source| PublishDiscoveryError::Serialize {
+				topic: topic.clone(),
+				source: DynError::new(source),
+			})?;
+
+			client
+				.publish_discovery(topic, unique_id, payload.into(), retained, qos)
+				.await
+		}
+		.boxed()
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum EntitySubscribeError {
+	#[error("failed to subscribe to command topic '{topic}' for entity {domain}.{entity_id}")]
+	Subscribe {
+		domain: Arc<str>,
+		entity_id: Arc<str>,
+		topic: Arc<str>,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+
+	/// The command topic was not subscribed to within the timeout set on the builder.
+	#[error("timed out subscribing to command topic '{topic}' for entity {domain}.{entity_id} after {timeout:?}")]
+	TimedOut {
+		domain: Arc<str>,
+		entity_id: Arc<str>,
+		topic: Arc<str>,
+		timeout: Duration,
+	},
 }
 
 impl EntityTopic {
@@ -202,11 +537,32 @@ impl EntityTopic {
 		CommandTopicBuilder {
 			entity: self,
 			topic: TopicName::Default,
-			qos: QosLevel::AtMostOnce,
+			qos: self.client.default_command_qos,
+			encoding: PayloadEncoding::default(),
+			options: SubscribeOptions::default(),
+			timeout: None,
 		}
 	}
 }
 
+/// The name of an entity (`domain.entity_id`), as used in errors and logs.
+fn entity_name(domain: &str, entity_id: &str) -> Arc<str> {
+	Arc::from(format!("{domain}.{entity_id}"))
+}
+
+/// Awaits `future`, giving up (with the elapsed timeout as error) once `timeout` passed.
+async fn with_timeout<F: Future>(
+	timeout: Option<Duration>,
+	future: F,
+) -> Result<F::Output, Duration> {
+	match timeout {
+		None => Ok(future.await),
+		Some(timeout) => tokio::time::timeout(timeout, future)
+			.await
+			.map_err(|_| timeout),
+	}
+}
+
 enum TopicName {
 	Default,
 	Named(String),
@@ -258,13 +614,23 @@ impl<'a> IntoFuture for StateTopicBuilder<'a> {
 		let span_context = span.context().span().span_context().clone();
 
 		let topic = topic.get(|s| self.entity.topics.state_topic(s));
-		future::ready(Ok(StateTopic::new(
-			entity.client.clone(),
-			entity.topics.domain.clone(),
-			entity.topics.domain.clone(),
-			topic,
-			span_context,
-		)))
+		async move {
+			entity
+				.client
+				.register_state_topic(
+					topic.clone(),
+					entity_name(&entity.topics.domain, &entity.topics.entity_id),
+				)
+				.await;
+
+			Ok(StateTopic::new(
+				entity.client.clone(),
+				entity.topics.domain.clone(),
+				entity.topics.domain.clone(),
+				topic,
+				span_context,
+			))
+		}
 		.instrument(span)
 		.boxed()
 	}
@@ -274,6 +640,9 @@ pub struct CommandTopicBuilder<'a> {
 	entity: &'a EntityTopic,
 	topic: TopicName,
 	qos: QosLevel,
+	encoding: PayloadEncoding,
+	options: SubscribeOptions,
+	timeout: Option<Duration>,
 }
 
 impl<'a> CommandTopicBuilder<'a> {
@@ -294,6 +663,37 @@ impl<'a> CommandTopicBuilder<'a> {
 	pub fn qos(self, qos: QosLevel) -> Self {
 		CommandTopicBuilder { qos, ..self }
 	}
+
+	/// Sets the encoding used by [CommandTopic::decode_payload]. This should match the
+	/// `encoding` of the entity document.
+	pub fn encoding(self, encoding: PayloadEncoding) -> Self {
+		CommandTopicBuilder { encoding, ..self }
+	}
+
+	/// Sets whether messages published by this client on the command topic are sent back
+	/// to it. Only supported by MQTT v5.
+	pub fn no_local(mut self, on: bool) -> Self {
+		self.options.no_local = Some(on);
+		self
+	}
+
+	/// Sets when the broker sends the retained message of the command topic, for instance
+	/// to receive the current command value right away. Only supported by MQTT v5.
+	///
+	/// Like the QoS level, this only applies if nothing else subscribed to the topic yet.
+	pub fn retain_handling(mut self, handling: MqttRetainHandling) -> Self {
+		self.options.retain_handling = Some(handling);
+		self
+	}
+
+	/// Fails with [EntitySubscribeError::TimedOut] if the topic isn't subscribed to within
+	/// `timeout`, instead of waiting for the broker indefinitely.
+	pub fn timeout(self, timeout: Duration) -> Self {
+		CommandTopicBuilder {
+			timeout: Some(timeout),
+			..self
+		}
+	}
 }
 
 impl<'a> IntoFuture for CommandTopicBuilder<'a> {
@@ -302,7 +702,8 @@ impl<'a> IntoFuture for CommandTopicBuilder<'a> {
 
 	fn into_future(self) -> Self::IntoFuture {
 		let topic = self.topic.get(|s| self.entity.topics.command_topic(s));
-		let span = tracing::info_span!(
+		let span = span!(
+			Level::INFO,
 			"EntityTopic::command_topic",
 			entity = %self.entity.topics.entity_id,
 			topic = %topic,
@@ -312,21 +713,36 @@ impl<'a> IntoFuture for CommandTopicBuilder<'a> {
 		let span_context = span.context().span().span_context().clone();
 
 		async move {
-			let subscription = self
-				.entity
-				.client
-				.subscribe(topic.clone(), self.qos)
+			let domain = &self.entity.topics.domain;
+			let entity_id = &self.entity.topics.entity_id;
+			let subscribe = self.entity.client.subscribe(
+				topic.clone(),
+				self.qos,
+				Some(entity_name(domain, entity_id)),
+				self.options,
+			);
+
+			let subscription = with_timeout(self.timeout, subscribe)
 				.await
-				.map_err(|source| EntitySubscribeError {
-					domain: self.entity.topics.domain.clone(),
-					entity_id: self.entity.topics.entity_id.clone(),
+				.map_err(|timeout| EntitySubscribeError::TimedOut {
+					domain: domain.clone(),
+					entity_id: entity_id.clone(),
+					topic: topic.clone(),
+					timeout,
+				})?
+				.map_err(|source| EntitySubscribeError::Subscribe {
+					domain: domain.clone(),
+					entity_id: entity_id.clone(),
 					topic: topic.clone(),
 					source: DynError::new(source),
 				})?;
 
 			Ok(CommandTopic::new(
 				self.entity.client.clone(),
+				self.entity.topics.domain.clone(),
+				self.entity.topics.entity_id.clone(),
 				subscription,
+				self.encoding,
 				span_context,
 			))
 		}
@@ -370,39 +786,76 @@ impl StateTopic {
 		self.topic.clone()
 	}
 
-	pub async fn publish(
+	pub fn publish(
 		&self,
 		payload: impl Into<Arc<[u8]>>,
 		retained: bool,
 		qos: QosLevel,
-	) -> Result<(), EntityPublishError> {
-		self._publish(payload.into(), retained, qos).await
+	) -> StatePublishBuilder<'_> {
+		StatePublishBuilder {
+			state: self,
+			payload: Ok(payload.into()),
+			retained,
+			qos,
+			timeout: None,
+		}
+	}
+
+	/// Publishes the current value of the entity (retained), serialized as JSON. For
+	/// instance, `21.5` for a temperature sensor. Strings are JSON-encoded, so `"ON"`
+	/// is published with its quotes; use [StateTopic::publish] for raw payloads like
+	/// the `ON` a switch expects by default.
+	pub fn publish_value<T: Serialize + ?Sized>(
+		&self,
+		value: &T,
+		qos: QosLevel,
+	) -> StatePublishBuilder<'_> {
+		let payload =
+			serde_json::to_vec(value)
+				.map(Arc::from)
+				.map_err(|source| EntityPublishError::Publish {
+					domain: self.domain.clone(),
+					entity_id: self.entity_id.clone(),
+					source: DynError::new(source),
+				});
+
+		StatePublishBuilder {
+			state: self,
+			payload,
+			retained: true,
+			qos,
+			timeout: None,
+		}
 	}
 
-	#[instrument(
-		level = Level::DEBUG,
-		name = "StateTopic::publish",
-		skip_all,
-		fields(
-			state.topic = %self.topic,
-			message.retained = retained,
-			message.qos = %qos,
-			message.payload.len = payload.len(),
-		),
-	)]
 	async fn _publish(
 		&self,
 		payload: Arc<[u8]>,
 		retained: bool,
 		qos: QosLevel,
 	) -> Result<(), EntityPublishError> {
-		Span::current().add_link(self.span_context.clone());
+		let sampled = self.client.sample_message();
+
+		// not using #[instrument] so that unsampled messages skip span creation
+		let span = MessageSpanSampler::span_if(sampled, || {
+			let span = span!(
+				Level::DEBUG,
+				"StateTopic::publish",
+				state.topic = %self.topic,
+				message.retained = retained,
+				message.qos = %qos,
+				message.payload.len = payload.len(),
+			);
+			span.add_link(self.span_context.clone());
+			span
+		});
 
 		self
 			.client
-			.publish_message(self.topic.clone(), payload, retained, qos)
+			.publish_message(self.topic.clone(), payload, retained, qos, sampled)
+			.instrument(span)
 			.await
-			.map_err(|source| EntityPublishError {
+			.map_err(|source| EntityPublishError::Publish {
 				domain: self.domain.clone(),
 				entity_id: self.entity_id.clone(),
 				source: DynError::new(source),
@@ -410,11 +863,54 @@ impl StateTopic {
 	}
 }
 
+pub struct StatePublishBuilder<'a> {
+	state: &'a StateTopic,
+	payload: Result<Arc<[u8]>, EntityPublishError>,
+	retained: bool,
+	qos: QosLevel,
+	timeout: Option<Duration>,
+}
+
+impl<'a> StatePublishBuilder<'a> {
+	/// Fails with [EntityPublishError::TimedOut] if the message isn't published within
+	/// `timeout`, instead of waiting for the broker indefinitely.
+	pub fn timeout(self, timeout: Duration) -> Self {
+		StatePublishBuilder {
+			timeout: Some(timeout),
+			..self
+		}
+	}
+}
+
+impl<'a> IntoFuture for StatePublishBuilder<'a> {
+	type Output = Result<(), EntityPublishError>;
+	type IntoFuture = BoxFuture<'a, Self::Output>;
+
+	fn into_future(self) -> Self::IntoFuture {
+		let state = self.state;
+
+		async move {
+			let publish = state._publish(self.payload?, self.retained, self.qos);
+			with_timeout(self.timeout, publish)
+				.await
+				.map_err(|timeout| EntityPublishError::TimedOut {
+					domain: state.domain.clone(),
+					entity_id: state.entity_id.clone(),
+					timeout,
+				})?
+		}
+		.boxed()
+	}
+}
+
 #[pin_project]
 pub struct CommandTopic {
-	_client: HassMqttClient,
+	client: HassMqttClient,
+	domain: Arc<str>,
+	entity_id: Arc<str>,
 	#[pin]
 	subscription: Subscription,
+	encoding: PayloadEncoding,
 	span_context: SpanContext,
 }
 
@@ -427,12 +923,18 @@ impl<'a> From<&'a CommandTopic> for hass_mqtt_proto::Topic<'a> {
 impl CommandTopic {
 	pub(crate) fn new(
 		client: HassMqttClient,
+		domain: Arc<str>,
+		entity_id: Arc<str>,
 		subscription: Subscription,
+		encoding: PayloadEncoding,
 		span_context: SpanContext,
 	) -> Self {
 		CommandTopic {
-			_client: client,
+			client,
+			domain,
+			entity_id,
 			subscription,
+			encoding,
 			span_context,
 		}
 	}
@@ -440,6 +942,113 @@ impl CommandTopic {
 	pub fn topic(&self) -> Arc<str> {
 		self.subscription.topic.clone()
 	}
+
+	pub fn encoding(&self) -> PayloadEncoding {
+		self.encoding
+	}
+
+	/// The QoS level the broker granted for the subscription to this topic, which
+	/// might be lower than the requested one.
+	pub fn granted_qos(&self) -> QosLevel {
+		self.subscription.granted_qos
+	}
+
+	/// Changes the QoS level of the subscription to this topic, returning the QoS level
+	/// granted by the broker.
+	///
+	/// The topic is subscribed to again with the new level, which replaces the existing
+	/// subscription at the broker without unsubscribing first, so no messages are lost
+	/// during the change. The subscription is shared with the other consumers of the
+	/// topic (see [CommandTopic::fan_out]), so their QoS level changes as well.
+	pub async fn set_qos(&mut self, qos: QosLevel) -> Result<QosLevel, EntitySubscribeError> {
+		let topic = self.subscription.topic.clone();
+		let span = span!(
+			Level::INFO,
+			"CommandTopic::set_qos",
+			entity = %self.entity_id,
+			topic = %topic,
+			qos = %qos,
+		);
+		span.add_link(self.span_context.clone());
+
+		let granted_qos = self
+			.client
+			.set_qos(topic.clone(), qos)
+			.instrument(span)
+			.await
+			.map_err(|source| EntitySubscribeError::Subscribe {
+				domain: self.domain.clone(),
+				entity_id: self.entity_id.clone(),
+				topic,
+				source: DynError::new(source),
+			})?;
+
+		self.subscription.qos = qos;
+		self.subscription.granted_qos = granted_qos;
+		Ok(granted_qos)
+	}
+
+	/// Decodes the payload of a message received on this topic using the encoding of
+	/// the topic. With an empty (raw) encoding the payload is returned as-is.
+	pub fn decode_payload<'m>(
+		&self,
+		message: &'m Message,
+	) -> Result<DecodedPayload<'m>, PayloadDecodeError> {
+		message.decode_payload(self.encoding)
+	}
+
+	/// Decodes the JSON payload of every message received on this topic into a `T`.
+	///
+	/// Messages that fail to decode are yielded as errors, which keep the span of the
+	/// message so they can be correlated with it.
+	pub fn decoded<T: DeserializeOwned>(self) -> impl Stream<Item = Result<T, DecodeError>> {
+		self.map(Message::decode_json)
+	}
+
+	/// Creates another consumer of this topic, which receives every message received on
+	/// the topic from now on, independently of this one.
+	///
+	/// Cloning the stream of messages doesn't work for this, as the clones would compete
+	/// for the same messages. The topic stays subscribed to at the broker until both
+	/// consumers are dropped.
+	pub async fn fan_out(&self) -> Result<CommandTopic, EntitySubscribeError> {
+		let topic = self.subscription.topic.clone();
+		let span = span!(
+			Level::DEBUG,
+			"CommandTopic::fan_out",
+			entity.domain = %self.domain,
+			entity.id = %self.entity_id,
+			subscription.topic = %topic,
+		);
+		span.add_link(self.span_context.clone());
+
+		let subscription = self
+			.client
+			.subscribe(
+				topic.clone(),
+				self.subscription.qos,
+				Some(entity_name(&self.domain, &self.entity_id)),
+				// the route of the command topic already exists, so options have no effect
+				SubscribeOptions::default(),
+			)
+			.instrument(span)
+			.await
+			.map_err(|source| EntitySubscribeError::Subscribe {
+				domain: self.domain.clone(),
+				entity_id: self.entity_id.clone(),
+				topic,
+				source: DynError::new(source),
+			})?;
+
+		Ok(CommandTopic::new(
+			self.client.clone(),
+			self.domain.clone(),
+			self.entity_id.clone(),
+			subscription,
+			self.encoding,
+			self.span_context.clone(),
+		))
+	}
 }
 
 impl Stream for CommandTopic {
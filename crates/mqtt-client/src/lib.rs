@@ -3,18 +3,42 @@
 
 mod availability;
 mod client;
+mod discovery;
+mod encoding;
 mod entity;
+#[cfg(test)]
+mod mock;
 mod mqtt;
 mod options;
+#[cfg(feature = "trace-payloads")]
+mod payload_trace;
 mod router;
 mod topics;
+mod trace;
 mod tracking;
 
-pub use client::{ConnectError, HassMqttClient, Message};
+pub use client::{
+	ClientStats, ConnectError, HassMqttClient, Message, PublishDiscoveryError,
+	RepublishDiscoveryError, SetAvailableError, TimeoutError,
+};
+pub use discovery::{DiscoveryDocumentError, DiscoveryValidationError, InvalidDiscoveryDocument};
+pub use encoding::{
+	DecodeError, DecodedPayload, PayloadDecodeError, PayloadEncoding, UnsupportedEncodingError,
+};
 pub use entity::{
-	CommandTopic, CommandTopicBuilder, CreateEntityError, EntityPublishError, EntitySubscribeError,
-	EntityTopic, EntityTopicBuilder, StateTopic, StateTopicBuilder,
+	ActivateEntityError, CommandTopic, CommandTopicBuilder, CreateEntityError,
+	DiscoveryPublishBuilder, EntityPublishError, EntitySubscribeError, EntityTopic,
+	EntityTopicBuilder, PublishDiscoveryBuilder, StatePublishBuilder, StateTopic, StateTopicBuilder,
 };
 pub use hass_mqtt_proto as proto;
-pub use hass_mqtt_provider::QosLevel;
-pub use options::{HassMqttOptions, MqttOptionsError, MqttPersistenceError};
+pub use hass_mqtt_proto::MqttQoS;
+#[cfg(feature = "tls")]
+pub use hass_mqtt_provider::MqttTlsConfig;
+pub use hass_mqtt_provider::{MqttRetainHandling, MqttTransport, QosLevel, ServerInfo};
+pub use options::{
+	HassMqttOptions, MqttOptionsError, MqttPersistenceError, MqttUrlError, PrefixOverlapError,
+	TopicCollisions,
+};
+pub use topics::{
+	DiscoveryTopicTemplate, DiscoveryTopicTemplateError, EntityTopicsConfig, TopicsConfig,
+};
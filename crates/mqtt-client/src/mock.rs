@@ -0,0 +1,616 @@
+//! An in-memory [MqttProvider] used by the tests of this crate.
+#![allow(dead_code)]
+
+use async_trait::async_trait;
+use futures::{future::LocalBoxFuture, FutureExt, Stream};
+use hass_mqtt_provider::{
+	AsMqttOptions, MessageSpanSampler, MqttBuildableMessage, MqttClient, MqttConnectionEvent,
+	MqttDisconnectBuilder, MqttMessage, MqttMessageBuilder, MqttProvider, MqttProviderCreateError,
+	MqttPublishBuilder, MqttReceivedMessage, MqttRetainHandling, MqttSubscribeBuilder,
+	MqttUnsubscribeBuilder, QosLevel, ServerInfo, Subscribed,
+};
+use pin_project::pin_project;
+use std::{
+	collections::BTreeMap,
+	convert::Infallible,
+	future::IntoFuture,
+	net::SocketAddr,
+	pin::Pin,
+	sync::{Arc, Mutex},
+	task::{Context, Poll},
+	thread,
+	time::Duration,
+};
+use thiserror::Error;
+use tokio::net::lookup_host;
+use tracing::{span, Instrument, Level, Span};
+
+/// The `no_local` and `retain_handling` options of a subscribe.
+type MockSubscribeOptions = (Option<bool>, Option<MqttRetainHandling>);
+
+static CLIENTS: Mutex<BTreeMap<String, Arc<MockState>>> = Mutex::new(BTreeMap::new());
+static SERVER_INFO: Mutex<BTreeMap<String, ServerInfo>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Error)]
+#[error("mock MQTT error: {0}")]
+pub(crate) struct MockError(String);
+
+impl MqttProviderCreateError for MockError {
+	fn create_message(
+		kind: impl Into<String>,
+		source: impl std::error::Error + Send + Sync + 'static,
+	) -> Self {
+		MockError(format!("{}: {}", kind.into(), source))
+	}
+}
+
+pub(crate) struct MockMqtt;
+
+impl MockMqtt {
+	/// Gets the state of the client created for `client_id`.
+	pub(crate) fn state(client_id: &str) -> Arc<MockState> {
+		CLIENTS
+			.lock()
+			.unwrap()
+			.get(client_id)
+			.cloned()
+			.expect("client should have been created")
+	}
+
+	/// Makes the (mock) broker announce `server_info` when a client connects with
+	/// `client_id`.
+	pub(crate) fn set_server_info(client_id: &str, server_info: ServerInfo) {
+		SERVER_INFO
+			.lock()
+			.unwrap()
+			.insert(client_id.into(), server_info);
+	}
+}
+
+fn announced_server_info(client_id: &str) -> ServerInfo {
+	SERVER_INFO
+		.lock()
+		.unwrap()
+		.get(client_id)
+		.cloned()
+		.unwrap_or_default()
+}
+
+#[async_trait(?Send)]
+impl MqttProvider for MockMqtt {
+	const NAME: &'static str = "mock";
+
+	type Client = MockClient;
+	type Message = MockMessage;
+	type Error = MockError;
+
+	async fn create(
+		options: &impl AsMqttOptions,
+		client_id: &str,
+		online_message: Self::Message,
+		offline_message: Self::Message,
+	) -> Result<Self::Client, Self::Error> {
+		let options = options
+			.mqtt_options()
+			.map_err(|e| MockError::create_message("options", e))?;
+		let broker_addrs = lookup_host((&*options.host, options.port))
+			.await
+			.map_err(|e| MockError::create_message("resolve host", e))?
+			.collect();
+		let server_info = Mutex::new(announced_server_info(client_id));
+
+		let (sender, receiver) = flume::unbounded();
+		let (event_sender, event_receiver) = flume::unbounded();
+		let state = Arc::new(MockState {
+			client_id: client_id.into(),
+			thread_name: thread::current().name().map(String::from),
+			online_message,
+			offline_message,
+			subscriptions: Mutex::default(),
+			subscribe_log: Mutex::default(),
+			subscribe_options: Mutex::default(),
+			published: Mutex::default(),
+			published_sampled: Mutex::default(),
+			max_packet_size: Mutex::default(),
+			broker_addrs,
+			server_info,
+			message_span_sampler: options.message_span_sampler.clone(),
+			granted_qos: Mutex::default(),
+			gates: Mutex::default(),
+			sender,
+			receiver,
+			event_sender,
+			event_receiver,
+		});
+
+		CLIENTS
+			.lock()
+			.unwrap()
+			.insert(client_id.into(), state.clone());
+
+		Ok(MockClient { state })
+	}
+}
+
+/// Makes subscribes to and publishes on a topic wait until the gate is released.
+pub(crate) struct MockGate {
+	started: flume::Receiver<()>,
+	release: flume::Sender<()>,
+}
+
+impl MockGate {
+	/// Waits until a subscribe to (or publish on) the gated topic has started.
+	pub(crate) async fn started(&self) {
+		self.started.recv_async().await.unwrap();
+	}
+
+	/// Lets the pending subscribe to (or publish on) the gated topic complete.
+	pub(crate) fn release(&self) {
+		self.release.send(()).unwrap();
+	}
+}
+
+struct MockGateInner {
+	started: flume::Sender<()>,
+	release: flume::Receiver<()>,
+}
+
+pub(crate) struct MockState {
+	client_id: Arc<str>,
+	/// The name of the thread the client was created on.
+	pub(crate) thread_name: Option<String>,
+	pub(crate) online_message: MockMessage,
+	pub(crate) offline_message: MockMessage,
+	subscriptions: Mutex<Vec<(Arc<str>, QosLevel)>>,
+	subscribe_log: Mutex<Vec<Arc<str>>>,
+	subscribe_options: Mutex<BTreeMap<Arc<str>, MockSubscribeOptions>>,
+	published: Mutex<Vec<MockMessage>>,
+	published_sampled: Mutex<Vec<bool>>,
+	max_packet_size: Mutex<Option<u32>>,
+	broker_addrs: Vec<SocketAddr>,
+	server_info: Mutex<ServerInfo>,
+	message_span_sampler: MessageSpanSampler,
+	granted_qos: Mutex<BTreeMap<Arc<str>, QosLevel>>,
+	gates: Mutex<BTreeMap<Arc<str>, MockGateInner>>,
+	sender: flume::Sender<MockMessage>,
+	receiver: flume::Receiver<MockMessage>,
+	event_sender: flume::Sender<MqttConnectionEvent>,
+	event_receiver: flume::Receiver<MqttConnectionEvent>,
+}
+
+impl MockState {
+	/// Topics currently subscribed to at the (mock) broker.
+	pub(crate) fn subscriptions(&self) -> Vec<(Arc<str>, QosLevel)> {
+		self.subscriptions.lock().unwrap().clone()
+	}
+
+	/// Every topic that was ever subscribed to, in order.
+	pub(crate) fn subscribe_log(&self) -> Vec<Arc<str>> {
+		self.subscribe_log.lock().unwrap().clone()
+	}
+
+	/// The `no_local` and `retain_handling` options of the last subscribe to `topic`.
+	pub(crate) fn subscribe_options(&self, topic: &str) -> Option<MockSubscribeOptions> {
+		self.subscribe_options.lock().unwrap().get(topic).copied()
+	}
+
+	/// Every message that was published, in order.
+	pub(crate) fn published(&self) -> Vec<MockMessage> {
+		self.published.lock().unwrap().clone()
+	}
+
+	/// Whether every message that was published got a span, in order.
+	pub(crate) fn published_sampled(&self) -> Vec<bool> {
+		self.published_sampled.lock().unwrap().clone()
+	}
+
+	/// Sets the maximum packet size announced by the (mock) broker.
+	pub(crate) fn set_max_packet_size(&self, max: Option<u32>) {
+		*self.max_packet_size.lock().unwrap() = max;
+	}
+
+	/// Makes the (mock) broker grant `qos` for subscribes to `topic`, regardless of
+	/// the requested QoS level.
+	pub(crate) fn grant_qos(&self, topic: impl Into<Arc<str>>, qos: QosLevel) {
+		self.granted_qos.lock().unwrap().insert(topic.into(), qos);
+	}
+
+	/// Sends a message to the client as if it came from the broker.
+	pub(crate) fn receive(&self, message: MockMessage) {
+		self.sender.send(message).unwrap();
+	}
+
+	/// Simulates losing the connection to the (mock) broker and reconnecting with a
+	/// clean session, which drops all subscriptions at the broker.
+	pub(crate) fn reconnect(&self) {
+		self.lose_connection();
+		self.connect();
+	}
+
+	/// Simulates losing the connection to the (mock) broker, which drops all
+	/// subscriptions at the broker (clean session).
+	pub(crate) fn lose_connection(&self) {
+		self
+			.event_sender
+			.send(MqttConnectionEvent::ConnectionLost)
+			.unwrap();
+		self.subscriptions.lock().unwrap().clear();
+	}
+
+	/// Simulates the (mock) broker closing the connection for `reason`, which drops all
+	/// subscriptions at the broker (clean session).
+	pub(crate) fn disconnect(&self, reason: &str) {
+		self
+			.event_sender
+			.send(MqttConnectionEvent::Disconnected {
+				reason: reason.into(),
+			})
+			.unwrap();
+		self.subscriptions.lock().unwrap().clear();
+	}
+
+	/// Simulates (re)connecting to the (mock) broker, which announces the server info set
+	/// with [MockMqtt::set_server_info].
+	pub(crate) fn connect(&self) {
+		*self.server_info.lock().unwrap() = announced_server_info(&self.client_id);
+		self
+			.event_sender
+			.send(MqttConnectionEvent::Connected)
+			.unwrap();
+	}
+
+	/// Makes the next subscribe to or publish on `topic` wait until the returned gate is
+	/// released.
+	pub(crate) fn gate(&self, topic: impl Into<Arc<str>>) -> MockGate {
+		let (started_sender, started_receiver) = flume::unbounded();
+		let (release_sender, release_receiver) = flume::unbounded();
+		self.gates.lock().unwrap().insert(
+			topic.into(),
+			MockGateInner {
+				started: started_sender,
+				release: release_receiver,
+			},
+		);
+
+		MockGate {
+			started: started_receiver,
+			release: release_sender,
+		}
+	}
+
+	/// Waits for the gate of `topic` to be released, if there is one.
+	async fn wait_for_gate(&self, topic: &str) {
+		let gate = self.gates.lock().unwrap().remove(topic);
+		if let Some(gate) = gate {
+			let _ = gate.started.send(());
+			let _ = gate.release.recv_async().await;
+		}
+	}
+}
+
+#[derive(Clone)]
+pub(crate) struct MockClient {
+	state: Arc<MockState>,
+}
+
+impl MqttClient for MockClient {
+	type Provider = MockMqtt;
+	type Message = MockMessage;
+	type Messages = MockMessageStream;
+	type ConnectionEvents = flume::r#async::RecvStream<'static, MqttConnectionEvent>;
+	type SubscriptionKey = Arc<str>;
+	type PublishBuilder<'a> = MockPublishBuilder<'a>;
+	type SubscribeBuilder<'a> = MockSubscribeBuilder<'a>;
+	type UnsubscribeBuilder<'a> = MockUnsubscribeBuilder<'a>;
+	type DisconnectBuilder<'a> = MockDisconnectBuilder<'a>;
+
+	fn client_id(&self) -> Arc<str> {
+		self.state.client_id.clone()
+	}
+
+	fn max_packet_size(&self) -> Option<u32> {
+		*self.state.max_packet_size.lock().unwrap()
+	}
+
+	fn broker_addrs(&self) -> &[SocketAddr] {
+		&self.state.broker_addrs
+	}
+
+	fn server_info(&self) -> ServerInfo {
+		self.state.server_info.lock().unwrap().clone()
+	}
+
+	fn messages(&self) -> Self::Messages {
+		MockMessageStream {
+			inner: self.state.receiver.clone().into_stream(),
+		}
+	}
+
+	fn connection_events(&self) -> Self::ConnectionEvents {
+		self.state.event_receiver.clone().into_stream()
+	}
+
+	fn publish(&self, message: Self::Message) -> Self::PublishBuilder<'_> {
+		MockPublishBuilder {
+			client: self,
+			message,
+			sampled: None,
+		}
+	}
+
+	fn subscribe(&self, topic: impl Into<Arc<str>>, qos: QosLevel) -> Self::SubscribeBuilder<'_> {
+		MockSubscribeBuilder {
+			client: self,
+			topic: topic.into(),
+			qos,
+			no_local: None,
+			retain_handling: None,
+		}
+	}
+
+	fn unsubscribe(&self, key: Self::SubscriptionKey) -> Self::UnsubscribeBuilder<'_> {
+		MockUnsubscribeBuilder { client: self, key }
+	}
+
+	fn disconnect(&self) -> Self::DisconnectBuilder<'_> {
+		MockDisconnectBuilder { _client: self }
+	}
+}
+
+#[pin_project]
+pub(crate) struct MockMessageStream {
+	#[pin]
+	inner: flume::r#async::RecvStream<'static, MockMessage>,
+}
+
+impl Stream for MockMessageStream {
+	type Item = MqttReceivedMessage<MockClient>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self
+			.project()
+			.inner
+			.poll_next(cx)
+			.map(|message| message.map(|message| MqttReceivedMessage::new(message, Span::none())))
+	}
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MockMessage {
+	pub(crate) topic: String,
+	pub(crate) payload: Vec<u8>,
+	pub(crate) qos: Option<QosLevel>,
+	pub(crate) retain: bool,
+	pub(crate) dup: bool,
+}
+
+impl MqttMessage for MockMessage {
+	type Client = MockClient;
+
+	fn topic(&self) -> &str {
+		&self.topic
+	}
+
+	fn payload(&self) -> &[u8] {
+		&self.payload
+	}
+
+	fn retained(&self) -> bool {
+		self.retain
+	}
+
+	fn qos(&self) -> QosLevel {
+		self.qos.unwrap_or(QosLevel::AtMostOnce)
+	}
+
+	fn dup(&self) -> bool {
+		self.dup
+	}
+}
+
+impl MqttBuildableMessage for MockMessage {
+	type Builder = MockMessage;
+
+	fn builder() -> Self::Builder {
+		MockMessage::default()
+	}
+}
+
+impl MqttMessageBuilder for MockMessage {
+	type Message = MockMessage;
+	type Error = Infallible;
+
+	fn topic(self, topic: impl Into<String>) -> Self {
+		MockMessage {
+			topic: topic.into(),
+			..self
+		}
+	}
+
+	fn payload(self, payload: impl Into<Vec<u8>>) -> Self {
+		MockMessage {
+			payload: payload.into(),
+			..self
+		}
+	}
+
+	fn qos(self, qos: QosLevel) -> Self {
+		MockMessage {
+			qos: Some(qos),
+			..self
+		}
+	}
+
+	fn retain(self, retain: bool) -> Self {
+		MockMessage { retain, ..self }
+	}
+
+	fn build(self) -> Result<Self::Message, Self::Error> {
+		Ok(self)
+	}
+}
+
+pub(crate) struct MockPublishBuilder<'a> {
+	client: &'a MockClient,
+	message: MockMessage,
+	sampled: Option<bool>,
+}
+
+impl<'a> MqttPublishBuilder for MockPublishBuilder<'a> {
+	type Error = MockError;
+
+	fn sampled(self, sampled: bool) -> Self {
+		MockPublishBuilder {
+			sampled: Some(sampled),
+			..self
+		}
+	}
+}
+
+impl<'a> IntoFuture for MockPublishBuilder<'a> {
+	type Output = Result<(), MockError>;
+	type IntoFuture = LocalBoxFuture<'a, Self::Output>;
+
+	fn into_future(self) -> Self::IntoFuture {
+		let state = &self.client.state;
+		let sampled = self
+			.sampled
+			.unwrap_or_else(|| state.message_span_sampler.sample());
+		let span = MessageSpanSampler::span_if(
+			sampled,
+			|| span!(Level::DEBUG, "MockMqtt::publish", message.topic = %self.message.topic),
+		);
+
+		async move {
+			state.wait_for_gate(&self.message.topic).await;
+			state.published.lock().unwrap().push(self.message);
+			state.published_sampled.lock().unwrap().push(sampled);
+			Ok(())
+		}
+		.instrument(span)
+		.boxed_local()
+	}
+}
+
+pub(crate) struct MockSubscribeBuilder<'a> {
+	client: &'a MockClient,
+	topic: Arc<str>,
+	qos: QosLevel,
+	no_local: Option<bool>,
+	retain_handling: Option<MqttRetainHandling>,
+}
+
+impl<'a> MqttSubscribeBuilder for MockSubscribeBuilder<'a> {
+	type SubscriptionKey = Arc<str>;
+	type Error = MockError;
+
+	fn no_local(self, on: bool) -> Self {
+		MockSubscribeBuilder {
+			no_local: Some(on),
+			..self
+		}
+	}
+
+	fn retain_handling(self, handling: MqttRetainHandling) -> Self {
+		MockSubscribeBuilder {
+			retain_handling: Some(handling),
+			..self
+		}
+	}
+}
+
+impl<'a> IntoFuture for MockSubscribeBuilder<'a> {
+	type Output = Result<Subscribed<Arc<str>>, MockError>;
+	type IntoFuture = LocalBoxFuture<'a, Self::Output>;
+
+	fn into_future(self) -> Self::IntoFuture {
+		async move {
+			let state = &self.client.state;
+			state.subscribe_log.lock().unwrap().push(self.topic.clone());
+			state
+				.subscribe_options
+				.lock()
+				.unwrap()
+				.insert(self.topic.clone(), (self.no_local, self.retain_handling));
+
+			state.wait_for_gate(&self.topic).await;
+
+			let granted_qos = state
+				.granted_qos
+				.lock()
+				.unwrap()
+				.get(&self.topic)
+				.copied()
+				.unwrap_or(self.qos);
+
+			// subscribing to a topic again replaces the subscription
+			let mut subscriptions = state.subscriptions.lock().unwrap();
+			match subscriptions
+				.iter_mut()
+				.find(|(topic, _)| *topic == self.topic)
+			{
+				Some(subscription) => subscription.1 = granted_qos,
+				None => subscriptions.push((self.topic.clone(), granted_qos)),
+			}
+
+			Ok(Subscribed {
+				key: self.topic,
+				granted_qos,
+			})
+		}
+		.boxed_local()
+	}
+}
+
+pub(crate) struct MockUnsubscribeBuilder<'a> {
+	client: &'a MockClient,
+	key: Arc<str>,
+}
+
+impl<'a> MqttUnsubscribeBuilder for MockUnsubscribeBuilder<'a> {
+	type Error = MockError;
+}
+
+impl<'a> IntoFuture for MockUnsubscribeBuilder<'a> {
+	type Output = Result<(), MockError>;
+	type IntoFuture = LocalBoxFuture<'a, Self::Output>;
+
+	fn into_future(self) -> Self::IntoFuture {
+		async move {
+			let mut subscriptions = self.client.state.subscriptions.lock().unwrap();
+			let idx = subscriptions
+				.iter()
+				.position(|(topic, _)| *topic == self.key)
+				.ok_or_else(|| MockError(format!("not subscribed to '{}'", self.key)))?;
+
+			subscriptions.remove(idx);
+			Ok(())
+		}
+		.boxed_local()
+	}
+}
+
+pub(crate) struct MockDisconnectBuilder<'a> {
+	_client: &'a MockClient,
+}
+
+impl<'a> MqttDisconnectBuilder for MockDisconnectBuilder<'a> {
+	type Error = MockError;
+
+	fn publish_last_will(self, _on: bool) -> Self {
+		self
+	}
+
+	fn after(self, _timeout: Duration) -> Self {
+		self
+	}
+}
+
+impl<'a> IntoFuture for MockDisconnectBuilder<'a> {
+	type Output = Result<(), MockError>;
+	type IntoFuture = LocalBoxFuture<'a, Self::Output>;
+
+	fn into_future(self) -> Self::IntoFuture {
+		async move { Ok(()) }.boxed_local()
+	}
+}
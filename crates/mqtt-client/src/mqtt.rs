@@ -1,7 +1,6 @@
 use crate::topics::TopicsConfig;
 use async_trait::async_trait;
 use hass_mqtt_provider::{MqttClient, MqttProvider, MqttProviderCreateError};
-use tracing::{instrument, Level};
 
 pub(crate) struct HassMqttConnection<T>
 where
@@ -14,29 +13,29 @@ where
 
 #[async_trait(?Send)]
 pub(crate) trait MqttProviderExt: MqttProvider {
-	#[instrument(
-		level = Level::DEBUG,
-		name = "MqttProvider::create_client",
-		skip_all,
-		fields(
-			provider.name = %Self::NAME,
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(
+			level = tracing::Level::DEBUG,
+			name = "MqttProvider::create_client",
+			skip_all,
+			fields(
+				provider.name = %Self::NAME,
+			)
 		)
 	)]
 	async fn create_client(
 		options: &crate::HassMqttOptions,
 	) -> Result<HassMqttConnection<Self::Client>, Self::Error> {
-		let node_id = options.node_id.clone();
-		let client_id = format!("{}_{}", options.application_name.slug(), options.node_id);
-		let topics = TopicsConfig::new(
-			options
-				.private_prefix
-				.as_deref()
-				.unwrap_or_else(|| options.application_name.slug()),
-			&*options.discovery_prefix,
-			node_id.clone(),
-		);
+		let client_id = match &options.client_id {
+			Some(client_id) => client_id.clone(),
+			None => format!("{}_{}", options.application_name.slug(), options.node_id),
+		};
+		let topics = options.topics();
+		// the messages are retained unless the broker doesn't support it, which the provider
+		// only learns while connecting
 		let online_message = topics
-			.online_message()
+			.online_message(true)
 			.map_err(|e| Self::Error::create_message("online", e))?;
 		let offline_message = topics
 			.offline_message()
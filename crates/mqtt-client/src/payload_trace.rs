@@ -0,0 +1,72 @@
+use crate::trace::event;
+use std::fmt;
+use tracing::Level;
+
+/// Logs message payloads as `TRACE` events in the current (message) span. Payloads
+/// that are valid UTF-8 are logged as text, anything else as hex. Only the first
+/// `max_bytes` bytes of a payload are logged, to avoid flooding the logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct PayloadTracer {
+	max_bytes: usize,
+}
+
+impl PayloadTracer {
+	pub(crate) fn new(max_bytes: usize) -> Self {
+		PayloadTracer { max_bytes }
+	}
+
+	/// Logs `payload` at `TRACE` level, truncated to `max_bytes`.
+	pub(crate) fn trace(&self, payload: &[u8]) {
+		if !tracing::enabled!(Level::TRACE) {
+			return;
+		}
+
+		let truncated = payload.len() > self.max_bytes;
+		let payload = &payload[..payload.len().min(self.max_bytes)];
+		event!(
+			Level::TRACE,
+			message.payload = %TracedPayload(payload),
+			message.payload.truncated = truncated,
+			"message payload",
+		);
+	}
+}
+
+struct TracedPayload<'a>(&'a [u8]);
+
+impl fmt::Display for TracedPayload<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let text = match std::str::from_utf8(self.0) {
+			Ok(text) => Some(text),
+			// the payload was truncated in the middle of a character
+			Err(e) if e.error_len().is_none() => std::str::from_utf8(&self.0[..e.valid_up_to()]).ok(),
+			Err(_) => None,
+		};
+
+		match text {
+			Some(text) => f.write_str(text),
+			None => self.0.iter().try_for_each(|b| write!(f, "{b:02x}")),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn formats_utf8_as_text() {
+		assert_eq!(TracedPayload(b"ON").to_string(), "ON");
+	}
+
+	#[test]
+	fn formats_binary_as_hex() {
+		assert_eq!(TracedPayload(&[0xff, 0x00, 0x1a]).to_string(), "ff001a");
+	}
+
+	#[test]
+	fn formats_utf8_cut_mid_character_as_text() {
+		let payload = "a€".as_bytes();
+		assert_eq!(TracedPayload(&payload[..2]).to_string(), "a");
+	}
+}
@@ -1,8 +1,9 @@
 use generational_arena::{Arena, Index};
 use std::{
-	collections::{btree_map, BTreeMap},
-	ops,
+	collections::{btree_map, BTreeMap, BTreeSet},
+	ops, slice,
 	sync::Arc,
+	vec,
 };
 
 #[derive(Debug)]
@@ -24,9 +25,13 @@ impl<T> Nodes<T> {
 		self.nodes.push(id)
 	}
 
-	fn remove(&mut self, id: Index) -> Option<Index> {
+	fn remove(&mut self, id: Index, ordered: bool) -> Option<Index> {
 		let index = self.nodes.iter().position(|node| *node == id)?;
-		Some(self.nodes.swap_remove(index))
+		if ordered {
+			Some(self.nodes.remove(index))
+		} else {
+			Some(self.nodes.swap_remove(index))
+		}
 	}
 
 	fn is_empty(&self) -> bool {
@@ -40,10 +45,57 @@ impl<T> Node<T> {
 	}
 }
 
+/// The topic filter of `route`, without the `$share/<group>/` prefix of shared
+/// subscriptions. The broker delivers messages of shared subscriptions on the topics
+/// matching the filter.
+fn topic_filter(route: &str) -> &str {
+	match route
+		.strip_prefix("$share/")
+		.and_then(|rest| rest.split_once('/'))
+	{
+		Some((_group, filter)) => filter,
+		None => route,
+	}
+}
+
+/// Whether `route` has to be matched as a topic filter, because it's a shared
+/// subscription or has `+` or `#` wildcards.
+fn is_filter(route: &str) -> bool {
+	let filter = topic_filter(route);
+	filter.len() != route.len() || filter.split('/').any(|level| level == "+" || level == "#")
+}
+
+/// Whether the topic filter `filter` matches `topic`. `+` matches a single level, and `#`
+/// matches the remaining levels (including none, so `home/#` matches `home`). Filters
+/// starting with a wildcard don't match topics starting with `$`, like `$SYS/...`.
+fn filter_matches(filter: &str, topic: &str) -> bool {
+	if topic.starts_with('$') && (filter.starts_with('+') || filter.starts_with('#')) {
+		return false;
+	}
+
+	let mut filter = filter.split('/');
+	let mut topic = topic.split('/');
+	loop {
+		match (filter.next(), topic.next()) {
+			(Some("#"), _) => return true,
+			(Some("+"), Some(_)) => (),
+			(Some(filter), Some(topic)) if filter == topic => (),
+			(None, None) => return true,
+			_ => return false,
+		}
+	}
+}
+
 #[derive(Debug)]
 pub struct Router<R, T> {
 	arena: Arena<Node<T>>,
 	routes: BTreeMap<Arc<str>, Nodes<R>>,
+	/// The routes that are matched as topic filters against every topic (see [is_filter]).
+	/// Exact routes are looked up in `routes` directly.
+	filters: BTreeSet<Arc<str>>,
+	/// Whether the nodes of a route keep their insertion order when a node is removed.
+	/// Otherwise the last node takes the place of the removed one.
+	ordered: bool,
 }
 
 impl<R, T> Default for Router<R, T> {
@@ -51,6 +103,8 @@ impl<R, T> Default for Router<R, T> {
 		Self {
 			arena: Arena::new(),
 			routes: BTreeMap::new(),
+			filters: BTreeSet::new(),
+			ordered: false,
 		}
 	}
 }
@@ -60,6 +114,30 @@ impl<R, T> Router<R, T> {
 		Self::default()
 	}
 
+	/// Makes the router match the nodes of a route in the order they were inserted, even
+	/// after some of them are removed (which makes removing slower).
+	pub fn ordered(self) -> Self {
+		Self {
+			ordered: true,
+			..self
+		}
+	}
+
+	/// Creates a router with room for `capacity` nodes before it has to grow. The routes
+	/// are kept in a [BTreeMap], which allocates as routes are added.
+	pub fn with_capacity(capacity: usize) -> Self {
+		Self {
+			arena: Arena::with_capacity(capacity),
+			..Self::default()
+		}
+	}
+
+	/// The number of nodes the router has room for before it has to grow.
+	#[cfg(test)]
+	pub fn capacity(&self) -> usize {
+		self.arena.capacity()
+	}
+
 	pub fn entry(&mut self, route: Arc<str>) -> RouterEntry<'_, R, T> {
 		match self.routes.entry(route) {
 			btree_map::Entry::Occupied(inner) => RouterEntry::Occupied(OccupiedRouterEntry {
@@ -68,6 +146,7 @@ impl<R, T> Router<R, T> {
 			}),
 			btree_map::Entry::Vacant(inner) => RouterEntry::Vacant(VacantRouterEntry {
 				arena: &mut self.arena,
+				filters: &mut self.filters,
 				inner,
 			}),
 		}
@@ -76,10 +155,11 @@ impl<R, T> Router<R, T> {
 	pub fn remove(&mut self, id: Index) -> Option<(T, Option<R>)> {
 		let node = self.arena.remove(id)?;
 		let nodes = self.routes.get_mut(&node.route)?;
-		nodes.remove(id).unwrap();
+		nodes.remove(id, self.ordered).unwrap();
 
 		if nodes.is_empty() {
 			let route = nodes.route.clone();
+			self.filters.remove(&route);
 			let route = self.routes.remove(&route).unwrap();
 			Some((node.value, Some(route.data)))
 		} else {
@@ -94,6 +174,10 @@ pub struct OccupiedRouterEntry<'a, R, T> {
 }
 
 impl<'a, R, T> OccupiedRouterEntry<'a, R, T> {
+	pub fn data_mut(&mut self) -> &mut R {
+		&mut self.inner.get_mut().data
+	}
+
 	pub fn insert(mut self, value: T) -> Index {
 		let key = self.inner.key().clone();
 		let id = self.arena.insert_with(|id| Node::new(key, value, id));
@@ -105,12 +189,17 @@ impl<'a, R, T> OccupiedRouterEntry<'a, R, T> {
 
 pub struct VacantRouterEntry<'a, R, T> {
 	arena: &'a mut Arena<Node<T>>,
+	filters: &'a mut BTreeSet<Arc<str>>,
 	inner: btree_map::VacantEntry<'a, Arc<str>, Nodes<R>>,
 }
 
 impl<'a, R, T> VacantRouterEntry<'a, R, T> {
 	pub fn insert(self, data: R, value: T) -> Index {
 		let key = self.inner.key().clone();
+		if is_filter(&key) {
+			self.filters.insert(key.clone());
+		}
+
 		let nodes = self.inner.insert(Nodes {
 			route: key.clone(),
 			nodes: Vec::new(),
@@ -159,57 +248,219 @@ impl<'a, T> AsRef<T> for Match<'a, T> {
 	}
 }
 
+/// The nodes of the routes matching a topic, in the order of their routes.
+pub struct Matches<'a, T> {
+	arena: &'a Arena<Node<T>>,
+	nodes: slice::Iter<'a, Index>,
+	routes: vec::IntoIter<&'a [Index]>,
+	len: usize,
+}
+
+impl<'a, T> Iterator for Matches<'a, T> {
+	type Item = Match<'a, T>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			if let Some(node) = self.nodes.next() {
+				self.len -= 1;
+				return Some(Match(&self.arena[*node]));
+			}
+
+			self.nodes = self.routes.next()?.iter();
+		}
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		(self.len, Some(self.len))
+	}
+}
+
+impl<'a, T> ExactSizeIterator for Matches<'a, T> {}
+
 impl<R, T> Router<R, T> {
-	pub fn matches<'a>(
-		&'a self,
-		key: &str,
-	) -> impl Iterator<Item = Match<'a, T>> + ExactSizeIterator {
+	pub fn routes(&self) -> impl ExactSizeIterator<Item = (&Arc<str>, &R)> {
+		self
+			.routes
+			.iter()
+			.map(|(route, nodes)| (route, &nodes.data))
+	}
+
+	pub fn routes_mut(&mut self) -> impl ExactSizeIterator<Item = (&Arc<str>, &mut R)> {
+		self
+			.routes
+			.iter_mut()
+			.map(|(route, nodes)| (route, &mut nodes.data))
+	}
+
+	/// The nodes of the route `key`, followed by the nodes of the wildcard routes and shared
+	/// subscriptions whose topic filter matches `key`.
+	pub fn matches<'a>(&'a self, key: &str) -> Matches<'a, T> {
 		let nodes = match self.routes.get(key) {
-			Some(nodes) => nodes.nodes.iter(),
-			None => [].iter(),
+			Some(nodes) => &*nodes.nodes,
+			None => &[],
 		};
 
-		nodes.map(|node| Match(&self.arena[*node]))
-	}
-}
-
-// #[cfg(test)]
-// mod tests {
-// 	use super::*;
-
-// 	#[test]
-// 	fn basic_test() {
-// 		let mut router = Router::new();
-// 		let r1 = router.insert("app/default/light/bedroom/brightness", 1);
-// 		let r2 = router.insert("app/default/light/bedroom/temperature", 2);
-// 		let r3 = router.insert("app/default/light/bedroom/brightness", 3);
-// 		let r4 = router.insert("app/default/light/bedroom/temperature", 4);
-
-// 		// Note: order is not guaranteed after a remove
-// 		assert_eq!(
-// 			router
-// 				.matches("app/default/light/bedroom/brightness")
-// 				.map(|m| *m)
-// 				.collect::<Vec<_>>(),
-// 			vec![1, 3]
-// 		);
-// 		assert_eq!(
-// 			router
-// 				.matches("app/default/light/bedroom/temperature")
-// 				.map(|m| *m)
-// 				.collect::<Vec<_>>(),
-// 			vec![2, 4]
-// 		);
-
-// 		assert_eq!(router.remove(r1), Some((1, None)));
-// 		assert_eq!(router.remove(r2), Some((2, None)));
-// 		assert_eq!(
-// 			router.remove(r3),
-// 			Some((3, Some("app/default/light/bedroom/brightness".into())))
-// 		);
-// 		assert_eq!(
-// 			router.remove(r4),
-// 			Some((4, Some("app/default/light/bedroom/temperature".into())))
-// 		);
-// 	}
-// }
+		// most routers have no filter routes, which don't need to allocate
+		let routes = self
+			.filters
+			.iter()
+			.filter(|route| filter_matches(topic_filter(route), key))
+			.map(|route| &*self.routes[route].nodes)
+			.collect::<Vec<_>>();
+
+		Matches {
+			arena: &self.arena,
+			nodes: nodes.iter(),
+			len: nodes.len() + routes.iter().map(|nodes| nodes.len()).sum::<usize>(),
+			routes: routes.into_iter(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn insert(router: &mut Router<(), u32>, route: &str, value: u32) -> Index {
+		match router.entry(route.into()) {
+			RouterEntry::Occupied(entry) => entry.insert(value),
+			RouterEntry::Vacant(entry) => entry.insert((), value),
+		}
+	}
+
+	fn matches(router: &Router<(), u32>, route: &str) -> Vec<u32> {
+		router.matches(route).map(|m| *m).collect()
+	}
+
+	#[test]
+	fn basic_test() {
+		let mut router = Router::new();
+		let r1 = insert(&mut router, "app/default/light/bedroom/brightness", 1);
+		let r2 = insert(&mut router, "app/default/light/bedroom/temperature", 2);
+		let r3 = insert(&mut router, "app/default/light/bedroom/brightness", 3);
+		let r4 = insert(&mut router, "app/default/light/bedroom/temperature", 4);
+
+		assert_eq!(matches(&router, "app/default/light/bedroom/brightness"), [1, 3]);
+		assert_eq!(matches(&router, "app/default/light/bedroom/temperature"), [2, 4]);
+
+		assert_eq!(router.remove(r1), Some((1, None)));
+		assert_eq!(router.remove(r2), Some((2, None)));
+		assert_eq!(router.remove(r3), Some((3, Some(()))));
+		assert_eq!(router.remove(r4), Some((4, Some(()))));
+		assert!(matches(&router, "app/default/light/bedroom/brightness").is_empty());
+	}
+
+	#[test]
+	fn single_level_wildcard_matches_one_level() {
+		let mut router = Router::new();
+		insert(&mut router, "home/+/state", 1);
+		insert(&mut router, "home/kitchen/state", 2);
+		insert(&mut router, "+/+/state", 3);
+
+		assert_eq!(matches(&router, "home/kitchen/state"), [2, 3, 1]);
+		assert_eq!(matches(&router, "home/bedroom/state"), [3, 1]);
+		assert!(matches(&router, "home/kitchen/light/state").is_empty());
+		assert!(matches(&router, "home/state").is_empty());
+		assert_eq!(router.matches("home/bedroom/state").len(), 2);
+	}
+
+	#[test]
+	fn multi_level_wildcard_matches_remaining_levels() {
+		let mut router = Router::new();
+		insert(&mut router, "home/#", 1);
+		insert(&mut router, "home/kitchen/#", 2);
+		insert(&mut router, "#", 3);
+
+		assert_eq!(matches(&router, "home"), [3, 1]);
+		assert_eq!(matches(&router, "home/kitchen"), [3, 1, 2]);
+		assert_eq!(matches(&router, "home/kitchen/light/state"), [3, 1, 2]);
+		assert_eq!(matches(&router, "garden/light"), [3]);
+	}
+
+	#[test]
+	fn wildcards_do_not_match_system_topics() {
+		let mut router = Router::new();
+		insert(&mut router, "#", 1);
+		insert(&mut router, "+/broker/uptime", 2);
+		insert(&mut router, "$SYS/#", 3);
+
+		assert_eq!(matches(&router, "$SYS/broker/uptime"), [3]);
+	}
+
+	#[test]
+	fn removed_wildcard_routes_stop_matching() {
+		let mut router = Router::new();
+		let r1 = insert(&mut router, "home/+/state", 1);
+		let r2 = insert(&mut router, "home/+/state", 2);
+
+		assert_eq!(router.remove(r1), Some((1, None)));
+		assert_eq!(matches(&router, "home/kitchen/state"), [2]);
+		assert_eq!(router.remove(r2), Some((2, Some(()))));
+		assert!(matches(&router, "home/kitchen/state").is_empty());
+		assert!(router.filters.is_empty());
+	}
+
+	#[test]
+	fn share_prefix_is_stripped_from_topic_filter() {
+		assert_eq!(topic_filter("$share/workers/home/+/state"), "home/+/state");
+		assert_eq!(topic_filter("$share/workers/#"), "#");
+		assert_eq!(topic_filter("home/+/state"), "home/+/state");
+		// not a valid shared subscription, so kept as it is
+		assert_eq!(topic_filter("$share/workers"), "$share/workers");
+		assert_eq!(topic_filter("$SYS/broker/uptime"), "$SYS/broker/uptime");
+
+		assert!(is_filter("$share/workers/home/kitchen/state"));
+		assert!(is_filter("home/+/state"));
+		assert!(!is_filter("home/kitchen/state"));
+	}
+
+	#[test]
+	fn shared_subscriptions_match_their_filter() {
+		let mut router = Router::new();
+		let r1 = insert(&mut router, "$share/workers/home/+/state", 1);
+		insert(&mut router, "$share/workers/home/kitchen/state", 2);
+		insert(&mut router, "home/kitchen/state", 3);
+
+		assert_eq!(matches(&router, "home/kitchen/state"), [3, 1, 2]);
+		assert_eq!(matches(&router, "home/bedroom/state"), [1]);
+		assert!(matches(&router, "workers/home/kitchen/state").is_empty());
+
+		assert_eq!(router.remove(r1), Some((1, Some(()))));
+		assert!(matches(&router, "home/bedroom/state").is_empty());
+		assert_eq!(
+			router
+				.routes()
+				.map(|(route, _)| &**route)
+				.collect::<Vec<_>>(),
+			["$share/workers/home/kitchen/state", "home/kitchen/state"]
+		);
+	}
+
+	#[test]
+	fn ordered_router_keeps_insertion_order_after_remove() {
+		let mut router = Router::new().ordered();
+		let first = insert(&mut router, "app/light/set", 1);
+		insert(&mut router, "app/light/set", 2);
+		insert(&mut router, "app/light/set", 3);
+
+		router.remove(first);
+		insert(&mut router, "app/light/set", 4);
+
+		assert_eq!(matches(&router, "app/light/set"), [2, 3, 4]);
+	}
+
+	#[test]
+	fn with_capacity_preallocates_nodes() {
+		let mut router = Router::with_capacity(100);
+		assert_eq!(router.capacity(), 100);
+
+		for value in 0..100 {
+			insert(&mut router, &format!("app/light/{value}/set"), value);
+		}
+		assert_eq!(router.capacity(), 100);
+
+		let router = Router::<(), u32>::with_capacity(100).ordered();
+		assert!(router.ordered);
+		assert_eq!(router.capacity(), 100);
+	}
+}
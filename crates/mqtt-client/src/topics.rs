@@ -1,6 +1,8 @@
+use hass_mqtt_proto::Availability;
 use hass_mqtt_provider::{MqttBuildableMessage, MqttMessageBuilder, QosLevel};
 use slug::slugify;
-use std::{fmt, sync::Arc};
+use std::{fmt, str::FromStr, sync::Arc};
+use thiserror::Error;
 
 #[derive(Clone)]
 pub struct NodeId(Arc<str>);
@@ -47,47 +49,253 @@ impl fmt::Display for ApplicationName {
 	}
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum DiscoveryTopicSegment {
+	Literal(Arc<str>),
+	Domain,
+	NodeId,
+	EntityId,
+}
+
+/// Template for the discovery topic of entities, relative to the discovery prefix.
+///
+/// The template may contain the placeholders `{domain}`, `{node_id}` and `{entity_id}`,
+/// of which `{domain}` and `{entity_id}` are required. The default template is
+/// `{domain}/{node_id}/{entity_id}/config`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiscoveryTopicTemplate {
+	segments: Arc<[DiscoveryTopicSegment]>,
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum DiscoveryTopicTemplateError {
+	#[error("unknown placeholder '{{{name}}}' in discovery topic template")]
+	UnknownPlaceholder { name: String },
+
+	#[error("unclosed placeholder in discovery topic template")]
+	UnclosedPlaceholder,
+
+	#[error("unexpected '}}' in discovery topic template")]
+	UnexpectedClosingBrace,
+
+	#[error("discovery topic template is missing the required placeholder '{{{name}}}'")]
+	MissingPlaceholder { name: &'static str },
+}
+
+impl DiscoveryTopicTemplate {
+	pub fn new(template: &str) -> Result<Self, DiscoveryTopicTemplateError> {
+		let mut segments = Vec::new();
+		let mut rest = template;
+
+		while !rest.is_empty() {
+			match rest.find(['{', '}']) {
+				None => {
+					segments.push(DiscoveryTopicSegment::Literal(rest.into()));
+					rest = "";
+				}
+				Some(idx) if rest[idx..].starts_with('}') => {
+					return Err(DiscoveryTopicTemplateError::UnexpectedClosingBrace);
+				}
+				Some(idx) => {
+					if idx > 0 {
+						segments.push(DiscoveryTopicSegment::Literal(rest[..idx].into()));
+					}
+
+					let placeholder = &rest[idx + 1..];
+					let end = placeholder
+						.find('}')
+						.ok_or(DiscoveryTopicTemplateError::UnclosedPlaceholder)?;
+					let segment = match &placeholder[..end] {
+						"domain" => DiscoveryTopicSegment::Domain,
+						"node_id" => DiscoveryTopicSegment::NodeId,
+						"entity_id" => DiscoveryTopicSegment::EntityId,
+						name => {
+							return Err(DiscoveryTopicTemplateError::UnknownPlaceholder { name: name.into() })
+						}
+					};
+
+					segments.push(segment);
+					rest = &placeholder[end + 1..];
+				}
+			}
+		}
+
+		for (segment, name) in [
+			(DiscoveryTopicSegment::Domain, "domain"),
+			(DiscoveryTopicSegment::EntityId, "entity_id"),
+		] {
+			if !segments.contains(&segment) {
+				return Err(DiscoveryTopicTemplateError::MissingPlaceholder { name });
+			}
+		}
+
+		Ok(DiscoveryTopicTemplate {
+			segments: segments.into(),
+		})
+	}
+
+	fn render(&self, domain: &str, node_id: &NodeId, entity_id: &str) -> String {
+		use fmt::Write;
+
+		let mut topic = String::new();
+		for segment in &*self.segments {
+			match segment {
+				DiscoveryTopicSegment::Literal(value) => topic.push_str(value),
+				DiscoveryTopicSegment::Domain => topic.push_str(domain),
+				DiscoveryTopicSegment::NodeId => write!(topic, "{node_id}").unwrap(),
+				DiscoveryTopicSegment::EntityId => topic.push_str(entity_id),
+			}
+		}
+
+		topic
+	}
+}
+
+impl Default for DiscoveryTopicTemplate {
+	fn default() -> Self {
+		DiscoveryTopicTemplate {
+			segments: Arc::new([
+				DiscoveryTopicSegment::Domain,
+				DiscoveryTopicSegment::Literal("/".into()),
+				DiscoveryTopicSegment::NodeId,
+				DiscoveryTopicSegment::Literal("/".into()),
+				DiscoveryTopicSegment::EntityId,
+				DiscoveryTopicSegment::Literal("/config".into()),
+			]),
+		}
+	}
+}
+
+impl FromStr for DiscoveryTopicTemplate {
+	type Err = DiscoveryTopicTemplateError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		DiscoveryTopicTemplate::new(s)
+	}
+}
+
+/// The topics used by a node, as configured by [HassMqttOptions](crate::HassMqttOptions).
+///
+/// Created with [HassMqttOptions::topics](crate::HassMqttOptions::topics), which doesn't
+/// need a connection to the broker, so the topics of entities can be computed offline.
 #[derive(Clone)]
 pub struct TopicsConfig {
 	private_prefix: Arc<str>,
 	discovery_prefix: Arc<str>,
+	discovery_topic_template: DiscoveryTopicTemplate,
 	node_id: NodeId,
+	availability_qos: QosLevel,
+	availability_topic: Arc<str>,
+	online_payload: Arc<str>,
+	offline_payload: Arc<str>,
 }
 
 impl TopicsConfig {
-	pub(crate) const ONLINE_PLAYLOAD: &'static str = "online";
-	pub(crate) const OFFLINE_PLAYLOAD: &'static str = "offline";
+	pub(crate) const DEFAULT_ONLINE_PAYLOAD: &'static str = "online";
+	pub(crate) const DEFAULT_OFFLINE_PAYLOAD: &'static str = "offline";
 
 	pub(crate) fn new(
 		private_prefix: impl Into<Arc<str>>,
 		discovery_prefix: impl Into<Arc<str>>,
+		discovery_topic_template: DiscoveryTopicTemplate,
 		node_id: NodeId,
+		availability_qos: QosLevel,
+		availability_topic: impl Into<Arc<str>>,
 	) -> Self {
 		TopicsConfig {
 			private_prefix: private_prefix.into(),
 			discovery_prefix: discovery_prefix.into(),
+			discovery_topic_template,
 			node_id,
+			availability_qos,
+			availability_topic: availability_topic.into(),
+			online_payload: Self::DEFAULT_ONLINE_PAYLOAD.into(),
+			offline_payload: Self::DEFAULT_OFFLINE_PAYLOAD.into(),
+		}
+	}
+
+	/// Sets the payloads published to the availability topic when the node goes online
+	/// and offline.
+	pub(crate) fn availability_payloads(
+		self,
+		online_payload: impl Into<Arc<str>>,
+		offline_payload: impl Into<Arc<str>>,
+	) -> Self {
+		TopicsConfig {
+			online_payload: online_payload.into(),
+			offline_payload: offline_payload.into(),
+			..self
 		}
 	}
 
 	fn discovery_topic(&self, domain: &str, entity_id: &str) -> String {
-		format!(
-			"{}/{}/{}/{}/config",
-			self.discovery_prefix, domain, self.node_id, entity_id
-		)
+		let topic = self
+			.discovery_topic_template
+			.render(domain, &self.node_id, entity_id);
+
+		format!("{}/{}", self.discovery_prefix, topic)
+	}
+
+	/// The topics of the entity `entity_id` in `domain`.
+	pub fn entity(&self, domain: &str, entity_id: &str) -> EntityTopicsConfig {
+		self.entity_with_discovery_topic(domain, entity_id, None, None)
+	}
+
+	/// The topics of the entity `entity_id` in `domain`, using the slug of `object_id`
+	/// in the topics instead of the `entity_id`. See [EntityTopicBuilder::object_id].
+	///
+	/// [EntityTopicBuilder::object_id]: crate::EntityTopicBuilder::object_id
+	pub fn entity_with_object_id(
+		&self,
+		domain: &str,
+		entity_id: &str,
+		object_id: &str,
+	) -> EntityTopicsConfig {
+		self.entity_with_discovery_topic(domain, entity_id, Some(object_id), None)
 	}
 
-	pub(crate) fn entity(
+	/// The topics of an entity, with an explicit discovery topic (instead of the one from
+	/// the discovery topic template).
+	pub(crate) fn entity_with_discovery_topic(
 		&self,
 		domain: &str,
 		entity_id: &str,
+		object_id: Option<&str>,
 		topic: Option<Arc<str>>,
 	) -> EntityTopicsConfig {
-		EntityTopicsConfig::new(self, domain, entity_id, topic)
+		EntityTopicsConfig::new(self, domain, entity_id, object_id, topic)
+	}
+
+	/// The availability topic of the node.
+	pub fn available(&self) -> String {
+		self.node_topic(&*self.availability_topic)
+	}
+
+	/// The availability of the node, for the `availability` list of entity documents. The
+	/// payloads are only set when they differ from the Home Assistant defaults.
+	pub fn availability(&self) -> Availability<'static> {
+		if &*self.online_payload == Self::DEFAULT_ONLINE_PAYLOAD
+			&& &*self.offline_payload == Self::DEFAULT_OFFLINE_PAYLOAD
+		{
+			Availability::new(self.available())
+		} else {
+			Availability::new_with_payloads(
+				self.available(),
+				self.online_payload.clone(),
+				self.offline_payload.clone(),
+			)
+		}
 	}
 
-	pub(crate) fn available(&self) -> String {
-		self.node_topic("available")
+	pub(crate) fn availability_qos(&self) -> QosLevel {
+		self.availability_qos
+	}
+
+	pub(crate) fn availability_payload(&self, available: bool) -> &str {
+		match available {
+			true => &self.online_payload,
+			false => &self.offline_payload,
+		}
 	}
 
 	pub(crate) fn node_topic(&self, topic: impl AsRef<str>) -> String {
@@ -114,23 +322,40 @@ impl TopicsConfig {
 		self.entity_topic(domain, entity_id, "set", name)
 	}
 
+	/// The `online` availability message. It is only published without `retain` when the
+	/// broker doesn't support retained messages.
 	pub(crate) fn online_message<T: MqttBuildableMessage>(
 		&self,
+		retain: bool,
 	) -> Result<T, <<T as MqttBuildableMessage>::Builder as MqttMessageBuilder>::Error> {
-		availability_message(&self.available(), Self::ONLINE_PLAYLOAD)
+		availability_message(
+			&self.available(),
+			&self.online_payload,
+			self.availability_qos,
+			retain,
+		)
 	}
 
 	pub(crate) fn offline_message<T: MqttBuildableMessage>(
 		&self,
 	) -> Result<T, <<T as MqttBuildableMessage>::Builder as MqttMessageBuilder>::Error> {
-		availability_message(&self.available(), Self::OFFLINE_PLAYLOAD)
+		availability_message(
+			&self.available(),
+			&self.offline_payload,
+			self.availability_qos,
+			true,
+		)
 	}
 }
 
-pub(crate) struct EntityTopicsConfig {
+/// The topics of an entity, created with [TopicsConfig::entity].
+pub struct EntityTopicsConfig {
 	topics: TopicsConfig,
 	pub(crate) domain: Arc<str>,
 	pub(crate) entity_id: Arc<str>,
+	/// The id used in the topics of the entity: the slug of the `object_id`, if the
+	/// entity has one, or else the `entity_id`.
+	object_id: Arc<str>,
 	pub(crate) discovery_topic: Arc<str>,
 }
 
@@ -139,44 +364,225 @@ impl EntityTopicsConfig {
 		topics: &TopicsConfig,
 		domain: impl Into<Arc<str>>,
 		entity_id: impl Into<Arc<str>>,
+		object_id: Option<&str>,
 		topic: Option<Arc<str>>,
 	) -> Self {
 		let domain = domain.into();
 		let entity_id = entity_id.into();
+		let object_id = match object_id {
+			Some(object_id) => Arc::from(slugify(object_id)),
+			None => entity_id.clone(),
+		};
 		let discovery_topic =
-			topic.unwrap_or_else(|| Arc::from(topics.discovery_topic(&domain, &entity_id)));
+			topic.unwrap_or_else(|| Arc::from(topics.discovery_topic(&domain, &object_id)));
 
 		EntityTopicsConfig {
 			topics: topics.clone(),
 			domain,
 			entity_id,
+			object_id,
 			discovery_topic,
 		}
 	}
 
-	pub(crate) fn discovery_topic(&self) -> Arc<str> {
+	/// The domain of the entity.
+	pub fn domain(&self) -> &str {
+		&self.domain
+	}
+
+	/// The id of the entity.
+	pub fn entity_id(&self) -> &str {
+		&self.entity_id
+	}
+
+	/// The id used in the topics of the entity: the slug of its `object_id`, or its
+	/// [entity_id](Self::entity_id) if it has none.
+	pub fn object_id(&self) -> &str {
+		&self.object_id
+	}
+
+	/// The topic the discovery document of the entity is published on.
+	pub fn discovery_topic(&self) -> Arc<str> {
 		self.discovery_topic.clone()
 	}
 
-	pub(crate) fn state_topic(&self, name: Option<&str>) -> String {
-		self.topics.state_topic(&self.domain, &self.entity_id, name)
+	/// The availability topic of the node of the entity.
+	pub fn available(&self) -> String {
+		self.topics.available()
+	}
+
+	/// The availability of the node of the entity. See [TopicsConfig::availability].
+	pub fn availability(&self) -> Availability<'static> {
+		self.topics.availability()
+	}
+
+	/// The state topic of the entity, or the state topic called `name` when the entity
+	/// has several.
+	pub fn state_topic(&self, name: Option<&str>) -> String {
+		self.topics.state_topic(&self.domain, &self.object_id, name)
 	}
 
-	pub(crate) fn command_topic(&self, name: Option<&str>) -> String {
+	/// The command topic of the entity, or the command topic called `name` when the
+	/// entity has several.
+	pub fn command_topic(&self, name: Option<&str>) -> String {
 		self
 			.topics
-			.command_topic(&self.domain, &self.entity_id, name)
+			.command_topic(&self.domain, &self.object_id, name)
 	}
 }
 
 fn availability_message<T: MqttBuildableMessage>(
 	topic: &str,
 	content: &str,
+	qos: QosLevel,
+	retain: bool,
 ) -> Result<T, <<T as MqttBuildableMessage>::Builder as MqttMessageBuilder>::Error> {
 	T::builder()
 		.topic(topic)
 		.payload(content)
-		.qos(QosLevel::ExactlyOnce)
-		.retain(true)
+		.qos(qos)
+		.retain(retain)
 		.build()
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn topics(template: DiscoveryTopicTemplate) -> TopicsConfig {
+		TopicsConfig::new(
+			"app",
+			"homeassistant",
+			template,
+			NodeId::new("node"),
+			QosLevel::ExactlyOnce,
+			"available",
+		)
+	}
+
+	#[test]
+	fn default_availability_topic() {
+		let topics = topics(DiscoveryTopicTemplate::default());
+
+		assert_eq!(topics.available(), "app/node/available");
+	}
+
+	#[test]
+	fn custom_availability_topic() {
+		let topics = TopicsConfig::new(
+			"app",
+			"homeassistant",
+			DiscoveryTopicTemplate::default(),
+			NodeId::new("node"),
+			QosLevel::ExactlyOnce,
+			"status",
+		);
+
+		assert_eq!(topics.available(), "app/node/status");
+		assert_eq!(
+			topics.entity("light", "kitchen").available(),
+			"app/node/status"
+		);
+	}
+
+	#[test]
+	fn availability_has_custom_payloads() {
+		let topics = topics(DiscoveryTopicTemplate::default());
+		assert_eq!(
+			topics.availability(),
+			Availability::new("app/node/available")
+		);
+
+		let topics = topics.availability_payloads("up", "down");
+		assert_eq!(topics.availability_payload(true), "up");
+		assert_eq!(topics.availability_payload(false), "down");
+		assert_eq!(
+			topics.entity("light", "kitchen").availability(),
+			Availability::new_with_payloads("app/node/available", "up", "down")
+		);
+	}
+
+	#[test]
+	fn default_discovery_topic() {
+		let topics = topics(DiscoveryTopicTemplate::default());
+
+		assert_eq!(
+			topics.discovery_topic("light", "kitchen"),
+			"homeassistant/light/node/kitchen/config"
+		);
+	}
+
+	#[test]
+	fn default_template_matches_parsed() {
+		assert_eq!(
+			DiscoveryTopicTemplate::default(),
+			"{domain}/{node_id}/{entity_id}/config".parse().unwrap()
+		);
+	}
+
+	#[test]
+	fn discovery_topic_without_node_id() {
+		let template = DiscoveryTopicTemplate::new("{domain}/{entity_id}/config").unwrap();
+		let topics = topics(template);
+
+		assert_eq!(
+			topics.discovery_topic("light", "kitchen"),
+			"homeassistant/light/kitchen/config"
+		);
+	}
+
+	#[test]
+	fn discovery_topic_with_custom_order() {
+		let template = DiscoveryTopicTemplate::new("{domain}/{entity_id}_{node_id}/config").unwrap();
+		let topics = topics(template);
+
+		assert_eq!(
+			topics.discovery_topic("light", "kitchen"),
+			"homeassistant/light/kitchen_node/config"
+		);
+	}
+
+	#[test]
+	fn object_id_is_used_in_topics() {
+		let topics = topics(DiscoveryTopicTemplate::default());
+		let entity = topics.entity_with_object_id("light", "kitchen", "Ceiling Light");
+
+		assert_eq!(entity.entity_id(), "kitchen");
+		assert_eq!(entity.object_id(), "ceiling-light");
+		assert_eq!(
+			&*entity.discovery_topic(),
+			"homeassistant/light/node/ceiling-light/config"
+		);
+		assert_eq!(entity.state_topic(None), "app/node/light/ceiling-light/state");
+		assert_eq!(
+			entity.command_topic(Some("brightness")),
+			"app/node/light/ceiling-light/set/brightness"
+		);
+	}
+
+	#[test]
+	fn invalid_templates() {
+		assert_eq!(
+			DiscoveryTopicTemplate::new("{domain}/{object_id}/config"),
+			Err(DiscoveryTopicTemplateError::UnknownPlaceholder {
+				name: "object_id".into()
+			})
+		);
+		assert_eq!(
+			DiscoveryTopicTemplate::new("{domain}/{entity_id/config"),
+			Err(DiscoveryTopicTemplateError::UnclosedPlaceholder)
+		);
+		assert_eq!(
+			DiscoveryTopicTemplate::new("{domain}/entity_id}/config"),
+			Err(DiscoveryTopicTemplateError::UnexpectedClosingBrace)
+		);
+		assert_eq!(
+			DiscoveryTopicTemplate::new("{domain}/{node_id}/config"),
+			Err(DiscoveryTopicTemplateError::MissingPlaceholder { name: "entity_id" })
+		);
+		assert_eq!(
+			DiscoveryTopicTemplate::new("{node_id}/{entity_id}/config"),
+			Err(DiscoveryTopicTemplateError::MissingPlaceholder { name: "domain" })
+		);
+	}
+}
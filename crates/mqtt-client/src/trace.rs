@@ -0,0 +1,40 @@
+//! Shims for the `tracing` macros used by this crate. With the `tracing` feature
+//! off, spans are created disabled and events are compiled out (their arguments
+//! are still type checked), so operations don't pay for instrumentation nobody
+//! collects. `#[instrument]` is applied with `cfg_attr(feature = "tracing", ...)`.
+
+#[cfg(feature = "tracing")]
+macro_rules! span {
+	($($arg:tt)*) => {
+		::tracing::span!($($arg)*)
+	};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+	($($arg:tt)*) => {{
+		if false {
+			let _ = ::tracing::span!($($arg)*);
+		}
+		::tracing::Span::none()
+	}};
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! event {
+	($($arg:tt)*) => {
+		::tracing::event!($($arg)*)
+	};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! event {
+	($($arg:tt)*) => {
+		if false {
+			::tracing::event!($($arg)*)
+		}
+	};
+}
+
+pub(crate) use event;
+pub(crate) use span;
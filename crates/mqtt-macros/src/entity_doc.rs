@@ -7,42 +7,43 @@ use syn::{parse2, FieldsNamed};
 fn common_fields() -> FieldsNamed {
 	let tokens = quote! {{
 		/// A list of MQTT topics subscribed to receive availability (online/offline) updates.
-		#[serde(borrow, default, skip_serializing_if = "<[crate::availability::Availability]>::is_empty")]
+		#[serde(borrow, default, skip_serializing_if = "<[crate::availability::Availability]>::is_empty", alias = "avty")]
 		#[entity(validate)]
 		pub availability: crate::HassItems<'a, crate::availability::Availability<'a>>,
 
 		/// When `availability` is configured, this controls the conditions needed
 		/// to set the entity to `available`.
-		#[serde(default, skip_serializing_if = "crate::availability::AvailabilityMode::is_default")]
+		#[serde(default, skip_serializing_if = "crate::availability::AvailabilityMode::is_default", alias = "avty_mode")]
 		pub availability_mode: crate::availability::AvailabilityMode,
 
 		/// Information about the device this entity is a part of to tie it into the device registry.
 		/// Only works through MQTT discovery and when `unique_id` is set.
 		/// At least one of identifiers or connections must be present to identify the device.
-		#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+		#[serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "dev")]
 		#[entity(validate)]
 		pub device: Option<crate::device::Device<'a>>,
 
 		/// Flag which defines if the entity should be enabled when first added.
 		/// Defaults to `true`.
-		#[serde(default, skip_serializing_if = "Option::is_none")]
+		#[serde(default, skip_serializing_if = "Option::is_none", alias = "en")]
+		#[entity(ha_default = "true")]
 		pub enabled_by_default: Option<bool>,
 
 		/// The encoding of the payloads received and published messages. Set to "" to disable decoding of incoming payload.
 		/// Defaults to `"utf-8"`.
-		#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+		#[serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "e")]
 		pub encoding: Option<crate::HassStr<'a>>,
 
 		/// The [category] of the entity.
 		///
 		/// [category]: https://developers.home-assistant.io/docs/core/entity#generic-properties
-		#[serde(default, skip_serializing_if = "crate::entity_category::EntityCategory::is_none")]
+		#[serde(default, skip_serializing_if = "crate::entity_category::EntityCategory::is_none", alias = "ent_cat")]
 		pub entity_category: crate::entity_category::EntityCategory,
 
 		/// [Icon][icon] for the entity.
 		///
 		/// [icon]: https://www.home-assistant.io/docs/configuration/customizing-devices/#icon
-		#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+		#[serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "ic")]
 		#[entity(validate)]
 		pub icon: Option<crate::icon::Icon<'a>>,
 
@@ -50,7 +51,7 @@ fn common_fields() -> FieldsNamed {
 		/// on the `json_attributes_topic`.
 		///
 		/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
-		#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+		#[serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "json_attr_tpl")]
 		#[entity(validate)]
 		pub json_attributes_template: Option<crate::template::Template<'a>>,
 
@@ -58,7 +59,7 @@ fn common_fields() -> FieldsNamed {
 		/// attributes.
 		///
 		/// Implies `force_update` of the current state when a message is received on this topic.
-		#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+		#[serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "json_attr_t")]
 		#[entity(validate)]
 		pub json_attributes_topic: Option<crate::topic::Topic<'a>>,
 
@@ -68,7 +69,7 @@ fn common_fields() -> FieldsNamed {
 		pub name: Option<crate::name::Name<'a>>,
 
 		/// Used instead of `name` for automatic generation of `entity_id`.
-		#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+		#[serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "obj_id")]
 		pub object_id: Option<crate::HassStr<'a>>,
 
 		/// The maximum QoS level of the state topic.
@@ -77,9 +78,16 @@ fn common_fields() -> FieldsNamed {
 
 		/// An ID that uniquely identifies this entity. If two entities have the same unique ID,
 		/// Home Assistant will raise an exception.
-		#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+		#[serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "uniq_id")]
 		#[entity(validate)]
 		pub unique_id: Option<crate::unique_id::UniqueId<'a>>,
+
+		/// Fields not known by this document, which are kept so they survive a
+		/// deserialize/serialize round trip.
+		#[cfg(feature = "extra-fields")]
+		#[serde(flatten, default)]
+		#[entity(builder = false)]
+		pub extra: crate::json::ExtraFields,
 	}};
 
 	let fields: FieldsNamed = syn::parse2(tokens).unwrap();
@@ -100,19 +108,51 @@ impl FromDeriveInput for EntityStruct {
 			f.prepend(common_fields());
 		}
 
-		DocumentStruct::from_derive_input(&input).map(Self)
+		let doc = DocumentStruct::from_derive_input(&input)?;
+		// the common fields borrow from 'a
+		if !doc.has_lifetime() {
+			return Err(
+				darling::Error::custom("Entity documents must have a lifetime 'a").with_span(&input.ident),
+			);
+		}
+
+		Ok(Self(doc))
 	}
 }
 
 impl EntityStruct {
+	/// Builders for the common fields that go beyond setting the field.
+	fn common_builders(&self) -> TokenStream {
+		let generics = self.0.generics();
+		let ident = self.0.ident();
+
+		quote! {
+			#[cfg(feature = "alloc")]
+			impl #generics #ident #generics {
+				/// Adds an identifier to the [device](crate::device::Device) this entity is a part of,
+				/// creating the device if it's not set yet.
+				pub fn device_identifier(mut self, identifier: impl ::core::convert::Into<crate::HassStr<'a>>) -> Self {
+					self
+						.device
+						.get_or_insert_with(::core::default::Default::default)
+						.identifiers
+						.push(identifier.into());
+					self
+				}
+			}
+		}
+	}
+
 	fn into_token_stream(self, args: Args) -> TokenStream {
 		let mut tokens = TokenStream::new();
 		self.0.document_struct(&args).to_tokens(&mut tokens);
 		self.0.ctor().to_tokens(&mut tokens);
 		self.0.builders().to_tokens(&mut tokens);
+		self.common_builders().to_tokens(&mut tokens);
 		self.0.invalidity_enum().to_tokens(&mut tokens);
 		self.0.validate().to_tokens(&mut tokens);
 		self.0.serde().to_tokens(&mut tokens);
+		self.0.json_parser().to_tokens(&mut tokens);
 		tokens
 	}
 }
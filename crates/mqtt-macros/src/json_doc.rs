@@ -3,6 +3,7 @@ pub(crate) mod input;
 mod builders;
 mod ctor;
 mod document;
+mod from_json;
 mod invalidity;
 mod serde;
 mod validate;
@@ -27,6 +28,18 @@ pub(crate) struct DocumentStruct {
 }
 
 impl DocumentStruct {
+	pub(crate) fn ident(&self) -> &syn::Ident {
+		&self.ident
+	}
+
+	pub(crate) fn generics(&self) -> &syn::Generics {
+		&self.generics
+	}
+
+	pub(crate) fn has_lifetime(&self) -> bool {
+		self.generics.lifetimes().next().is_some()
+	}
+
 	pub(crate) fn document_struct<'a>(&'a self, args: &'a Args) -> impl ToTokens + 'a {
 		document::document_struct(self, args)
 	}
@@ -43,6 +56,10 @@ impl DocumentStruct {
 		serde::serde_impl(self)
 	}
 
+	pub(crate) fn json_parser(&self) -> impl ToTokens + '_ {
+		from_json::from_json(self)
+	}
+
 	pub(crate) fn invalidity_enum(&self) -> impl ToTokens + '_ {
 		invalidity::invalidity_enum(self)
 	}
@@ -57,33 +74,13 @@ impl TryFrom<input::DocumentStructInput> for DocumentStruct {
 
 	fn try_from(value: input::DocumentStructInput) -> Result<Self> {
 		let mut accumulator = Accumulator::default();
-		{
-			let lifetimes = value.generics.declared_lifetimes();
-			let mut has_a = false;
-			let mut first = true;
-			for lifetime in lifetimes {
-				if first {
-					first = false;
-				}
-
-				if lifetime.ident == "a" {
-					has_a = true;
-				} else {
-					accumulator.push(
-						Error::custom("Documents must only have a single lifetime named 'a")
-							.with_span(&lifetime.ident),
-					);
-				}
-			}
-
-			if !has_a {
-				let error = Error::custom("Documents must have a lifetime 'a");
-				let error = if first {
-					error.with_span(&value.ident)
-				} else {
-					error.with_span(&value.generics)
-				};
-				accumulator.push(error);
+		// the lifetime is optional, so state documents without borrowed fields don't need one
+		for lifetime in value.generics.declared_lifetimes() {
+			if lifetime.ident != "a" {
+				accumulator.push(
+					Error::custom("Documents must only have a single lifetime named 'a")
+						.with_span(&lifetime.ident),
+				);
 			}
 		}
 
@@ -138,7 +135,16 @@ pub(crate) struct DocumentField {
 	attrs: Vec<syn::Attribute>,
 	validate: FieldValidation,
 	builder: Builder,
+	ha_default: Option<syn::Expr>,
 	required: bool,
+	flatten: bool,
+}
+
+impl DocumentField {
+	/// The `#[cfg]` attributes of the field, which must be repeated wherever the field is used.
+	fn cfgs(&self) -> impl Iterator<Item = &syn::Attribute> {
+		self.attrs.iter().filter(|attr| attr.path.is_ident("cfg"))
+	}
 }
 
 enum FieldValidation {
@@ -181,6 +187,22 @@ impl From<input::Builder> for Builder {
 	}
 }
 
+/// Checks whether any of the `#[serde(...)]` attributes contains the argument `name`,
+/// either as a flag (`#[serde(flatten)]`) or with a value (`#[serde(default = "..")]`).
+fn has_serde_arg(serde: &[syn::Attribute], name: &str, accumulator: &mut Accumulator) -> bool {
+	serde
+		.iter()
+		.filter_map(|attr| accumulator.handle(attr.parse_meta().map_err(Error::from)))
+		.any(|meta| match meta {
+			syn::Meta::List(list) => list.nested.iter().any(|nested| match nested {
+				syn::NestedMeta::Meta(syn::Meta::Path(path)) => path.is_ident(name),
+				syn::NestedMeta::Meta(syn::Meta::NameValue(value)) => value.path.is_ident(name),
+				_ => false,
+			}),
+			_ => false,
+		})
+}
+
 impl TryFrom<input::DocumentFieldInput> for DocumentField {
 	type Error = darling::Error;
 
@@ -212,11 +234,20 @@ impl TryFrom<input::DocumentFieldInput> for DocumentField {
 			}
 		}
 		let validate = value.validate.into();
-		let builder = value.builder.into();
-		let has_default = serde
-			.iter()
-			.any(|attr| attr.path.is_ident("serde") && attr.tokens.to_string().contains("default"));
+		let builder: Builder = value.builder.into();
+		if builder.enabled && !matches!(ty, syn::Type::Path(_)) {
+			accumulator.push(
+				Error::custom(
+					"Document fields with a builder must have a path type (like `Option<T>`), \
+					 or set `builder = false` on the field",
+				)
+				.with_span(&ty),
+			);
+		}
+
+		let has_default = has_serde_arg(&serde, "default", &mut accumulator);
 		let required = !has_default;
+		let flatten = has_serde_arg(&serde, "flatten", &mut accumulator);
 
 		let variant_ident = format_ident!(
 			"{}",
@@ -236,7 +267,9 @@ impl TryFrom<input::DocumentFieldInput> for DocumentField {
 			serde,
 			validate,
 			builder,
+			ha_default: value.ha_default,
 			required,
+			flatten,
 		})
 	}
 }
@@ -248,3 +281,56 @@ impl FromDeriveInput for DocumentStruct {
 		result.try_into()
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use quote::quote;
+
+	#[test]
+	fn non_path_field_type_is_a_compile_error() {
+		let input: syn::DeriveInput = syn::parse2(quote! {
+			pub struct Doc<'a> {
+				pub name: Option<&'a str>,
+				pub values: [u8; 4],
+			}
+		})
+		.unwrap();
+
+		let error = match DocumentStruct::from_derive_input(&input) {
+			Ok(_) => panic!("document with a non-path field type should be rejected"),
+			Err(error) => error,
+		};
+		assert_eq!(error.len(), 1);
+		assert!(error
+			.to_string()
+			.starts_with("Document fields with a builder must have a path type"));
+	}
+
+	#[test]
+	fn non_path_field_type_without_builder_is_allowed() {
+		let input: syn::DeriveInput = syn::parse2(quote! {
+			pub struct Doc<'a> {
+				pub name: Option<&'a str>,
+				#[entity(builder = false)]
+				pub values: [u8; 4],
+			}
+		})
+		.unwrap();
+
+		assert!(DocumentStruct::from_derive_input(&input).is_ok());
+	}
+
+	#[test]
+	fn document_without_lifetime_is_allowed() {
+		let input: syn::DeriveInput = syn::parse2(quote! {
+			pub struct Doc {
+				pub on: bool,
+			}
+		})
+		.unwrap();
+
+		let doc = DocumentStruct::from_derive_input(&input).expect("should be accepted");
+		assert!(!doc.has_lifetime());
+	}
+}
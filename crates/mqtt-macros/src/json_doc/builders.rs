@@ -50,6 +50,7 @@ impl<'a> ToTokens for Builders<'a> {
 				.clone()
 				.unwrap_or_else(|| format_ident!("{}", &f.ident, span = Span::call_site()));
 			let docs = &f.docs;
+			let cfgs = f.cfgs().collect::<Vec<_>>();
 			let ty = &f.ty;
 			match ty {
 				syn::Type::Path(p) => {
@@ -57,12 +58,14 @@ impl<'a> ToTokens for Builders<'a> {
 						let unset_ident = format_ident!("unset_{}", ident, span = Span::call_site());
 						quote! {
 							#(#docs)*
+							#(#cfgs)*
 							pub fn #ident(mut self, #ident: impl ::core::convert::Into< #inner >) -> Self {
 								self.#ident = Some(#ident.into());
 								self
 							}
 
 							#(#docs)*
+							#(#cfgs)*
 							pub fn #unset_ident(&mut self) -> &mut Self {
 								self.#ident = None;
 								self
@@ -71,6 +74,7 @@ impl<'a> ToTokens for Builders<'a> {
 					} else {
 						quote! {
 							#(#docs)*
+							#(#cfgs)*
 							pub fn #ident(mut self, #ident: impl ::core::convert::Into< #ty >) -> Self {
 								self.#ident = #ident.into();
 								self
@@ -78,14 +82,45 @@ impl<'a> ToTokens for Builders<'a> {
 						}
 					}
 				}
-				// TODO: deal with?
-				_ => panic!("type should be a path"),
+				// rejected in `DocumentField::try_from`
+				_ => unreachable!("document field with a builder should have a path type"),
+			}
+		});
+
+		let compact_fields = self
+			.0
+			.fields
+			.iter()
+			.filter_map(|f| {
+				let ha_default = f.ha_default.as_ref()?;
+				let ident = &f.ident;
+				let cfgs = f.cfgs();
+				Some(quote! {
+					#(#cfgs)*
+					if self.#ident == Some(#ha_default) {
+						self.#ident = None;
+					}
+				})
+			})
+			.collect::<Vec<_>>();
+
+		// only documents with fields that have a Home Assistant default can be compacted
+		let compact = (!compact_fields.is_empty()).then(|| {
+			quote! {
+				/// Unsets the fields that are set to the value Home Assistant uses when the
+				/// field is missing, so they are left out of the serialized document.
+				pub fn compact(mut self) -> Self {
+					#(#compact_fields)*
+					self
+				}
 			}
 		});
 
 		tokens.extend(quote! {
 			impl #generics #ident #generics {
 				#(#builders)*
+
+				#compact
 			}
 		});
 	}
@@ -18,13 +18,17 @@ impl<'a> ToTokens for Ctor<'a> {
 				let ident = format_ident!("{}", &f.ident, span = Span::call_site());
 				let ty = &f.ty;
 				let required = &f.required;
+				let cfgs = f.cfgs();
 				let (field, arg) = if *required {
 					(
-						quote! { #ident: ::core::convert::Into::into( #ident ) },
+						quote! { #(#cfgs)* #ident: ::core::convert::Into::into( #ident ) },
 						Some(quote! { #ident: impl ::core::convert::Into::< #ty > }),
 					)
 				} else {
-					(quote! { #ident: ::core::default::Default::default() }, None)
+					(
+						quote! { #(#cfgs)* #ident: ::core::default::Default::default() },
+						None,
+					)
 				};
 				(arg, field)
 			})
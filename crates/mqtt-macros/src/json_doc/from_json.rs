@@ -0,0 +1,68 @@
+use super::{input::AdditionalProps, DocumentStruct};
+use darling::ToTokens;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+struct FromJson<'a>(&'a DocumentStruct);
+
+impl<'a> ToTokens for FromJson<'a> {
+	fn to_tokens(&self, tokens: &mut TokenStream) {
+		let generics = &self.0.generics;
+		let ident = &self.0.ident;
+
+		// the constant json properties identify the kind of document (like the `schema`
+		// of lights), so they must be present with the expected value
+		let check_discriminators = match self.0.additional_props.as_ref() {
+			None => quote! {},
+			Some(v) => {
+				let (fields, checks): (Vec<_>, Vec<_>) = v
+					.props()
+					.enumerate()
+					.map(|(idx, (name, value))| {
+						let ident = format_ident!("__const_field_{}", idx);
+						let alias =
+							AdditionalProps::abbreviation(name).map(|alias| quote! { , alias = #alias });
+						let field = quote! {
+							#[serde(borrow, default, rename = #name #alias)]
+							#ident: ::core::option::Option<crate::HassStr<'d>>
+						};
+						let check = quote! {
+							crate::json::check_discriminator(#name, #value, discriminators.#ident.as_deref())?;
+						};
+
+						(field, check)
+					})
+					.unzip();
+
+				quote! {
+					#[derive(::serde::Deserialize)]
+					struct Discriminators<'d> {
+						#(#fields,)*
+					}
+
+					let discriminators: Discriminators = ::serde_json::from_str(json)
+						.map_err(crate::json::DiscoveryJsonError::Parse)?;
+					#(#checks)*
+				}
+			}
+		};
+
+		tokens.extend(quote! {
+			#[cfg(feature = "json")]
+			impl #generics #ident #generics {
+				/// Parses a discovery document, like the ones Home Assistant reads from the
+				/// discovery topics. Unknown fields are ignored, and abbreviated keys are
+				/// accepted. The `~` base topic is not expanded.
+				pub fn from_discovery_json(json: &'a str) -> ::core::result::Result<Self, crate::json::DiscoveryJsonError> {
+					#check_discriminators
+
+					::serde_json::from_str(json).map_err(crate::json::DiscoveryJsonError::Parse)
+				}
+			}
+		});
+	}
+}
+
+pub(super) fn from_json(doc: &DocumentStruct) -> impl ToTokens + '_ {
+	FromJson(doc)
+}
@@ -5,7 +5,7 @@ use std::collections::BTreeMap;
 use syn::{spanned::Spanned, Meta, MetaList, MetaNameValue, NestedMeta};
 
 #[derive(FromDeriveInput, Debug)]
-#[darling(attributes(entity, state), supports(struct_named), forward_attrs)]
+#[darling(attributes(entity, state, discovery), supports(struct_named), forward_attrs)]
 pub(super) struct DocumentStructInput {
 	pub ident: syn::Ident,
 	pub vis: syn::Visibility,
@@ -19,7 +19,7 @@ pub(super) struct DocumentStructInput {
 }
 
 #[derive(FromField, Debug)]
-#[darling(attributes(entity, state), forward_attrs)]
+#[darling(attributes(entity, state, discovery), forward_attrs)]
 pub(super) struct DocumentFieldInput {
 	// guaranteed to never be `None` by `darling`
 	pub ident: Option<syn::Ident>,
@@ -27,6 +27,10 @@ pub(super) struct DocumentFieldInput {
 	pub attrs: Vec<syn::Attribute>,
 	pub validate: FieldValidation,
 	pub builder: Builder,
+	/// The value Home Assistant uses when the (optional) field is missing, which is
+	/// left out of compacted documents.
+	#[darling(default)]
+	pub ha_default: Option<syn::Expr>,
 	pub vis: syn::Visibility,
 }
 
@@ -139,6 +143,14 @@ impl AdditionalProps {
 	pub(crate) fn props(&self) -> impl Iterator<Item = (&str, &str)> {
 		self.values.iter().map(|(k, v)| (&**k, &**v))
 	}
+
+	/// The abbreviation Home Assistant accepts for the property `name`, if any.
+	pub(crate) fn abbreviation(name: &str) -> Option<&'static str> {
+		match name {
+			"automation_type" => Some("atype"),
+			_ => None,
+		}
+	}
 }
 
 impl FromMeta for AdditionalProps {
@@ -1,9 +1,10 @@
-use super::DocumentStruct;
+use super::{input::AdditionalProps, DocumentStruct};
 use crate::util::ModifyLifetimes;
 use darling::ToTokens;
 use itertools::MultiUnzip;
 use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote};
+use std::iter;
 
 struct SerdeImpl<'a>(&'a DocumentStruct);
 
@@ -15,23 +16,65 @@ impl<'a> ToTokens for SerdeImpl<'a> {
 
 		let mut proxy_generics = generics.clone();
 		proxy_generics.params.clear();
-		proxy_generics.params.push(syn::parse_quote!('b));
+		// documents without a lifetime have nothing for the inner lifetime to stand in for
+		if self.0.has_lifetime() {
+			proxy_generics.params.push(syn::parse_quote!('b));
+		}
 		proxy_generics.params.push(syn::parse_quote!('p));
 
 		let proxy_inner_lifetime = proxy_generics.lifetimes().next().unwrap();
 		let proxy_outer_lifetime = proxy_generics.lifetimes().last().unwrap();
 
-		let proxy_fields = self.0.fields.iter().map(|f| {
-			let ident = format_ident!("{}", &f.ident, span = Span::call_site());
-			let attrs = &f.attrs;
-			let serde = &f.serde;
-			let ty = f.ty.make_lifetimes(&proxy_inner_lifetime.lifetime);
-			quote! {
-				#(#attrs)*
-				#(#serde)*
-				#ident: & #proxy_outer_lifetime #ty
-			}
-		});
+		// flattened fields (like the unknown fields of entities) can contain the constant json
+		// properties (or their abbreviations) when the document was deserialized, which are
+		// already written by the proxy
+		let const_names = self
+			.0
+			.additional_props
+			.as_ref()
+			.map(|v| {
+				v.props()
+					.flat_map(|(name, _)| iter::once(name).chain(AdditionalProps::abbreviation(name)))
+					.collect::<Vec<_>>()
+			})
+			.unwrap_or_default();
+		let (proxy_fields, flatten_ser_fns): (Vec<_>, Vec<_>) = self
+			.0
+			.fields
+			.iter()
+			.map(|f| {
+				let ident = format_ident!("{}", &f.ident, span = Span::call_site());
+				let attrs = &f.attrs;
+				let serde = &f.serde;
+				let ty = f.ty.make_lifetimes(&proxy_inner_lifetime.lifetime);
+				if !f.flatten || const_names.is_empty() {
+					let fld = quote! {
+						#(#attrs)*
+						#(#serde)*
+						#ident: & #proxy_outer_lifetime #ty
+					};
+					return (fld, quote! {});
+				}
+
+				let cfgs = f.cfgs();
+				let field_ty = &f.ty;
+				let ser_ident = format_ident!("__serialize_flattened_{}", &f.ident);
+				let ser_ident_str = ser_ident.to_string();
+				let ser_fn = quote! {
+					#(#cfgs)*
+					fn #ser_ident<S: ::serde::Serializer>(value: &&#field_ty, s: S) -> ::core::result::Result<S::Ok, S::Error> {
+						value.serialize_without(&[#(#const_names,)*], s)
+					}
+				};
+				let fld = quote! {
+					#(#attrs)*
+					#(#serde)*
+					#[serde(serialize_with = #ser_ident_str)]
+					#ident: & #proxy_outer_lifetime #ty
+				};
+				(fld, ser_fn)
+			})
+			.unzip();
 
 		let (ser_fns, additional_proxy_fields, additional_proxy_assigns) =
 			match self.0.additional_props.as_ref() {
@@ -69,7 +112,9 @@ impl<'a> ToTokens for SerdeImpl<'a> {
 
 		let proxy_assign = self.0.fields.iter().map(|f| {
 			let ident = format_ident!("{}", &f.ident, span = Span::call_site());
+			let cfgs = f.cfgs();
 			quote! {
+				#(#cfgs)*
 				#ident: &doc.#ident
 			}
 		});
@@ -82,6 +127,7 @@ impl<'a> ToTokens for SerdeImpl<'a> {
           S: ::serde::Serializer,
         {
           #ser_fns
+          #(#flatten_ser_fns)*
 
           #[derive(::serde::Serialize)]
           struct #proxy_ident #proxy_generics {
@@ -1,4 +1,5 @@
 mod args;
+mod discovery_doc;
 mod entity_doc;
 mod json_doc;
 mod state_doc;
@@ -17,6 +18,15 @@ pub fn entity_document(attr: TokenStream, item: TokenStream) -> TokenStream {
 	}
 }
 
+#[proc_macro_attribute]
+pub fn discovery_document(attr: TokenStream, item: TokenStream) -> TokenStream {
+	let args = parse_macro_input!(attr as Args);
+	match discovery_doc::create(item.into(), args) {
+		Ok(tokens) => tokens.into(),
+		Err(err) => err.write_errors().into(),
+	}
+}
+
 #[proc_macro_attribute]
 pub fn state_document(attr: TokenStream, item: TokenStream) -> TokenStream {
 	let args = parse_macro_input!(attr as Args);
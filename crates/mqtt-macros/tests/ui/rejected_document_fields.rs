@@ -0,0 +1,9 @@
+use hass_mqtt_macros::discovery_document;
+
+#[discovery_document]
+pub struct Doc<'a> {
+	name: Option<&'a str>,
+	pub values: [u8; 4],
+}
+
+fn main() {}
@@ -6,6 +6,12 @@ use crate::{
 };
 use semval::{context::Context, Validate, ValidationResult};
 
+#[cfg(feature = "alloc")]
+use crate::HassItems;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
 /// When availability is configured, this controls the conditions needed to set the entity to available.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "ser", derive(serde::Serialize))]
@@ -45,7 +51,7 @@ impl Default for AvailabilityMode {
 #[cfg_attr(feature = "de", derive(serde::Deserialize))]
 pub struct Availability<'a> {
 	/// An MQTT topic subscribed to receive availability (online/offline) updates.
-	#[cfg_attr(any(feature = "ser", feature = "de"), serde(borrow))]
+	#[cfg_attr(any(feature = "ser", feature = "de"), serde(borrow, alias = "t"))]
 	pub topic: Topic<'a>,
 
 	/// The payload that represents the available state.
@@ -53,7 +59,12 @@ pub struct Availability<'a> {
 	/// The default (used if `None`) is `online`.
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
-		serde(borrow, default, skip_serializing_if = "Option::is_none")
+		serde(
+			borrow,
+			default,
+			skip_serializing_if = "Option::is_none",
+			alias = "pl_avail"
+		)
 	)]
 	pub payload_available: Option<Payload<'a>>,
 
@@ -62,7 +73,12 @@ pub struct Availability<'a> {
 	/// The default (used if `None`) is `offline`.
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
-		serde(borrow, default, skip_serializing_if = "Option::is_none")
+		serde(
+			borrow,
+			default,
+			skip_serializing_if = "Option::is_none",
+			alias = "pl_not_avail"
+		)
 	)]
 	pub payload_not_available: Option<Payload<'a>>,
 
@@ -72,7 +88,12 @@ pub struct Availability<'a> {
 	/// will be compared to payload_available and payload_not_available.
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
-		serde(borrow, default, skip_serializing_if = "Option::is_none")
+		serde(
+			borrow,
+			default,
+			skip_serializing_if = "Option::is_none",
+			alias = "val_tpl"
+		)
 	)]
 	pub value_template: Option<Template<'a>>,
 }
@@ -101,6 +122,65 @@ impl<'a> Availability<'a> {
 	}
 }
 
+/// Builder for the `availability` list of entity documents.
+///
+/// ```
+/// # use hass_mqtt_proto::{availability::AvailabilityList, Sensor};
+/// let availability = AvailabilityList::new()
+///   .topic("app/default/available")
+///   .topic_with_payloads("bridge/state", "up", "down");
+///
+/// let sensor = Sensor::new("app/default/sensor/temperature/state").availability(availability);
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AvailabilityList<'a> {
+	items: Vec<Availability<'a>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> AvailabilityList<'a> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds an availability topic using the default payloads (`online` and `offline`).
+	pub fn topic(self, topic: impl Into<Topic<'a>>) -> Self {
+		self.availability(Availability::new(topic))
+	}
+
+	/// Adds an availability topic with custom payloads.
+	pub fn topic_with_payloads(
+		self,
+		topic: impl Into<Topic<'a>>,
+		available_payload: impl Into<Payload<'a>>,
+		not_available_payload: impl Into<Payload<'a>>,
+	) -> Self {
+		self.availability(Availability::new_with_payloads(
+			topic,
+			available_payload,
+			not_available_payload,
+		))
+	}
+
+	pub fn availability(mut self, availability: impl Into<Availability<'a>>) -> Self {
+		self.items.push(availability.into());
+		self
+	}
+
+	pub fn build(self) -> HassItems<'a, Availability<'a>> {
+		HassItems::Vec(self.items)
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<AvailabilityList<'a>> for HassItems<'a, Availability<'a>> {
+	#[inline]
+	fn from(list: AvailabilityList<'a>) -> Self {
+		list.build()
+	}
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum AvailabilityDataInvalidity {
 	Topic(TopicInvalidity),
@@ -138,7 +218,7 @@ impl<'a> Validate for Availability<'a> {
 mod tests {
 	use super::*;
 	use crate::string::HassStr;
-	use alloc::vec::Vec;
+	use alloc::{vec, vec::Vec};
 	use assert_matches::assert_matches;
 	use nameof::{name_of, name_of_type};
 	use serde_test::{assert_tokens, Token};
@@ -201,6 +281,34 @@ mod tests {
 		assert_matches!(availability.topic, Topic(HassStr::Borrowed(_)));
 	}
 
+	#[test]
+	fn availability_list() {
+		let sensor = crate::Sensor::new("app/default/sensor/temperature/state")
+			.availability(
+				AvailabilityList::new()
+					.topic("app/default/available")
+					.topic_with_payloads("bridge/state", "up", "down"),
+			)
+			.availability_mode(AvailabilityMode::All);
+
+		let json = serde_json::to_value(&sensor).expect("should serialize");
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"availability": [
+					{ "topic": "app/default/available" },
+					{
+						"topic": "bridge/state",
+						"payload_available": "up",
+						"payload_not_available": "down",
+					},
+				],
+				"availability_mode": "all",
+				"state_topic": "app/default/sensor/temperature/state",
+			})
+		);
+	}
+
 	#[test]
 	fn invalid_payload_available_is_invalid() {
 		let err: Vec<_> = Availability {
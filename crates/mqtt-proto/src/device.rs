@@ -16,28 +16,43 @@ pub struct Device<'a> {
 	/// For example the MAC address of a network interface: "connections": [["mac", "02:5b:26:a8:dc:12"]].
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
-		serde(borrow, default, skip_serializing_if = "<[_]>::is_empty")
+		serde(
+			borrow,
+			default,
+			skip_serializing_if = "<[_]>::is_empty",
+			alias = "cns"
+		)
 	)]
 	pub connections: HassItems<'a, ConnectionInfo<'a>>,
 
 	/// A list of IDs that uniquely identify the device. For example a serial number.
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
-		serde(borrow, default, skip_serializing_if = "<[_]>::is_empty")
+		serde(
+			borrow,
+			default,
+			skip_serializing_if = "<[_]>::is_empty",
+			alias = "ids"
+		)
 	)]
 	pub identifiers: HassItems<'a, HassStr<'a>>,
 
 	/// The manufacturer of the device.
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
-		serde(borrow, default, skip_serializing_if = "Option::is_none")
+		serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "mf")
 	)]
 	pub manufacturer: Option<HassStr<'a>>,
 
 	/// The model of the device.
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
-		serde(borrow, default, skip_serializing_if = "Option::is_none")
+		serde(
+			borrow,
+			default,
+			skip_serializing_if = "Option::is_none",
+			alias = "mdl"
+		)
 	)]
 	pub model: Option<HassStr<'a>>,
 
@@ -50,21 +65,21 @@ pub struct Device<'a> {
 	/// Suggest an area if the device isn’t in one yet.
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
-		serde(borrow, default, skip_serializing_if = "Option::is_none")
+		serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "sa")
 	)]
 	pub suggested_area: Option<HassStr<'a>>,
 
 	/// The firmware version of the device.
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
-		serde(borrow, default, skip_serializing_if = "Option::is_none")
+		serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "sw")
 	)]
 	pub sw_version: Option<HassStr<'a>>,
 
 	/// The hardware version of the device.
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
-		serde(borrow, default, skip_serializing_if = "Option::is_none")
+		serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "hw")
 	)]
 	pub hw_version: Option<HassStr<'a>>,
 
@@ -81,7 +96,7 @@ pub struct Device<'a> {
 	/// Can be either an HTTP or HTTPS link.
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
-		serde(borrow, default, skip_serializing_if = "Option::is_none")
+		serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "cu")
 	)]
 	pub configuration_url: Option<HassStr<'a>>,
 }
@@ -0,0 +1,161 @@
+use crate::{
+	device::Device, payload::Payload, qos::MqttQoS, template::Template, topic::Topic,
+	validation::Validator, HassStr,
+};
+use hass_mqtt_macros::discovery_document;
+
+/// Device triggers let a device expose stateless events (like button presses)
+/// that can be used as triggers in Home Assistant automations.
+///
+/// Unlike entities, device triggers must always be tied to a [Device].
+///
+/// See: <https://www.home-assistant.io/integrations/device_trigger.mqtt/>
+#[discovery_document]
+#[discovery(extend_json(automation_type = "trigger"))]
+#[discovery(validate(DeviceEmpty))]
+pub struct DeviceTrigger<'a> {
+	/// Information about the device this device trigger is a part of to tie it into
+	/// the device registry. At least one of identifiers or connections must be present
+	/// to identify the device.
+	#[serde(borrow, alias = "dev")]
+	#[discovery(validate)]
+	pub device: Device<'a>,
+
+	/// Optional payload to match the payload being sent over the topic.
+	#[serde(borrow, default, skip_serializing_if = "Option::is_none", alias = "pl")]
+	#[discovery(validate)]
+	pub payload: Option<Payload<'a>>,
+
+	/// The maximum QoS level to be used when receiving messages.
+	#[serde(default, skip_serializing_if = "MqttQoS::is_default")]
+	pub qos: MqttQoS,
+
+	/// The subtype of the trigger, e.g. `button_1`. Entries supported by the frontend:
+	/// `turn_on`, `turn_off`, `button_1`, `button_2`, `button_3`, `button_4`, `button_5`,
+	/// `button_6`. If set to an unsupported value, will render as `subtype type`, e.g.
+	/// `left_button pressed` with `type` set to `button_short_press` and `subtype` set
+	/// to `left_button`.
+	#[serde(borrow, alias = "stype")]
+	pub subtype: HassStr<'a>,
+
+	/// The MQTT topic subscribed to receive trigger events.
+	#[serde(borrow, alias = "t")]
+	#[discovery(validate)]
+	pub topic: Topic<'a>,
+
+	/// The type of the trigger, e.g. `button_short_press`. Entries supported by the
+	/// frontend: `button_short_press`, `button_short_release`, `button_long_press`,
+	/// `button_long_release`, `button_double_press`, `button_triple_press`,
+	/// `button_quadruple_press`, `button_quintuple_press`. If set to an unsupported
+	/// value, will render as `subtype type`, e.g. `button_1 spammed` with `type` set
+	/// to `spammed` and `subtype` set to `button_1`.
+	#[serde(borrow, rename = "type")]
+	pub trigger_type: HassStr<'a>,
+
+	/// Defines a [template][template] to extract the value.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "val_tpl"
+	)]
+	#[discovery(validate)]
+	pub value_template: Option<Template<'a>>,
+}
+
+impl<'a> Validator for DeviceTrigger<'a> {
+	type Invalidity = DeviceTriggerInvalidity;
+
+	fn validate_value(
+		&self,
+		value: &Self,
+		context: semval::context::Context<Self::Invalidity>,
+	) -> semval::context::Context<Self::Invalidity> {
+		context.invalidate_if(
+			value.device.is_empty(),
+			DeviceTriggerInvalidity::DeviceEmpty,
+		)
+	}
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "ser", feature = "de"))]
+mod tests {
+	use super::*;
+	use crate::HassItems;
+	use alloc::{vec, vec::Vec};
+	use semval::Validate;
+
+	fn device() -> Device<'static> {
+		Device {
+			identifiers: HassItems::Borrowed(&[HassStr::Borrowed("0xabcdef")]),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn serialize_matches_schema() {
+		let trigger = DeviceTrigger::new(
+			device(),
+			"subtype_1",
+			"devices/0xabcdef/action",
+			"button_short_press",
+		)
+		.payload("single");
+
+		let json = serde_json::to_value(&trigger).expect("should serialize");
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"automation_type": "trigger",
+				"device": {
+					"identifiers": ["0xabcdef"],
+				},
+				"payload": "single",
+				"subtype": "subtype_1",
+				"topic": "devices/0xabcdef/action",
+				"type": "button_short_press",
+			})
+		);
+	}
+
+	#[test]
+	fn deserialize_from_schema() {
+		let json = r#"{
+			"automation_type": "trigger",
+			"device": { "identifiers": ["0xabcdef"] },
+			"subtype": "subtype_1",
+			"topic": "devices/0xabcdef/action",
+			"type": "button_short_press"
+		}"#;
+
+		let trigger: DeviceTrigger = serde_json::from_str(json).expect("should parse");
+		assert_eq!(
+			trigger,
+			DeviceTrigger::new(
+				device(),
+				"subtype_1",
+				"devices/0xabcdef/action",
+				"button_short_press",
+			)
+		);
+	}
+
+	#[test]
+	fn empty_device_is_invalid() {
+		let err: Vec<_> = DeviceTrigger::new(
+			Device::default(),
+			"subtype_1",
+			"devices/0xabcdef/action",
+			"button_short_press",
+		)
+		.validate()
+		.expect_err("should be invalid")
+		.into_iter()
+		.collect();
+
+		assert_eq!(&*err, &[DeviceTriggerInvalidity::DeviceEmpty]);
+	}
+}
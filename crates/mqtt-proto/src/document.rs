@@ -4,6 +4,13 @@ use semval::Validate;
 use semval::Validated;
 
 pub trait Document: Sized + Validate {
+	/// Validates the document, returning it if it's valid, or all its invalidities if not.
+	/// Meant for the end of a chain of builder methods, to find out about missing or
+	/// conflicting fields before the document is published.
+	fn validated(self) -> Result<Self, semval::context::Context<Self::Invalidity>> {
+		self.validate().map(|()| self)
+	}
+
 	#[cfg(feature = "ser")]
 	fn serialize_validated<S: serde::Serializer>(
 		validated: Validated<&Self>,
@@ -28,4 +35,19 @@ pub trait Document: Sized + Validate {
 
 		Self::serialize_validated(validated, serializer)
 	}
+
+	/// Serializes the document as pretty-printed JSON, validating it like
+	/// [serialize](Document::serialize) does. Useful for debugging, or to write fixtures.
+	#[cfg(feature = "pretty-json")]
+	#[cfg_attr(doc_cfg, doc(cfg(feature = "pretty-json")))]
+	fn to_pretty_json(&self) -> Result<alloc::string::String, serde_json::Error> {
+		struct PrettyJson<'a, D>(&'a D);
+		impl<'a, D: Document> serde::Serialize for PrettyJson<'a, D> {
+			fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+				Document::serialize(self.0, serializer)
+			}
+		}
+
+		serde_json::to_string_pretty(&PrettyJson(self))
+	}
 }
@@ -1,18 +1,30 @@
 mod binary_sensor;
 mod button;
+mod climate;
 mod cover;
 mod device_tracker;
+mod fan;
 mod light;
+mod lock;
+mod number;
+mod select;
 mod sensor;
 mod switch;
 
 pub use binary_sensor::{BinarySensor, BinarySensorInvalidity};
 pub use button::{Button, ButtonInvalidity};
-pub use cover::{Cover, CoverInvalidity};
+pub use climate::{Climate, ClimateInvalidity};
+pub use cover::{Cover, CoverInvalidity, CoverState, CoverStateInvalidity, CoverStatus};
 pub use device_tracker::{DeviceTracker, DeviceTrackerInvalidity};
+pub use fan::{Fan, FanInvalidity};
 pub use light::{
 	ColorMode, ColorModesInvalidity, Light, LightColorState, LightInvalidity, LightState,
-	LightStateInvalidity, OnOff,
+	LightStateInvalidity, OnOff, OnOffParseError,
 };
+#[cfg(feature = "json")]
+pub use light::{LightCommand, LightSubCommand};
+pub use lock::{Lock, LockInvalidity};
+pub use number::{Number, NumberInvalidity, NumberMode};
+pub use select::{Select, SelectInvalidity};
 pub use sensor::{Sensor, SensorInvalidity};
-pub use switch::{Switch, SwitchInvalidity};
+pub use switch::{Switch, SwitchInvalidity, SwitchState, SwitchStateInvalidity};
@@ -23,40 +23,54 @@ pub struct BinarySensor<'a> {
 	/// the icon in the frontend.
 	///
 	/// [device_class]: https://www.home-assistant.io/integrations/binary_sensor/#device-class
-	#[serde(default, skip_serializing_if = "DeviceClass::is_none")]
+	#[serde(
+		default,
+		skip_serializing_if = "DeviceClass::is_none",
+		alias = "dev_cla"
+	)]
 	pub device_class: DeviceClass,
 
 	/// Defines the number of seconds after the value expires if it's not updated. After
 	/// expiry, the sensor’s state becomes `unavailable`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "exp_aft")]
 	pub expire_after: Option<NonZeroU32>,
 
 	/// Sends update events (which results in update of state object’s last_changed) even if the
 	/// sensor’s state hasn’t changed. Useful if you want to have meaningful value graphs in history
 	/// or want to create an automation that triggers on every incoming state message (not only when
 	/// the sensor’s new state is different to the current one).
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "frc_upd")]
 	pub force_update: Option<bool>,
 
 	/// For sensors that only send `on` state updates (like PIRs), this variable sets a
 	/// delay in seconds after which the sensor’s state will be updated back to `off`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "off_dly")]
 	pub off_delay: Option<NonZeroU32>,
 
 	/// The string that represents the `off` state. It will be compared to the message in the
 	/// `state_topic` (see `value_template` for details)
 	/// Defaults to `"OFF"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_off"
+	)]
 	pub payload_off: Option<Payload<'a>>,
 
 	/// The string that represents the `on` state. It will be compared to the message in the
 	/// `state_topic` (see `value_template` for details)
 	/// Defaults to `"ON"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_on"
+	)]
 	pub payload_on: Option<Payload<'a>>,
 
 	/// The MQTT topic subscribed to receive sensor values.
-	#[serde(borrow)]
+	#[serde(borrow, alias = "stat_t")]
 	pub state_topic: Topic<'a>,
 
 	/// Defines a [template][template] that returns a string to be compared to
@@ -66,6 +80,16 @@ pub struct BinarySensor<'a> {
 	/// (i.e no pre-processing of original message is required).
 	///
 	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "val_tpl"
+	)]
 	pub value_template: Option<Template<'a>>,
 }
+
+impl<'a> BinarySensor<'a> {
+	/// The variables available within [BinarySensor::value_template].
+	pub const VALUE_TEMPLATE_VARS: &'static [&'static str] = &["entity_id"];
+}
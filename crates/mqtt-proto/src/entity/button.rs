@@ -11,26 +11,41 @@ pub struct Button<'a> {
 	/// Defines a [template][template] to generate the payload to send to `command_topic`.
 	///
 	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#using-templates-with-the-mqtt-integration
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "cmd_tpl"
+	)]
 	pub command_template: Option<Template<'a>>,
 
 	/// The MQTT topic to publish commands to trigger the button.
-	#[serde(borrow)]
+	#[serde(borrow, alias = "cmd_t")]
 	pub command_topic: Topic<'a>,
 
 	/// The [type/class][device_class] of the button to set the icon in the frontend.
 	///
 	/// [device_class]: https://www.home-assistant.io/integrations/button/#device-class
-	#[serde(default, skip_serializing_if = "DeviceClass::is_none")]
+	#[serde(
+		default,
+		skip_serializing_if = "DeviceClass::is_none",
+		alias = "dev_cla"
+	)]
 	pub device_class: DeviceClass,
 
 	/// The payload to send to trigger the button.
 	/// Defaults to `"PRESS"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_prs"
+	)]
 	pub payload_press: Option<Payload<'a>>,
 
 	/// If the published message should have the retain flag on or not.
 	/// Defaults to `false`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "ret")]
+	#[entity(ha_default = "false")]
 	pub retain: Option<bool>,
 }
@@ -0,0 +1,436 @@
+use crate::{template::Template, topic::Topic, validation::Validator, HassItems, HassStr};
+use hass_mqtt_macros::entity_document;
+
+/// The mqtt climate platform lets you control your MQTT enabled HVAC devices.
+///
+/// See: <https://www.home-assistant.io/integrations/climate.mqtt/>
+#[entity_document(?Eq)]
+#[entity(validate(ModesMissing, TemperatureRangeInverted))]
+pub struct Climate<'a> {
+	/// A template to render the value received on the `action_topic` with.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "act_tpl"
+	)]
+	pub action_template: Option<Template<'a>>,
+
+	/// The MQTT topic to subscribe for changes of the current action. If this
+	/// is set, the climate graph uses the value received as data source.
+	/// Valid values: `off`, `heating`, `cooling`, `drying`, `idle`, `fan`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "act_t"
+	)]
+	pub action_topic: Option<Topic<'a>>,
+
+	/// A template with which the value received on `current_temperature_topic`
+	/// will be rendered.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "curr_temp_tpl"
+	)]
+	pub current_temperature_template: Option<Template<'a>>,
+
+	/// The MQTT topic on which to listen for the current temperature.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "curr_temp_t"
+	)]
+	pub current_temperature_topic: Option<Topic<'a>>,
+
+	/// A template to render the value sent to the `fan_mode_command_topic` with.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "fan_mode_cmd_tpl"
+	)]
+	pub fan_mode_command_template: Option<Template<'a>>,
+
+	/// The MQTT topic to publish commands to change the fan mode.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "fan_mode_cmd_t"
+	)]
+	pub fan_mode_command_topic: Option<Topic<'a>>,
+
+	/// A template to render the value received on the `fan_mode_state_topic` with.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "fan_mode_stat_tpl"
+	)]
+	pub fan_mode_state_template: Option<Template<'a>>,
+
+	/// The MQTT topic to subscribe for changes of the fan mode.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "fan_mode_stat_t"
+	)]
+	pub fan_mode_state_topic: Option<Topic<'a>>,
+
+	/// A list of supported fan modes.
+	/// Defaults to `["auto", "low", "medium", "high"]`.
+	#[serde(borrow, default, skip_serializing_if = "<[_]>::is_empty")]
+	pub fan_modes: HassItems<'a, HassStr<'a>>,
+
+	/// Maximum set point available.
+	/// Defaults to `35` °C or `95` °F.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub max_temp: Option<f32>,
+
+	/// Minimum set point available.
+	/// Defaults to `7` °C or `44.6` °F.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub min_temp: Option<f32>,
+
+	/// A template to render the value sent to the `mode_command_topic` with.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "mode_cmd_tpl"
+	)]
+	pub mode_command_template: Option<Template<'a>>,
+
+	/// The MQTT topic to publish commands to change the HVAC operation mode.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "mode_cmd_t"
+	)]
+	pub mode_command_topic: Option<Topic<'a>>,
+
+	/// A template to render the value received on the `mode_state_topic` with.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "mode_stat_tpl"
+	)]
+	pub mode_state_template: Option<Template<'a>>,
+
+	/// The MQTT topic to subscribe for changes of the HVAC operation mode.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "mode_stat_t"
+	)]
+	pub mode_state_topic: Option<Topic<'a>>,
+
+	/// A list of supported HVAC operation modes. This is required if
+	/// [Self::temperature_command_topic] is set.
+	/// Defaults to `["auto", "off", "cool", "heat", "dry", "fan_only"]`.
+	#[serde(borrow, default, skip_serializing_if = "<[_]>::is_empty")]
+	pub modes: HassItems<'a, HassStr<'a>>,
+
+	/// Flag that defines if the climate works in optimistic mode.
+	/// Defaults to `true` if no state topic is defined, else `false`.
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "opt")]
+	pub optimistic: Option<bool>,
+
+	/// The desired precision for this device. Can be used to match your actual
+	/// thermostat's precision. Supported values are `0.1`, `0.5` and `1.0`.
+	/// Defaults to `0.1` for Celsius and `1.0` for Fahrenheit.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub precision: Option<f32>,
+
+	/// A template to render the value sent to the `preset_mode_command_topic` with.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pr_mode_cmd_tpl"
+	)]
+	pub preset_mode_command_template: Option<Template<'a>>,
+
+	/// The MQTT topic to publish commands to change the preset mode.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pr_mode_cmd_t"
+	)]
+	pub preset_mode_command_topic: Option<Topic<'a>>,
+
+	/// The MQTT topic to subscribe for changes of the preset mode.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pr_mode_stat_t"
+	)]
+	pub preset_mode_state_topic: Option<Topic<'a>>,
+
+	/// A template to render the value received on the `preset_mode_state_topic` with.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pr_mode_val_tpl"
+	)]
+	pub preset_mode_value_template: Option<Template<'a>>,
+
+	/// A list of supported preset modes, like `eco`, `away` or `boost`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "<[_]>::is_empty",
+		alias = "pr_modes"
+	)]
+	pub preset_modes: HassItems<'a, HassStr<'a>>,
+
+	/// Defines if published messages should have the retain flag set.
+	/// Defaults to `false`.
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "ret")]
+	#[entity(ha_default = "false")]
+	pub retain: Option<bool>,
+
+	/// A template to render the value sent to the `swing_mode_command_topic` with.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "swing_mode_cmd_tpl"
+	)]
+	pub swing_mode_command_template: Option<Template<'a>>,
+
+	/// The MQTT topic to publish commands to change the swing mode.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "swing_mode_cmd_t"
+	)]
+	pub swing_mode_command_topic: Option<Topic<'a>>,
+
+	/// A template to render the value received on the `swing_mode_state_topic` with.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "swing_mode_stat_tpl"
+	)]
+	pub swing_mode_state_template: Option<Template<'a>>,
+
+	/// The MQTT topic to subscribe for changes of the swing mode.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "swing_mode_stat_t"
+	)]
+	pub swing_mode_state_topic: Option<Topic<'a>>,
+
+	/// A list of supported swing modes.
+	/// Defaults to `["on", "off"]`.
+	#[serde(borrow, default, skip_serializing_if = "<[_]>::is_empty")]
+	pub swing_modes: HassItems<'a, HassStr<'a>>,
+
+	/// Step size for temperature set point.
+	/// Defaults to `1`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[entity(ha_default = "1.0")]
+	pub temp_step: Option<f32>,
+
+	/// A template to render the value sent to the `temperature_command_topic` with.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "temp_cmd_tpl"
+	)]
+	pub temperature_command_template: Option<Template<'a>>,
+
+	/// The MQTT topic to publish commands to change the target temperature.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "temp_cmd_t"
+	)]
+	pub temperature_command_topic: Option<Topic<'a>>,
+
+	/// A template to render the value received on the `temperature_state_topic` with.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "temp_stat_tpl"
+	)]
+	pub temperature_state_template: Option<Template<'a>>,
+
+	/// The MQTT topic to subscribe for changes in the target temperature.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "temp_stat_t"
+	)]
+	pub temperature_state_topic: Option<Topic<'a>>,
+
+	/// Defines the temperature unit of the device, `C` or `F`.
+	/// Defaults to the unit system of Home Assistant.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "temp_unit"
+	)]
+	pub temperature_unit: Option<HassStr<'a>>,
+}
+
+impl<'a> Validator for Climate<'a> {
+	type Invalidity = ClimateInvalidity;
+
+	fn validate_value(
+		&self,
+		value: &Self,
+		context: semval::context::Context<Self::Invalidity>,
+	) -> semval::context::Context<Self::Invalidity> {
+		context
+			.invalidate_if(
+				value.temperature_command_topic.is_some() && value.modes.is_empty(),
+				ClimateInvalidity::ModesMissing,
+			)
+			.invalidate_if(
+				matches!(
+					(value.min_temp, value.max_temp),
+					(Some(min), Some(max)) if min > max
+				),
+				ClimateInvalidity::TemperatureRangeInverted,
+			)
+	}
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "ser", feature = "de"))]
+mod tests {
+	use super::*;
+	use alloc::{vec, vec::Vec};
+	use assert_matches::assert_matches;
+	use semval::Validate;
+
+	#[test]
+	fn serializes_to_discovery_json() {
+		let climate = Climate::new()
+			.mode_command_topic("hvac/mode/set")
+			.mode_state_topic("hvac/mode")
+			.modes(alloc::vec![HassStr::from("off"), HassStr::from("heat")])
+			.temperature_command_topic("hvac/temperature/set")
+			.current_temperature_topic("hvac/temperature")
+			.min_temp(10.0)
+			.max_temp(30.5)
+			.temp_step(0.5);
+
+		assert_eq!(
+			serde_json::to_value(&climate).expect("should serialize"),
+			serde_json::json!({
+				"current_temperature_topic": "hvac/temperature",
+				"max_temp": 30.5,
+				"min_temp": 10.0,
+				"mode_command_topic": "hvac/mode/set",
+				"mode_state_topic": "hvac/mode",
+				"modes": ["off", "heat"],
+				"temp_step": 0.5,
+				"temperature_command_topic": "hvac/temperature/set",
+			})
+		);
+	}
+
+	#[test]
+	fn deserializes_abbreviated_keys() {
+		let climate: Climate = serde_json::from_str(
+			r#"{
+				"mode_cmd_t": "hvac/mode/set",
+				"temp_cmd_t": "hvac/temperature/set",
+				"curr_temp_t": "hvac/temperature",
+				"modes": ["off", "cool"],
+				"pr_modes": ["eco"]
+			}"#,
+		)
+		.expect("should deserialize");
+
+		assert_eq!(climate.mode_command_topic.as_deref(), Some("hvac/mode/set"));
+		assert_eq!(
+			climate.temperature_command_topic.as_deref(),
+			Some("hvac/temperature/set")
+		);
+		assert_eq!(
+			climate.current_temperature_topic.as_deref(),
+			Some("hvac/temperature")
+		);
+		assert_eq!(
+			&*climate.modes,
+			&[HassStr::from("off"), HassStr::from("cool")]
+		);
+		assert_eq!(&*climate.preset_modes, &[HassStr::from("eco")]);
+	}
+
+	#[test]
+	fn deserialization_borrows() {
+		let json = r#"{"mode_command_topic":"hvac/mode/set","modes":["off","heat"]}"#;
+		let climate: Climate = serde_json::from_str(json).expect("should parse");
+		assert_matches!(
+			climate.mode_command_topic,
+			Some(Topic(HassStr::Borrowed(_)))
+		);
+		assert!(climate.modes.iter().all(HassStr::is_borrowed));
+	}
+
+	#[test]
+	fn temperature_command_topic_without_modes_is_invalid() {
+		let err: Vec<_> = Climate::new()
+			.temperature_command_topic("hvac/temperature/set")
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(&*err, &[ClimateInvalidity::ModesMissing]);
+	}
+
+	#[test]
+	fn temperature_command_topic_with_modes_is_valid() {
+		Climate::new()
+			.temperature_command_topic("hvac/temperature/set")
+			.modes(alloc::vec![HassStr::from("heat")])
+			.validate()
+			.expect("should be valid");
+	}
+
+	#[test]
+	fn inverted_temperature_range_is_invalid() {
+		let err: Vec<_> = Climate::new()
+			.min_temp(25.0)
+			.max_temp(18.0)
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(&*err, &[ClimateInvalidity::TemperatureRangeInverted]);
+	}
+
+	#[test]
+	fn compact_omits_home_assistant_defaults() {
+		let climate = Climate::new().temp_step(1.0).retain(false).compact();
+		assert_eq!(climate.temp_step, None);
+		assert_eq!(climate.retain, None);
+	}
+}
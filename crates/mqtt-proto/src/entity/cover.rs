@@ -2,7 +2,7 @@ use crate::{
 	device_class::DeviceClass, payload::Payload, template::Template, topic::Topic,
 	validation::Validator,
 };
-use hass_mqtt_macros::entity_document;
+use hass_mqtt_macros::{entity_document, state_document};
 
 /// The mqtt cover platform allows you to control an MQTT cover (such as blinds, a roller shutter or a garage door).
 ///
@@ -17,43 +17,69 @@ use hass_mqtt_macros::entity_document;
 ))]
 pub struct Cover<'a> {
 	/// The MQTT topic to publish commands to control the cover.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "cmd_t"
+	)]
 	pub command_topic: Option<Topic<'a>>,
 
 	/// Sets the [class of the device][device_class], changing the device state and icon that is displayed on the frontend.
 	///
 	/// [device_class]: https://www.home-assistant.io/integrations/cover/#device-class
-	#[serde(default, skip_serializing_if = "DeviceClass::is_none")]
+	#[serde(
+		default,
+		skip_serializing_if = "DeviceClass::is_none",
+		alias = "dev_cla"
+	)]
 	pub device_class: DeviceClass,
 
 	/// Flag that defines if the cover works in optimistic mode.
 	/// Defaults to `false` if a `state_topic` or `position_topic` is defined, else `true`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "opt")]
 	pub optimistic: Option<bool>,
 
 	/// The command payload that closes the cover.
 	/// Defaults to `"CLOSE"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_cls"
+	)]
 	pub payload_close: Option<Payload<'a>>,
 
 	/// The command payload that opens the cover.
 	/// Defaults to `"OPEN"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_open"
+	)]
 	pub payload_open: Option<Payload<'a>>,
 
 	/// The command payload that stops the cover.
 	/// Defaults to `"STOP"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_stop"
+	)]
 	pub payload_stop: Option<Payload<'a>>,
 
 	/// Number which represents closed position.
 	/// Defaults to `0`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "pos_clsd")]
+	#[entity(ha_default = "0")]
 	pub position_closed: Option<u32>,
 
 	/// Number which represents open position.
 	/// Defaults to `100`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "pos_open")]
+	#[entity(ha_default = "100")]
 	pub position_open: Option<u32>,
 
 	/// Defines a [template][template] that can be used to extract the
@@ -70,16 +96,27 @@ pub struct Cover<'a> {
 	///
 	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
 	/// [states]: https://www.home-assistant.io/docs/configuration/templating/#states
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pos_tpl"
+	)]
 	pub position_template: Option<Template<'a>>,
 
 	/// The MQTT topic subscribed to receive cover position messages.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pos_t"
+	)]
 	pub position_topic: Option<Topic<'a>>,
 
 	/// Defines if published messages should have the retain flag set.
 	/// Defaults to `false`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "ret")]
+	#[entity(ha_default = "false")]
 	pub retain: Option<bool>,
 
 	/// Defines a [template][template] to define the position to be sent
@@ -98,7 +135,12 @@ pub struct Cover<'a> {
 	///
 	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
 	/// [states]: https://www.home-assistant.io/docs/configuration/templating/#states
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "set_pos_tpl"
+	)]
 	pub set_position_template: Option<Template<'a>>,
 
 	/// The MQTT topic to publish position commands to.
@@ -107,44 +149,84 @@ pub struct Cover<'a> {
 	/// range `position_closed` - `position_open`. If template is not defined
 	/// and `position_closed` != 100 and `position_open` != 0 then proper
 	/// position value is calculated from percentage position.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "set_pos_t"
+	)]
 	pub set_position_topic: Option<Topic<'a>>,
 
 	/// The payload that represents the closed state.
 	/// Defaults to `"closed"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_clsd"
+	)]
 	pub state_closed: Option<Payload<'a>>,
 
 	/// The payload that represents the closing state.
 	/// Defaults to `"closing"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_closing"
+	)]
 	pub state_closing: Option<Payload<'a>>,
 
 	/// The payload that represents the open state.
 	/// Defaults to `"open"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_open"
+	)]
 	pub state_open: Option<Payload<'a>>,
 
 	/// The payload that represents the opening state.
 	/// Defaults to `"opening"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_opening"
+	)]
 	pub state_opening: Option<Payload<'a>>,
 
 	/// The payload that represents the stopped state
 	/// (for covers that do not report `open`/`closed` state).
 	/// Defaults to `"stopped"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_stopped"
+	)]
 	pub state_stopped: Option<Payload<'a>>,
 
 	/// The MQTT topic subscribed to receive cover state messages.
 	/// State topic can only read (`open`, `opening`, `closed`, `closing`
 	/// or `stopped`) state.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_t"
+	)]
 	pub state_topic: Option<Topic<'a>>,
 
 	/// The value that will be sent on a close_cover_tilt command.
 	/// Defaults to `0`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "tilt_clsd_val"
+	)]
+	#[entity(ha_default = "0")]
 	pub tilt_closed_value: Option<u32>,
 
 	/// Defines a [template][template] that can be used to extract the
@@ -162,26 +244,43 @@ pub struct Cover<'a> {
 	///
 	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
 	/// [states]: https://www.home-assistant.io/docs/configuration/templating/#states
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "tilt_cmd_tpl"
+	)]
 	pub tilt_command_template: Option<Template<'a>>,
 
 	/// The MQTT topic to publish commands to control the cover tilt.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "tilt_cmd_t"
+	)]
 	pub tilt_command_topic: Option<Topic<'a>>,
 
 	/// The maximum tilt value.
 	/// Defaults to `100`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "tilt_max")]
+	#[entity(ha_default = "100")]
 	pub tilt_max: Option<u32>,
 
 	/// The minimum tilt value.
 	/// Defaults to `0`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "tilt_min")]
+	#[entity(ha_default = "0")]
 	pub tilt_min: Option<u32>,
 
 	/// The value that will be sent on an `open_cover_tilt` command.
 	/// Defaults to `100`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "tilt_opnd_val"
+	)]
+	#[entity(ha_default = "100")]
 	pub tilt_opened_value: Option<u32>,
 
 	/// Flag that determines if tilt works in optimistic mode.
@@ -203,21 +302,76 @@ pub struct Cover<'a> {
 	///
 	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
 	/// [states]: https://www.home-assistant.io/docs/configuration/templating/#states
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "tilt_status_tpl"
+	)]
 	pub tilt_status_template: Option<Template<'a>>,
 
 	/// The MQTT topic subscribed to receive tilt status update values.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "tilt_status_t"
+	)]
 	pub tilt_status_topic: Option<Topic<'a>>,
 
 	/// Defines a [template][template] that can be used to extract the
 	/// payload for the `state_topic` topic.
 	///
 	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "val_tpl"
+	)]
 	pub value_template: Option<Template<'a>>,
 }
 
+impl<'a> Cover<'a> {
+	/// The variables available within [Cover::position_template].
+	pub const POSITION_TEMPLATE_VARS: &'static [&'static str] = &[
+		"entity_id",
+		"position_open",
+		"position_closed",
+		"tilt_min",
+		"tilt_max",
+	];
+
+	/// The variables available within [Cover::set_position_template].
+	pub const SET_POSITION_TEMPLATE_VARS: &'static [&'static str] = &[
+		"entity_id",
+		"position",
+		"position_open",
+		"position_closed",
+		"tilt_min",
+		"tilt_max",
+	];
+
+	/// The variables available within [Cover::tilt_command_template].
+	pub const TILT_COMMAND_TEMPLATE_VARS: &'static [&'static str] = &[
+		"entity_id",
+		"tilt_position",
+		"position_open",
+		"position_closed",
+		"tilt_min",
+		"tilt_max",
+	];
+
+	/// The variables available within [Cover::tilt_status_template].
+	pub const TILT_STATUS_TEMPLATE_VARS: &'static [&'static str] = &[
+		"entity_id",
+		"position_open",
+		"position_closed",
+		"tilt_min",
+		"tilt_max",
+	];
+}
+
 impl<'a> Validator for Cover<'a> {
 	type Invalidity = CoverInvalidity;
 
@@ -254,3 +408,114 @@ impl<'a> Validator for Cover<'a> {
 			)
 	}
 }
+
+/// The states a cover can report on its state topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum CoverStatus {
+	#[serde(rename = "open")]
+	Open,
+
+	#[serde(rename = "opening")]
+	Opening,
+
+	#[serde(rename = "closed")]
+	Closed,
+
+	#[serde(rename = "closing")]
+	Closing,
+
+	#[serde(rename = "stopped")]
+	Stopped,
+}
+
+/// The state of a cover, including its position and tilt when the cover
+/// supports them.
+#[state_document]
+#[state(validate(PositionOutOfRange))]
+pub struct CoverState {
+	/// Current position of the cover, in percent (`0` is closed, `100` is open).
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub position: Option<u8>,
+
+	/// Current cover state.
+	pub state: CoverStatus,
+
+	/// Current tilt position of the cover.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub tilt: Option<u8>,
+}
+
+impl Validator for CoverState {
+	type Invalidity = CoverStateInvalidity;
+
+	fn validate_value(
+		&self,
+		value: &Self,
+		context: semval::context::Context<Self::Invalidity>,
+	) -> semval::context::Context<Self::Invalidity> {
+		context.invalidate_if(
+			matches!(value.position, Some(position) if position > 100),
+			CoverStateInvalidity::PositionOutOfRange,
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[cfg(all(feature = "ser", feature = "de"))]
+	use alloc::vec::Vec;
+	#[cfg(all(feature = "ser", feature = "de"))]
+	use semval::Validate;
+
+	#[test]
+	fn position_template_vars() {
+		assert_eq!(
+			Cover::POSITION_TEMPLATE_VARS,
+			[
+				"entity_id",
+				"position_open",
+				"position_closed",
+				"tilt_min",
+				"tilt_max"
+			]
+		);
+	}
+
+	#[test]
+	#[cfg(all(feature = "ser", feature = "de"))]
+	fn state_round_trips_through_json() {
+		let state = CoverState::new(CoverStatus::Opening).position(40).tilt(10);
+
+		let json = serde_json::to_value(&state).expect("should serialize");
+		assert_eq!(
+			json,
+			serde_json::json!({ "position": 40, "state": "opening", "tilt": 10 })
+		);
+		assert_eq!(
+			serde_json::from_value::<CoverState>(json).expect("should parse"),
+			state
+		);
+
+		let json =
+			serde_json::to_value(CoverState::new(CoverStatus::Stopped)).expect("should serialize");
+		assert_eq!(json, serde_json::json!({ "state": "stopped" }));
+	}
+
+	#[test]
+	#[cfg(all(feature = "ser", feature = "de"))]
+	fn position_above_100_is_invalid() {
+		let err: Vec<_> = CoverState::new(CoverStatus::Open)
+			.position(101)
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+		assert_eq!(&*err, &[CoverStateInvalidity::PositionOutOfRange]);
+
+		CoverState::new(CoverStatus::Open)
+			.position(100)
+			.validate()
+			.expect("should be valid");
+	}
+}
@@ -12,28 +12,43 @@ use hass_mqtt_macros::entity_document;
 pub struct DeviceTracker<'a> {
 	/// The payload value that represents the `home` state for the device.
 	/// Defaults to `"home"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_home"
+	)]
 	pub payload_home: Option<Payload<'a>>,
 
 	/// The payload value that represents the `not_home` state for the device.
 	/// Defaults to `"not_home"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_not_home"
+	)]
 	pub payload_not_home: Option<Payload<'a>>,
 
 	/// Attribute of a device tracker that affects state when being used to track a [person][person].
 	/// Valid options are `gps`, `router`, `bluetooth`, or `bluetooth_le`.
 	///
 	/// [person]: https://www.home-assistant.io/integrations/person/
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "src_type")]
 	pub source_type: Option<DeviceTrackerSourceType>,
 
 	/// The MQTT topic subscribed to receive device tracker state changes.
-	#[serde(borrow)]
+	#[serde(borrow, alias = "stat_t")]
 	pub state_topic: Topic<'a>,
 
 	/// Defines a [template][template] that returns a device tracker state.
 	///
 	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "val_tpl"
+	)]
 	pub value_template: Option<Template<'a>>,
 }
@@ -0,0 +1,383 @@
+use crate::{
+	payload::Payload, template::Template, topic::Topic, validation::Validator, HassItems, HassStr,
+};
+use hass_mqtt_macros::entity_document;
+
+/// The mqtt fan platform lets you control your MQTT enabled fans.
+///
+/// See: <https://www.home-assistant.io/integrations/fan.mqtt/>
+#[entity_document]
+#[entity(validate(SpeedRangeMinZero, SpeedRangeInverted, SpeedRangeMissing))]
+pub struct Fan<'a> {
+	/// Defines a [template][template] to generate the payload to send to
+	/// `command_topic`.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#using-command-templates-with-mqtt
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "cmd_tpl"
+	)]
+	pub command_template: Option<Template<'a>>,
+
+	/// The MQTT topic to publish commands to change the fan state.
+	#[serde(borrow, alias = "cmd_t")]
+	pub command_topic: Topic<'a>,
+
+	/// Flag that defines if the fan works in optimistic mode.
+	/// Defaults to `true` if no `state_topic` defined, else `false`.
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "opt")]
+	pub optimistic: Option<bool>,
+
+	/// Defines a [template][template] to generate the payload to send to
+	/// `oscillation_command_topic`.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#using-command-templates-with-mqtt
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "osc_cmd_tpl"
+	)]
+	pub oscillation_command_template: Option<Template<'a>>,
+
+	/// The MQTT topic to publish commands to change the oscillation state.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "osc_cmd_t"
+	)]
+	pub oscillation_command_topic: Option<Topic<'a>>,
+
+	/// The MQTT topic subscribed to receive oscillation state updates.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "osc_stat_t"
+	)]
+	pub oscillation_state_topic: Option<Topic<'a>>,
+
+	/// Defines a [template][template] to extract a value from the oscillation.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "osc_val_tpl"
+	)]
+	pub oscillation_value_template: Option<Template<'a>>,
+
+	/// The payload that represents the stop state.
+	/// Defaults to `"OFF"`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_off"
+	)]
+	pub payload_off: Option<Payload<'a>>,
+
+	/// The payload that represents the running state.
+	/// Defaults to `"ON"`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_on"
+	)]
+	pub payload_on: Option<Payload<'a>>,
+
+	/// The payload that represents the oscillation off state.
+	/// Defaults to `"oscillate_off"`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_osc_off"
+	)]
+	pub payload_oscillation_off: Option<Payload<'a>>,
+
+	/// The payload that represents the oscillation on state.
+	/// Defaults to `"oscillate_on"`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_osc_on"
+	)]
+	pub payload_oscillation_on: Option<Payload<'a>>,
+
+	/// Defines a [template][template] to generate the payload to send to
+	/// `percentage_command_topic`.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#using-command-templates-with-mqtt
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pct_cmd_tpl"
+	)]
+	pub percentage_command_template: Option<Template<'a>>,
+
+	/// The MQTT topic to publish commands to change the fan speed state based
+	/// on a percentage. This requires [Self::speed_range_min] and
+	/// [Self::speed_range_max] to be set.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pct_cmd_t"
+	)]
+	pub percentage_command_topic: Option<Topic<'a>>,
+
+	/// The MQTT topic subscribed to receive fan speed based on percentage.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pct_stat_t"
+	)]
+	pub percentage_state_topic: Option<Topic<'a>>,
+
+	/// Defines a [template][template] to extract the `percentage` value from
+	/// the payload received on `percentage_state_topic`.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pct_val_tpl"
+	)]
+	pub percentage_value_template: Option<Template<'a>>,
+
+	/// Defines a [template][template] to generate the payload to send to
+	/// `preset_mode_command_topic`.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#using-command-templates-with-mqtt
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pr_mode_cmd_tpl"
+	)]
+	pub preset_mode_command_template: Option<Template<'a>>,
+
+	/// The MQTT topic to publish commands to change the preset mode.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pr_mode_cmd_t"
+	)]
+	pub preset_mode_command_topic: Option<Topic<'a>>,
+
+	/// The MQTT topic subscribed to receive fan preset mode updates.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pr_mode_stat_t"
+	)]
+	pub preset_mode_state_topic: Option<Topic<'a>>,
+
+	/// Defines a [template][template] to extract the `preset_mode` value from
+	/// the payload received on `preset_mode_state_topic`.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pr_mode_val_tpl"
+	)]
+	pub preset_mode_value_template: Option<Template<'a>>,
+
+	/// List of preset modes this fan is capable of running at.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "<[_]>::is_empty",
+		alias = "pr_modes"
+	)]
+	pub preset_modes: HassItems<'a, HassStr<'a>>,
+
+	/// If the published message should have the retain flag on or not.
+	/// Defaults to `false`.
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "ret")]
+	#[entity(ha_default = "false")]
+	pub retain: Option<bool>,
+
+	/// The maximum of numeric output range (representing 100 %).
+	/// Defaults to `100`.
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "spd_rng_max"
+	)]
+	pub speed_range_max: Option<u32>,
+
+	/// The minimum of numeric output range (`off` not included, so
+	/// `speed_range_min - 1` represents 0 %). Must be at least `1`.
+	/// Defaults to `1`.
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "spd_rng_min"
+	)]
+	pub speed_range_min: Option<u32>,
+
+	/// The MQTT topic subscribed to receive state updates.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_t"
+	)]
+	pub state_topic: Option<Topic<'a>>,
+
+	/// Defines a [template][template] to extract a value from the state.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_val_tpl"
+	)]
+	pub state_value_template: Option<Template<'a>>,
+}
+
+impl<'a> Validator for Fan<'a> {
+	type Invalidity = FanInvalidity;
+
+	fn validate_value(
+		&self,
+		value: &Self,
+		context: semval::context::Context<Self::Invalidity>,
+	) -> semval::context::Context<Self::Invalidity> {
+		context
+			// Home Assistant maps `speed_range_min - 1` to 0 %, so the range has to start at 1
+			.invalidate_if(
+				value.speed_range_min == Some(0),
+				FanInvalidity::SpeedRangeMinZero,
+			)
+			.invalidate_if(
+				matches!(
+					(value.speed_range_min, value.speed_range_max),
+					(Some(min), Some(max)) if min > max
+				),
+				FanInvalidity::SpeedRangeInverted,
+			)
+			.invalidate_if(
+				value.percentage_command_topic.is_some()
+					&& (value.speed_range_min.is_none() || value.speed_range_max.is_none()),
+				FanInvalidity::SpeedRangeMissing,
+			)
+	}
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "ser", feature = "de"))]
+mod tests {
+	use super::*;
+	use alloc::{string::ToString, vec, vec::Vec};
+	use semval::Validate;
+
+	#[test]
+	fn round_trips_through_json() {
+		let fan = Fan::new("bedroom/fan/set")
+			.state_topic("bedroom/fan")
+			.payload_on("1")
+			.payload_off("0")
+			.percentage_command_topic("bedroom/fan/speed/set")
+			.percentage_state_topic("bedroom/fan/speed")
+			.speed_range_min(1u32)
+			.speed_range_max(3u32)
+			.preset_mode_command_topic("bedroom/fan/preset/set")
+			.preset_modes(alloc::vec![HassStr::from("breeze"), HassStr::from("sleep")])
+			.oscillation_command_topic("bedroom/fan/oscillation/set");
+
+		let json = serde_json::to_value(&fan).expect("should serialize");
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"command_topic": "bedroom/fan/set",
+				"oscillation_command_topic": "bedroom/fan/oscillation/set",
+				"payload_off": "0",
+				"payload_on": "1",
+				"percentage_command_topic": "bedroom/fan/speed/set",
+				"percentage_state_topic": "bedroom/fan/speed",
+				"preset_mode_command_topic": "bedroom/fan/preset/set",
+				"preset_modes": ["breeze", "sleep"],
+				"speed_range_max": 3,
+				"speed_range_min": 1,
+				"state_topic": "bedroom/fan",
+			})
+		);
+
+		let text = json.to_string();
+		let parsed: Fan = serde_json::from_str(&text).expect("should deserialize");
+		assert_eq!(parsed, fan);
+	}
+
+	#[test]
+	fn deserializes_abbreviated_keys() {
+		let fan: Fan = serde_json::from_str(
+			r#"{
+				"cmd_t": "bedroom/fan/set",
+				"pct_cmd_t": "bedroom/fan/speed/set",
+				"spd_rng_min": 1,
+				"spd_rng_max": 6,
+				"pl_on": "on"
+			}"#,
+		)
+		.expect("should deserialize");
+
+		assert_eq!(&*fan.command_topic, "bedroom/fan/set");
+		assert_eq!(
+			fan.percentage_command_topic.as_deref(),
+			Some("bedroom/fan/speed/set")
+		);
+		assert_eq!(fan.speed_range_min, Some(1));
+		assert_eq!(fan.speed_range_max, Some(6));
+		fan.validate().expect("should be valid");
+	}
+
+	#[test]
+	fn invalid_speed_ranges_are_rejected() {
+		let err: Vec<_> = Fan::new("bedroom/fan/set")
+			.speed_range_min(0u32)
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+		assert_eq!(&*err, &[FanInvalidity::SpeedRangeMinZero]);
+
+		let err: Vec<_> = Fan::new("bedroom/fan/set")
+			.speed_range_min(5u32)
+			.speed_range_max(2u32)
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+		assert_eq!(&*err, &[FanInvalidity::SpeedRangeInverted]);
+	}
+
+	#[test]
+	fn percentage_command_topic_without_speed_range_is_invalid() {
+		let err: Vec<_> = Fan::new("bedroom/fan/set")
+			.percentage_command_topic("bedroom/fan/speed/set")
+			.speed_range_max(3u32)
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(&*err, &[FanInvalidity::SpeedRangeMissing]);
+	}
+}
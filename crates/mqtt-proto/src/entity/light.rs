@@ -7,78 +7,108 @@ use hass_mqtt_macros::{entity_document, state_document};
 /// See: <https://www.home-assistant.io/integrations/light.mqtt/#json-schema>
 #[entity_document]
 #[entity(extend_json(schema = "json"))]
-#[entity(validate(ColorModeWithoutSupportedColorModes))]
+#[entity(validate(
+	ColorModeWithoutSupportedColorModes,
+	BrightnessScaleZero,
+	WhiteValueScaleZero,
+	MiredRangeInverted
+))]
 pub struct Light<'a> {
 	/// Flag that defines if the light supports brightness.
 	/// Defaults to `false`.
 	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[entity(ha_default = "false")]
 	pub brightness: Option<bool>,
 
 	/// Defines the maximum brightness value (i.e., 100%) of the MQTT device.
 	/// Defaults to `255`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "bri_scl")]
+	#[entity(ha_default = "255")]
 	pub brightness_scale: Option<u8>,
 
 	/// Flag that defines if the light supports color modes.
 	/// Defaults to `false`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "clrm")]
+	#[entity(ha_default = "false")]
 	pub color_mode: Option<bool>,
 
 	/// The MQTT topic to publish commands to change the light’s state.
-	#[serde(borrow)]
+	#[serde(borrow, alias = "cmd_t")]
 	pub command_topic: Topic<'a>,
 
 	/// Flag that defines if the light supports effects.
 	/// Defaults to `false`.
 	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[entity(ha_default = "false")]
 	pub effect: Option<bool>,
 
 	/// The list of effects the light supports.
-	#[serde(borrow, default, skip_serializing_if = "<[_]>::is_empty")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "<[_]>::is_empty",
+		alias = "fx_list"
+	)]
 	pub effect_list: HassItems<'a, HassStr<'a>>,
 
 	/// The duration, in seconds, of a “long” flash.
 	/// Defaults to `10`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "flsh_tlng")]
+	#[entity(ha_default = "10")]
 	pub flash_time_long: Option<u8>,
 
 	/// The duration, in seconds, of a “short” flash.
 	/// Defaults to `2`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "flsh_tsht")]
+	#[entity(ha_default = "2")]
 	pub flash_time_short: Option<u8>,
 
 	/// The maximum color temperature in mireds.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "max_mirs")]
 	pub max_mireds: Option<u16>,
 
 	/// The minimum color temperature in mireds.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "min_mirs")]
 	pub min_mireds: Option<u16>,
 
 	/// Flag that defines if light works in optimistic mode.
 	/// Defaults to `true` if no `state_topic` defined, else `false`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "opt")]
 	pub optimistic: Option<bool>,
 
 	/// If the published message should have the retain flag on or not.
 	/// Defaults to `false`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "ret")]
+	#[entity(ha_default = "false")]
 	pub retain: Option<bool>,
 
 	/// The MQTT topic subscribed to receive state updates.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_t"
+	)]
 	pub state_topic: Option<Topic<'a>>,
 
 	/// A list of color modes supported by the list. This is required if
 	/// [Self::color_mode] is `true`.
+	///
+	/// The modes are serialized in the order they are declared in [ColorMode],
+	/// regardless of the order they were added in.
 	#[entity(validate = "ColorModeSetValidator")]
-	#[serde(default, skip_serializing_if = "EnumSet::is_empty")]
+	#[serde(default, skip_serializing_if = "EnumSet::is_empty", alias = "sup_clrm")]
 	pub supported_color_modes: EnumSet<ColorMode>,
 
 	/// Defines the maximum white level (i.e., 100%) of the MQTT device. This
 	/// is used when setting the light to white mode.
 	/// Defaults to `255`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "whit_val_scl"
+	)]
+	#[entity(ha_default = "255")]
 	pub white_value_scale: Option<u8>,
 }
 
@@ -90,14 +120,34 @@ impl<'a> Validator for Light<'a> {
 		value: &Self,
 		context: semval::context::Context<Self::Invalidity>,
 	) -> semval::context::Context<Self::Invalidity> {
-		context.invalidate_if(
-			value.color_mode == Some(true) && value.supported_color_modes.is_empty(),
-			LightInvalidity::ColorModeWithoutSupportedColorModes,
-		)
+		context
+			.invalidate_if(
+				value.color_mode == Some(true) && value.supported_color_modes.is_empty(),
+				LightInvalidity::ColorModeWithoutSupportedColorModes,
+			)
+			// Home Assistant divides by the scales to convert values, so they can't be zero
+			.invalidate_if(
+				value.brightness_scale == Some(0),
+				LightInvalidity::BrightnessScaleZero,
+			)
+			.invalidate_if(
+				value.white_value_scale == Some(0),
+				LightInvalidity::WhiteValueScaleZero,
+			)
+			.invalidate_if(
+				matches!(
+					(value.min_mireds, value.max_mireds),
+					(Some(min), Some(max)) if min > max
+				),
+				LightInvalidity::MiredRangeInverted,
+			)
 	}
 }
 
 /// Color modes for lights.
+///
+/// The declaration order of the variants defines the order in which a set of
+/// color modes is serialized, so reordering them changes the wire format.
 #[derive(EnumSetType, Debug, serde::Serialize, serde::Deserialize)]
 #[enumset(serialize_as_list)]
 pub enum ColorMode {
@@ -230,8 +280,41 @@ impl OnOff {
 	pub fn is_off(&self) -> bool {
 		matches!(self, Self::Off)
 	}
+
+	/// Parses a bare `ON`/`OFF` payload, as sent to the command topic of lights and
+	/// switches that don't use the JSON schema. Matching is case-insensitive.
+	pub fn parse(payload: &[u8]) -> Result<Self, OnOffParseError> {
+		if payload.eq_ignore_ascii_case(b"ON") {
+			Ok(Self::On)
+		} else if payload.eq_ignore_ascii_case(b"OFF") {
+			Ok(Self::Off)
+		} else {
+			Err(OnOffParseError)
+		}
+	}
 }
 
+impl core::str::FromStr for OnOff {
+	type Err = OnOffParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::parse(s.as_bytes())
+	}
+}
+
+/// The error returned when a payload is neither `ON` nor `OFF`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OnOffParseError;
+
+impl core::fmt::Display for OnOffParseError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.write_str("payload is neither 'ON' nor 'OFF'")
+	}
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OnOffParseError {}
+
 impl From<bool> for OnOff {
 	fn from(value: bool) -> Self {
 		match value {
@@ -350,32 +433,47 @@ pub struct LightColorState {
 		any(feature = "ser", feature = "de"),
 		serde(default, skip_serializing_if = "Option::is_none")
 	)]
+	#[cfg_attr(feature = "de", serde(alias = "r"))]
 	red: Option<u8>,
 
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
 		serde(default, skip_serializing_if = "Option::is_none")
 	)]
+	#[cfg_attr(feature = "de", serde(alias = "g"))]
 	green: Option<u8>,
 
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
 		serde(default, skip_serializing_if = "Option::is_none")
 	)]
+	#[cfg_attr(feature = "de", serde(alias = "b"))]
 	blue: Option<u8>,
 
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
 		serde(default, skip_serializing_if = "Option::is_none")
 	)]
+	#[cfg_attr(feature = "de", serde(alias = "c"))]
 	cold_white: Option<u8>,
 
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
 		serde(default, skip_serializing_if = "Option::is_none")
 	)]
+	#[cfg_attr(feature = "de", serde(alias = "w"))]
 	white: Option<u8>,
 
+	#[cfg_attr(
+		any(feature = "ser", feature = "de"),
+		serde(
+			rename = "warm_white",
+			default,
+			skip_serializing_if = "Option::is_none"
+		)
+	)]
+	warm_white: Option<u8>,
+
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
 		serde(default, skip_serializing_if = "Option::is_none")
@@ -392,12 +490,14 @@ pub struct LightColorState {
 		any(feature = "ser", feature = "de"),
 		serde(default, skip_serializing_if = "Option::is_none")
 	)]
+	#[cfg_attr(feature = "de", serde(alias = "h"))]
 	hue: Option<f32>,
 
 	#[cfg_attr(
 		any(feature = "ser", feature = "de"),
 		serde(default, skip_serializing_if = "Option::is_none")
 	)]
+	#[cfg_attr(feature = "de", serde(alias = "s"))]
 	saturation: Option<f32>,
 }
 
@@ -428,7 +528,7 @@ impl LightColorState {
 	}
 
 	pub fn warm_white(&mut self, value: u8) -> &mut Self {
-		self.white = Some(value);
+		self.warm_white = Some(value);
 		self
 	}
 
@@ -452,3 +552,420 @@ impl LightColorState {
 		self
 	}
 }
+
+/// A command sent by Home Assistant to the command topic of a light using the JSON
+/// schema.
+///
+/// Home Assistant only sends the fields that changed (turning on a light can be just a
+/// new brightness), so the command lists the [LightSubCommand]s present in the payload
+/// instead of a full [LightState].
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightCommand<'a> {
+	commands: alloc::vec::Vec<LightSubCommand<'a>>,
+}
+
+/// A part of a [LightCommand].
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LightSubCommand<'a> {
+	On,
+	Off,
+	SetBrightness(u16),
+	SetColor(LightColorState),
+	SetEffect(HassStr<'a>),
+}
+
+#[cfg(feature = "json")]
+#[derive(serde::Deserialize)]
+struct LightCommandPayload<'a> {
+	#[serde(default)]
+	state: Option<OnOff>,
+
+	#[serde(default)]
+	brightness: Option<u16>,
+
+	#[serde(default)]
+	color: Option<LightColorState>,
+
+	#[serde(default, borrow)]
+	effect: Option<HassStr<'a>>,
+}
+
+#[cfg(feature = "json")]
+impl<'a> LightCommand<'a> {
+	/// Parses a JSON command payload, like `{"state":"ON","brightness":128}`.
+	pub fn parse(payload: &'a [u8]) -> Result<Self, serde_json::Error> {
+		let payload: LightCommandPayload<'a> = serde_json::from_slice(payload)?;
+		let commands = [
+			payload.state.map(|state| match state {
+				OnOff::On => LightSubCommand::On,
+				OnOff::Off => LightSubCommand::Off,
+			}),
+			payload.brightness.map(LightSubCommand::SetBrightness),
+			payload.color.map(LightSubCommand::SetColor),
+			payload.effect.map(LightSubCommand::SetEffect),
+		];
+
+		Ok(Self {
+			commands: commands.into_iter().flatten().collect(),
+		})
+	}
+
+	/// The sub-commands present in the payload, in the order `state`, `brightness`,
+	/// `color` and `effect`.
+	pub fn commands(&self) -> &[LightSubCommand<'a>] {
+		&self.commands
+	}
+
+	/// The requested brightness, if the command sets it.
+	pub fn brightness(&self) -> Option<u16> {
+		self.commands.iter().find_map(|command| match command {
+			LightSubCommand::SetBrightness(brightness) => Some(*brightness),
+			_ => None,
+		})
+	}
+}
+
+#[cfg(feature = "json")]
+impl<'a> IntoIterator for LightCommand<'a> {
+	type Item = LightSubCommand<'a>;
+	type IntoIter = alloc::vec::IntoIter<LightSubCommand<'a>>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.commands.into_iter()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[cfg(feature = "alloc")]
+	use crate::Document;
+	#[cfg(all(feature = "json", feature = "ser"))]
+	use alloc::vec;
+	#[cfg(feature = "alloc")]
+	use alloc::vec::Vec;
+	use semval::Validate;
+
+	#[test]
+	fn parse_on() {
+		assert_eq!(OnOff::parse(b"ON"), Ok(OnOff::On));
+	}
+
+	#[test]
+	fn parse_off_case_insensitive() {
+		assert_eq!(OnOff::parse(b"off"), Ok(OnOff::Off));
+		assert_eq!("Off".parse::<OnOff>(), Ok(OnOff::Off));
+	}
+
+	#[test]
+	fn parse_invalid() {
+		assert_eq!(OnOff::parse(b"toggle"), Err(OnOffParseError));
+		assert_eq!(OnOff::parse(br#"{"state":"ON"}"#), Err(OnOffParseError));
+	}
+
+	#[test]
+	#[cfg(feature = "json")]
+	fn parse_brightness_only_command() {
+		let command = LightCommand::parse(br#"{"brightness":128}"#).expect("should parse");
+
+		assert_eq!(command.commands(), [LightSubCommand::SetBrightness(128)]);
+		assert_eq!(command.brightness(), Some(128));
+	}
+
+	#[test]
+	#[cfg(feature = "json")]
+	fn parse_color_command() {
+		let command =
+			LightCommand::parse(br#"{"state":"ON","color":{"r":255,"g":64,"b":0},"effect":"colorloop"}"#)
+				.expect("should parse");
+
+		let mut color = LightColorState::default();
+		color.red(255).green(64).blue(0);
+		assert_eq!(
+			command.commands(),
+			[
+				LightSubCommand::On,
+				LightSubCommand::SetColor(color),
+				LightSubCommand::SetEffect(HassStr::from("colorloop")),
+			]
+		);
+		assert_eq!(command.brightness(), None);
+	}
+
+	#[test]
+	#[cfg(all(feature = "ser", feature = "json"))]
+	fn rgbww_channels_serialize_to_their_own_keys() {
+		let mut state = LightState::new(OnOff::On);
+		state.color_rgbww(1, 2, 3, 4, 5);
+		let color = state.color.expect("should set color");
+
+		let json = serde_json::to_value(&color).unwrap();
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"red": 1,
+				"green": 2,
+				"blue": 3,
+				"cold_white": 4,
+				"warm_white": 5,
+			})
+		);
+		assert_eq!(
+			serde_json::from_value::<LightColorState>(json).unwrap(),
+			color
+		);
+	}
+
+	#[test]
+	#[cfg(feature = "json")]
+	fn from_discovery_json() {
+		let json = r#"{
+			"availability": [{ "topic": "zigbee2mqtt/bridge/state" }],
+			"brightness": true,
+			"brightness_scale": 254,
+			"color_mode": true,
+			"command_topic": "zigbee2mqtt/Living room lamp/set",
+			"device": {
+				"identifiers": ["zigbee2mqtt_0x00178801099ab123"],
+				"manufacturer": "Philips",
+				"model": "Hue White and color ambiance E26/E27 (9290022166)",
+				"name": "Living room lamp",
+				"sw_version": "1.93.11"
+			},
+			"effect": true,
+			"effect_list": ["blink", "breathe", "okay"],
+			"json_attributes_topic": "zigbee2mqtt/Living room lamp",
+			"max_mireds": 500,
+			"min_mireds": 153,
+			"name": null,
+			"origin": { "name": "Zigbee2MQTT", "sw": "1.35.1" },
+			"schema": "json",
+			"state_topic": "zigbee2mqtt/Living room lamp",
+			"supported_color_modes": ["xy", "color_temp"],
+			"unique_id": "0x00178801099ab123_light_zigbee2mqtt"
+		}"#;
+
+		let light = Light::from_discovery_json(json).expect("should parse");
+		assert_eq!(&*light.command_topic, "zigbee2mqtt/Living room lamp/set");
+		assert_eq!(
+			light.state_topic.as_deref(),
+			Some("zigbee2mqtt/Living room lamp")
+		);
+		assert_eq!(light.brightness_scale, Some(254));
+		assert_eq!(
+			light.supported_color_modes,
+			ColorMode::XY | ColorMode::ColorTemp
+		);
+		assert_eq!(light.availability.len(), 1);
+		assert_eq!(
+			&*light.device.expect("should have device").identifiers,
+			&[HassStr::from("zigbee2mqtt_0x00178801099ab123")]
+		);
+		assert_eq!(
+			light.unique_id.as_deref(),
+			Some("0x00178801099ab123_light_zigbee2mqtt")
+		);
+	}
+
+	#[test]
+	#[cfg(feature = "json")]
+	fn from_abbreviated_discovery_json() {
+		let json = r#"{
+			"schema": "json",
+			"name": "Desk",
+			"uniq_id": "desk_light",
+			"cmd_t": "office/desk/set",
+			"stat_t": "office/desk/state",
+			"brightness": true,
+			"avty": [{ "t": "office/status", "pl_avail": "up" }],
+			"dev": { "ids": ["desk"], "mf": "Acme" }
+		}"#;
+
+		let light = Light::from_discovery_json(json).expect("should parse");
+		assert_eq!(&*light.command_topic, "office/desk/set");
+		assert_eq!(light.state_topic.as_deref(), Some("office/desk/state"));
+		assert_eq!(light.unique_id.as_deref(), Some("desk_light"));
+		assert_eq!(&*light.availability[0].topic, "office/status");
+		let device = light.device.expect("should have device");
+		assert_eq!(device.manufacturer.as_deref(), Some("Acme"));
+	}
+
+	#[test]
+	#[cfg(feature = "json")]
+	fn from_discovery_json_requires_json_schema() {
+		use crate::json::DiscoveryJsonError;
+
+		let err = Light::from_discovery_json(r#"{ "command_topic": "light/set" }"#)
+			.expect_err("default schema should be rejected");
+		assert!(matches!(
+			err,
+			DiscoveryJsonError::Mismatch {
+				field: "schema",
+				actual: None,
+				..
+			}
+		));
+
+		let err =
+			Light::from_discovery_json(r#"{ "schema": "template", "command_topic": "light/set" }"#)
+				.expect_err("template schema should be rejected");
+		assert!(matches!(
+			err,
+			DiscoveryJsonError::Mismatch {
+				actual: Some(ref schema),
+				..
+			} if schema == "template"
+		));
+	}
+
+	#[test]
+	#[cfg(feature = "alloc")]
+	fn zero_scales_are_invalid() {
+		let err: Vec<_> = Light::new("office/desk/set")
+			.brightness_scale(0)
+			.white_value_scale(0)
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(
+			&*err,
+			&[
+				LightInvalidity::BrightnessScaleZero,
+				LightInvalidity::WhiteValueScaleZero
+			]
+		);
+	}
+
+	#[test]
+	fn full_scales_are_valid() {
+		Light::new("office/desk/set")
+			.brightness_scale(255)
+			.white_value_scale(255)
+			.validate()
+			.expect("should be valid");
+	}
+
+	#[test]
+	#[cfg(feature = "alloc")]
+	fn color_mode_without_supported_color_modes_is_invalid() {
+		let err: Vec<_> = Light::new("office/desk/set")
+			.color_mode(true)
+			.validated()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+		assert_eq!(
+			&*err,
+			&[LightInvalidity::ColorModeWithoutSupportedColorModes]
+		);
+
+		let light = Light::new("office/desk/set")
+			.color_mode(true)
+			.supported_color_modes(ColorMode::ColorTemp)
+			.validated()
+			.expect("should be valid");
+		assert_eq!(light.supported_color_modes, ColorMode::ColorTemp);
+	}
+
+	#[test]
+	#[cfg(feature = "alloc")]
+	fn inverted_mired_range_is_invalid() {
+		let err: Vec<_> = Light::new("office/desk/set")
+			.min_mireds(500u16)
+			.max_mireds(153u16)
+			.color_mode(true)
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(
+			&*err,
+			&[
+				LightInvalidity::ColorModeWithoutSupportedColorModes,
+				LightInvalidity::MiredRangeInverted
+			]
+		);
+	}
+
+	#[test]
+	fn mired_range_is_valid() {
+		Light::new("office/desk/set")
+			.min_mireds(153u16)
+			.max_mireds(500u16)
+			.validate()
+			.expect("should be valid");
+
+		Light::new("office/desk/set")
+			.min_mireds(500u16)
+			.validate()
+			.expect("should be valid");
+	}
+
+	#[test]
+	#[cfg(all(feature = "json", feature = "ser"))]
+	fn compact_omits_home_assistant_defaults() {
+		let light = Light::new("office/desk/set")
+			.brightness_scale(255u8)
+			.flash_time_short(5u8);
+
+		let value = serde_json::to_value(&light).expect("should serialize");
+		assert_eq!(value["brightness_scale"], serde_json::json!(255));
+
+		let value = serde_json::to_value(light.compact()).expect("should serialize");
+		assert_eq!(value.get("brightness_scale"), None);
+		assert_eq!(value["flash_time_short"], serde_json::json!(5));
+	}
+
+	#[test]
+	#[cfg(all(feature = "json", feature = "ser"))]
+	fn supported_color_modes_serialize_in_declaration_order() {
+		let light = Light::new("office/desk/set").supported_color_modes(
+			ColorMode::White | ColorMode::RedGreenBlueWhite | ColorMode::RedGreenBlue,
+		);
+
+		let value = serde_json::to_value(&light).expect("should serialize");
+		assert_eq!(
+			value["supported_color_modes"],
+			serde_json::json!(["rgb", "rgbw", "white"])
+		);
+	}
+
+	#[test]
+	#[cfg(all(feature = "extra-fields", feature = "ser"))]
+	fn unknown_fields_survive_round_trip() {
+		let json = r#"{
+			"schema": "json",
+			"command_topic": "office/desk/set",
+			"unique_id": "desk_light",
+			"origin": { "name": "Zigbee2MQTT", "sw": "1.35.1" },
+			"future_field": 42
+		}"#;
+
+		let light = Light::from_discovery_json(json).expect("should parse");
+		assert_eq!(
+			light.extra.get("future_field"),
+			Some(&serde_json::json!(42))
+		);
+
+		let value = serde_json::to_value(&light).expect("should serialize");
+		assert_eq!(
+			value,
+			serde_json::json!({
+				"schema": "json",
+				"command_topic": "office/desk/set",
+				"unique_id": "desk_light",
+				"origin": { "name": "Zigbee2MQTT", "sw": "1.35.1" },
+				"future_field": 42
+			})
+		);
+
+		let json = serde_json::to_string(&light).expect("should serialize");
+		let reparsed = Light::from_discovery_json(&json).expect("should parse");
+		assert_eq!(reparsed, light);
+	}
+}
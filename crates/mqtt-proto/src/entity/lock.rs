@@ -0,0 +1,183 @@
+use crate::{payload::Payload, template::Template, topic::Topic, validation::Validator};
+use hass_mqtt_macros::entity_document;
+
+/// The mqtt lock platform lets you control your MQTT enabled locks.
+///
+/// See: <https://www.home-assistant.io/integrations/lock.mqtt/>
+#[entity_document]
+#[entity(validate(StateTopicMissing))]
+pub struct Lock<'a> {
+	/// The MQTT topic to publish commands to change the lock state.
+	#[serde(borrow, alias = "cmd_t")]
+	pub command_topic: Topic<'a>,
+
+	/// Flag that defines if the lock works in optimistic mode.
+	/// Defaults to `true` if no `state_topic` defined, else `false`.
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "opt")]
+	pub optimistic: Option<bool>,
+
+	/// The payload sent to the lock to lock it.
+	/// Defaults to `"LOCK"`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_lock"
+	)]
+	pub payload_lock: Option<Payload<'a>>,
+
+	/// The payload sent to the lock to open it. Only set this if the lock
+	/// supports being opened (for example unlatching the door).
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_open"
+	)]
+	pub payload_open: Option<Payload<'a>>,
+
+	/// The payload sent to the lock to unlock it.
+	/// Defaults to `"UNLOCK"`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_unlk"
+	)]
+	pub payload_unlock: Option<Payload<'a>>,
+
+	/// If the published message should have the retain flag on or not.
+	/// Defaults to `false`.
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "ret")]
+	#[entity(ha_default = "false")]
+	pub retain: Option<bool>,
+
+	/// The payload sent to `state_topic` by the lock when it's jammed.
+	/// Defaults to `"JAMMED"`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_jam"
+	)]
+	pub state_jammed: Option<Payload<'a>>,
+
+	/// The payload sent to `state_topic` by the lock when it's locked.
+	/// Defaults to `"LOCKED"`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_locked"
+	)]
+	pub state_locked: Option<Payload<'a>>,
+
+	/// The MQTT topic subscribed to receive state updates.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_t"
+	)]
+	pub state_topic: Option<Topic<'a>>,
+
+	/// The payload sent to `state_topic` by the lock when it's unlocked.
+	/// Defaults to `"UNLOCKED"`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_unlocked"
+	)]
+	pub state_unlocked: Option<Payload<'a>>,
+
+	/// Defines a [template][template] to extract a state value from the
+	/// payload received on `state_topic`.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "val_tpl"
+	)]
+	pub value_template: Option<Template<'a>>,
+}
+
+impl<'a> Validator for Lock<'a> {
+	type Invalidity = LockInvalidity;
+
+	fn validate_value(
+		&self,
+		value: &Self,
+		context: semval::context::Context<Self::Invalidity>,
+	) -> semval::context::Context<Self::Invalidity> {
+		context.invalidate_if(
+			value.value_template.is_some() && value.state_topic.is_none(),
+			LockInvalidity::StateTopicMissing,
+		)
+	}
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "ser", feature = "de"))]
+mod tests {
+	use super::*;
+	use alloc::{string::ToString, vec::Vec};
+	use semval::Validate;
+
+	#[test]
+	fn round_trips_through_json() {
+		let lock = Lock::new("front_door/lock/set")
+			.state_topic("front_door/lock")
+			.payload_open("OPEN")
+			.state_jammed("MOTOR_JAMMED")
+			.value_template("{{ value_json.state }}");
+
+		let json = serde_json::to_value(&lock).expect("should serialize");
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"command_topic": "front_door/lock/set",
+				"payload_open": "OPEN",
+				"state_jammed": "MOTOR_JAMMED",
+				"state_topic": "front_door/lock",
+				"value_template": "{{ value_json.state }}",
+			})
+		);
+
+		let text = json.to_string();
+		let parsed: Lock = serde_json::from_str(&text).expect("should deserialize");
+		assert_eq!(parsed, lock);
+	}
+
+	#[test]
+	fn deserializes_abbreviated_keys() {
+		let lock: Lock = serde_json::from_str(
+			r#"{
+				"cmd_t": "front_door/lock/set",
+				"stat_t": "front_door/lock",
+				"pl_lock": "1",
+				"pl_unlk": "0"
+			}"#,
+		)
+		.expect("should deserialize");
+
+		assert_eq!(&*lock.command_topic, "front_door/lock/set");
+		assert_eq!(lock.state_topic.as_deref(), Some("front_door/lock"));
+		assert!(lock.payload_lock.is_some());
+		assert!(lock.payload_unlock.is_some());
+	}
+
+	#[test]
+	fn value_template_without_state_topic_is_invalid() {
+		let err: Vec<_> = Lock::new("front_door/lock/set")
+			.value_template("{{ value_json.state }}")
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(&*err, &[LockInvalidity::StateTopicMissing]);
+	}
+}
@@ -0,0 +1,236 @@
+use crate::{payload::Payload, template::Template, topic::Topic, validation::Validator, HassStr};
+use hass_mqtt_macros::entity_document;
+
+/// The mqtt number platform allows you to integrate devices that might expose
+/// configuration options through MQTT into Home Assistant as a number.
+///
+/// See: <https://www.home-assistant.io/integrations/number.mqtt/>
+#[entity_document(?Eq)]
+#[entity(validate(RangeInverted, StepNotPositive))]
+pub struct Number<'a> {
+	/// Defines a [template][template] to generate the payload to send to
+	/// `command_topic`.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#using-command-templates-with-mqtt
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "cmd_tpl"
+	)]
+	pub command_template: Option<Template<'a>>,
+
+	/// The MQTT topic to publish commands to change the number.
+	#[serde(borrow, alias = "cmd_t")]
+	pub command_topic: Topic<'a>,
+
+	/// Maximum value.
+	/// Defaults to `100`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub max: Option<f32>,
+
+	/// Minimum value.
+	/// Defaults to `1`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub min: Option<f32>,
+
+	/// Control how the number should be displayed in the UI.
+	/// Defaults to [NumberMode::Auto].
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[entity(ha_default = "NumberMode::Auto")]
+	pub mode: Option<NumberMode>,
+
+	/// Flag that defines if number works in optimistic mode.
+	/// Defaults to `true` if no `state_topic` defined, else `false`.
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "opt")]
+	pub optimistic: Option<bool>,
+
+	/// A special payload that resets the state to `None` when received on the
+	/// `state_topic`.
+	/// Defaults to `"None"`.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_rst"
+	)]
+	pub payload_reset: Option<Payload<'a>>,
+
+	/// If the published message should have the retain flag on or not.
+	/// Defaults to `false`.
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "ret")]
+	#[entity(ha_default = "false")]
+	pub retain: Option<bool>,
+
+	/// The MQTT topic subscribed to receive number values.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_t"
+	)]
+	pub state_topic: Option<Topic<'a>>,
+
+	/// Step value. Smallest value `0.001`.
+	/// Defaults to `1`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub step: Option<f32>,
+
+	/// Defines the unit of measurement of the number, if any.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "unit_of_meas"
+	)]
+	pub unit_of_measurement: Option<HassStr<'a>>,
+
+	/// Defines a [template][template] to extract the value.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "val_tpl"
+	)]
+	pub value_template: Option<Template<'a>>,
+}
+
+impl<'a> Validator for Number<'a> {
+	type Invalidity = NumberInvalidity;
+
+	fn validate_value(
+		&self,
+		value: &Self,
+		context: semval::context::Context<Self::Invalidity>,
+	) -> semval::context::Context<Self::Invalidity> {
+		context
+			.invalidate_if(
+				matches!(
+					(value.min, value.max),
+					(Some(min), Some(max)) if min > max
+				),
+				NumberInvalidity::RangeInverted,
+			)
+			.invalidate_if(
+				matches!(value.step, Some(step) if step <= 0.0),
+				NumberInvalidity::StepNotPositive,
+			)
+	}
+}
+
+/// How a [Number] is displayed in the Home Assistant UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "ser", derive(serde::Serialize))]
+#[cfg_attr(feature = "de", derive(serde::Deserialize))]
+pub enum NumberMode {
+	/// Let Home Assistant pick the display mode.
+	#[cfg_attr(any(feature = "ser", feature = "de"), serde(rename = "auto"))]
+	Auto,
+
+	/// Display the number as an input box.
+	#[cfg_attr(any(feature = "ser", feature = "de"), serde(rename = "box"))]
+	Box,
+
+	/// Display the number as a slider.
+	#[cfg_attr(any(feature = "ser", feature = "de"), serde(rename = "slider"))]
+	Slider,
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "ser", feature = "de"))]
+mod tests {
+	use super::*;
+	use alloc::{string::ToString, vec::Vec};
+	use assert_matches::assert_matches;
+	use semval::Validate;
+
+	#[test]
+	fn mode_serializes_to_lowercase_names() {
+		for (mode, name) in [
+			(NumberMode::Auto, "auto"),
+			(NumberMode::Box, "box"),
+			(NumberMode::Slider, "slider"),
+		] {
+			let json = serde_json::to_value(mode).expect("should serialize");
+			assert_eq!(json, serde_json::json!(name));
+			assert_eq!(
+				serde_json::from_value::<NumberMode>(json).expect("should deserialize"),
+				mode
+			);
+		}
+	}
+
+	#[test]
+	fn round_trips_through_json() {
+		let number = Number::new("heater/target/set")
+			.state_topic("heater/target")
+			.min(5.0)
+			.max(30.0)
+			.step(0.5)
+			.mode(NumberMode::Slider)
+			.unit_of_measurement("°C");
+
+		let json = serde_json::to_value(&number).expect("should serialize");
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"command_topic": "heater/target/set",
+				"max": 30.0,
+				"min": 5.0,
+				"mode": "slider",
+				"state_topic": "heater/target",
+				"step": 0.5,
+				"unit_of_measurement": "°C",
+			})
+		);
+
+		let text = json.to_string();
+		let parsed: Number = serde_json::from_str(&text).expect("should deserialize");
+		assert_eq!(parsed, number);
+	}
+
+	#[test]
+	fn deserialization_borrows() {
+		let json = r#"{"command_topic":"heater/target/set","unit_of_measurement":"W"}"#;
+		let number: Number = serde_json::from_str(json).expect("should parse");
+		assert_matches!(number.command_topic, Topic(HassStr::Borrowed(_)));
+		assert_matches!(number.unit_of_measurement, Some(HassStr::Borrowed(_)));
+	}
+
+	#[test]
+	fn inverted_range_is_invalid() {
+		let err: Vec<_> = Number::new("heater/target/set")
+			.min(30.0)
+			.max(5.0)
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(&*err, &[NumberInvalidity::RangeInverted]);
+	}
+
+	#[test]
+	fn non_positive_step_is_invalid() {
+		let err: Vec<_> = Number::new("heater/target/set")
+			.step(0.0)
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(&*err, &[NumberInvalidity::StepNotPositive]);
+	}
+
+	#[test]
+	fn equal_bounds_are_valid() {
+		Number::new("heater/target/set")
+			.min(20.0)
+			.max(20.0)
+			.step(1.0)
+			.validate()
+			.expect("should be valid");
+	}
+}
@@ -0,0 +1,124 @@
+use crate::{template::Template, topic::Topic, validation::Validator, HassItems, HassStr};
+use hass_mqtt_macros::entity_document;
+
+/// The mqtt select platform allows you to integrate devices that might expose
+/// configuration options through MQTT into Home Assistant as a select.
+///
+/// See: <https://www.home-assistant.io/integrations/select.mqtt/>
+#[entity_document]
+#[entity(validate(OptionsEmpty))]
+pub struct Select<'a> {
+	/// Defines a [template][template] to generate the payload to send to
+	/// `command_topic`.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#using-command-templates-with-mqtt
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "cmd_tpl"
+	)]
+	pub command_template: Option<Template<'a>>,
+
+	/// The MQTT topic to publish commands to change the selected option.
+	#[serde(borrow, alias = "cmd_t")]
+	pub command_topic: Topic<'a>,
+
+	/// Flag that defines if the select works in optimistic mode.
+	/// Defaults to `true` if no `state_topic` defined, else `false`.
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "opt")]
+	pub optimistic: Option<bool>,
+
+	/// List of options that can be selected. Must not be empty.
+	#[serde(borrow, alias = "ops")]
+	pub options: HassItems<'a, HassStr<'a>>,
+
+	/// If the published message should have the retain flag on or not.
+	/// Defaults to `false`.
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "ret")]
+	#[entity(ha_default = "false")]
+	pub retain: Option<bool>,
+
+	/// The MQTT topic subscribed to receive update of the selected option.
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_t"
+	)]
+	pub state_topic: Option<Topic<'a>>,
+
+	/// Defines a [template][template] to extract the value.
+	///
+	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "val_tpl"
+	)]
+	pub value_template: Option<Template<'a>>,
+}
+
+impl<'a> Validator for Select<'a> {
+	type Invalidity = SelectInvalidity;
+
+	fn validate_value(
+		&self,
+		value: &Self,
+		context: semval::context::Context<Self::Invalidity>,
+	) -> semval::context::Context<Self::Invalidity> {
+		context.invalidate_if(value.options.is_empty(), SelectInvalidity::OptionsEmpty)
+	}
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "ser", feature = "de"))]
+mod tests {
+	use super::*;
+	use alloc::{string::ToString, vec, vec::Vec};
+	use assert_matches::assert_matches;
+	use semval::Validate;
+
+	#[test]
+	fn options_serialize_as_json_array() {
+		let select = Select::new(
+			"washer/program/set",
+			alloc::vec![HassStr::from("cotton"), HassStr::from("wool")],
+		)
+		.state_topic("washer/program");
+
+		let json = serde_json::to_value(&select).expect("should serialize");
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"command_topic": "washer/program/set",
+				"options": ["cotton", "wool"],
+				"state_topic": "washer/program",
+			})
+		);
+
+		let text = json.to_string();
+		let parsed: Select = serde_json::from_str(&text).expect("should deserialize");
+		assert_eq!(parsed, select);
+	}
+
+	#[test]
+	fn deserialization_borrows() {
+		let json = r#"{"command_topic":"washer/program/set","options":["cotton","wool"]}"#;
+		let select: Select = serde_json::from_str(json).expect("should parse");
+		assert_matches!(select.command_topic, Topic(HassStr::Borrowed(_)));
+		assert!(select.options.iter().all(HassStr::is_borrowed));
+	}
+
+	#[test]
+	fn empty_options_are_invalid() {
+		let err: Vec<_> = Select::new("washer/program/set", HassItems::default())
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(&*err, &[SelectInvalidity::OptionsEmpty]);
+	}
+}
@@ -1,5 +1,6 @@
 use crate::{
-	device_class::DeviceClass, state_class::StateClass, template::Template, topic::Topic, HassStr,
+	device_class::DeviceClass, state_class::StateClass, template::Template, topic::Topic,
+	validation::Validator, HassStr,
 };
 use core::num::NonZeroU32;
 use hass_mqtt_macros::entity_document;
@@ -11,49 +12,345 @@ use hass_mqtt_macros::entity_document;
 ///
 /// See: <https://www.home-assistant.io/integrations/sensor.mqtt/>
 #[entity_document]
+#[entity(validate(
+	StateClassIncompatibleWithDeviceClass,
+	MissingUnitOfMeasurement,
+	UnitIncompatibleWithDeviceClass
+))]
 pub struct Sensor<'a> {
 	/// The [type/class][device_class] of the sensor to set
 	/// the icon in the frontend.
 	///
 	/// [device_class]: https://www.home-assistant.io/integrations/sensor/#device-class
-	#[serde(default, skip_serializing_if = "DeviceClass::is_none")]
+	#[serde(
+		default,
+		skip_serializing_if = "DeviceClass::is_none",
+		alias = "dev_cla"
+	)]
 	pub device_class: DeviceClass,
 
 	/// Defines the number of seconds after the value expires if it's not updated. After
 	/// expiry, the sensor’s state becomes `unavailable`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "exp_aft")]
 	pub expire_after: Option<NonZeroU32>,
 
 	/// Sends update events even if the value hasn’t changed. Useful if you want to have
 	/// meaningful value graphs in history. Defaults to `false`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "frc_upd")]
+	#[entity(ha_default = "false")]
 	pub force_update: Option<bool>,
 
 	/// Defines a [template][template] to extract the last_reset. Available variables: `entity_id`.
 	/// The `entity_id` can be used to reference the entity’s attributes.
 	///
 	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "lrst_val_tpl"
+	)]
 	pub last_reset_value_template: Option<Template<'a>>,
 
 	/// The [state_class][state_class] of the sensor.
 	///
 	/// [state_class]: https://developers.home-assistant.io/docs/core/entity/sensor#available-state-classes
-	#[serde(default, skip_serializing_if = "StateClass::is_none")]
+	#[serde(
+		default,
+		skip_serializing_if = "StateClass::is_none",
+		alias = "stat_cla"
+	)]
 	pub state_class: StateClass,
 
 	/// The MQTT topic subscribed to receive sensor values.
-	#[serde(borrow)]
+	#[serde(borrow, alias = "stat_t")]
 	pub state_topic: Topic<'a>,
 
 	/// Defines the units of measurement of the sensor, if any.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "unit_of_meas"
+	)]
 	pub unit_of_measurement: Option<HassStr<'a>>,
 
 	/// Defines a [template][template] to extract the value. Available variables: `entity_id`.
 	/// The `entity_id` can be used to reference the entity’s attributes.
 	///
 	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "val_tpl"
+	)]
 	pub value_template: Option<Template<'a>>,
 }
+
+/// The state classes Home Assistant supports for a device class, if it restricts them.
+/// Not setting a state class is always allowed.
+fn supported_state_classes(device_class: DeviceClass) -> Option<&'static [StateClass]> {
+	match device_class {
+		DeviceClass::Energy | DeviceClass::Gas => {
+			Some(&[StateClass::Total, StateClass::TotalIncreasing])
+		}
+		DeviceClass::Monetary => Some(&[StateClass::Total]),
+		DeviceClass::Battery
+		| DeviceClass::Current
+		| DeviceClass::Humidity
+		| DeviceClass::Power
+		| DeviceClass::PowerFactor
+		| DeviceClass::Temperature
+		| DeviceClass::Voltage => Some(&[StateClass::Measurement]),
+		DeviceClass::Date | DeviceClass::Timestamp => Some(&[]),
+		_ => None,
+	}
+}
+
+/// The units of measurement Home Assistant supports for a device class, if it restricts
+/// them, following `DEVICE_CLASS_UNITS` of the sensor integration. A `None` entry means
+/// the device class can be used without a unit.
+fn supported_units(device_class: DeviceClass) -> Option<&'static [Option<&'static str>]> {
+	const MICROGRAMS_PER_CUBIC_METER: Option<&str> = Some("µg/m³");
+	const PERCENTAGE: Option<&str> = Some("%");
+
+	match device_class {
+		DeviceClass::AirQualityIndex | DeviceClass::Date | DeviceClass::Timestamp => Some(&[None]),
+		DeviceClass::Battery | DeviceClass::Humidity => Some(&[PERCENTAGE]),
+		DeviceClass::CarbonDioxide | DeviceClass::CarbonMonoxide => Some(&[Some("ppm")]),
+		DeviceClass::Current => Some(&[Some("A"), Some("mA")]),
+		DeviceClass::Energy => Some(&[
+			Some("J"),
+			Some("kJ"),
+			Some("MJ"),
+			Some("GJ"),
+			Some("mWh"),
+			Some("Wh"),
+			Some("kWh"),
+			Some("MWh"),
+			Some("GWh"),
+			Some("TWh"),
+			Some("cal"),
+			Some("kcal"),
+			Some("Mcal"),
+			Some("Gcal"),
+		]),
+		DeviceClass::Gas => Some(&[Some("L"), Some("m³"), Some("ft³"), Some("CCF"), Some("MCF")]),
+		DeviceClass::Illuminance => Some(&[Some("lx")]),
+		DeviceClass::NitrogenDioxide
+		| DeviceClass::NitrogenMonoxide
+		| DeviceClass::NitrousOxide
+		| DeviceClass::Ozone
+		| DeviceClass::Pm1
+		| DeviceClass::Pm10
+		| DeviceClass::Pm25
+		| DeviceClass::SulphurDioxide => Some(&[MICROGRAMS_PER_CUBIC_METER]),
+		DeviceClass::Power => Some(&[
+			Some("mW"),
+			Some("W"),
+			Some("kW"),
+			Some("MW"),
+			Some("GW"),
+			Some("TW"),
+			Some("BTU/h"),
+		]),
+		DeviceClass::PowerFactor => Some(&[PERCENTAGE, None]),
+		DeviceClass::Pressure => Some(&[
+			Some("Pa"),
+			Some("kPa"),
+			Some("hPa"),
+			Some("mbar"),
+			Some("cbar"),
+			Some("bar"),
+			Some("mmHg"),
+			Some("inHg"),
+			Some("psi"),
+		]),
+		DeviceClass::SignalStrength => Some(&[Some("dB"), Some("dBm")]),
+		DeviceClass::Temperature => Some(&[Some("°C"), Some("°F"), Some("K")]),
+		DeviceClass::VolatileOrganicCompounds => Some(&[MICROGRAMS_PER_CUBIC_METER, Some("mg/m³")]),
+		DeviceClass::Voltage => Some(&[Some("µV"), Some("mV"), Some("V"), Some("kV"), Some("MV")]),
+		DeviceClass::None | DeviceClass::Monetary => None,
+	}
+}
+
+impl<'a> Sensor<'a> {
+	/// The variables available within [Sensor::last_reset_value_template].
+	pub const LAST_RESET_VALUE_TEMPLATE_VARS: &'static [&'static str] = &["entity_id"];
+
+	/// The variables available within [Sensor::value_template].
+	pub const VALUE_TEMPLATE_VARS: &'static [&'static str] = &["entity_id"];
+}
+
+impl<'a> Validator for Sensor<'a> {
+	type Invalidity = SensorInvalidity;
+
+	fn validate_value(
+		&self,
+		value: &Self,
+		context: semval::context::Context<Self::Invalidity>,
+	) -> semval::context::Context<Self::Invalidity> {
+		let state_classes = supported_state_classes(value.device_class);
+		let units = supported_units(value.device_class);
+		let unit = value.unit_of_measurement.as_ref().map(|unit| unit.as_str());
+
+		context
+			.invalidate_if(
+				matches!(
+					state_classes,
+					Some(state_classes) if !value.state_class.is_none() && !state_classes.contains(&value.state_class)
+				),
+				SensorInvalidity::StateClassIncompatibleWithDeviceClass,
+			)
+			.invalidate_if(
+				matches!(units, Some(units) if unit.is_none() && !units.contains(&None)),
+				SensorInvalidity::MissingUnitOfMeasurement,
+			)
+			.invalidate_if(
+				matches!(
+					(units, unit),
+					(Some(units), Some(unit)) if !units.contains(&Some(unit))
+				),
+				SensorInvalidity::UnitIncompatibleWithDeviceClass,
+			)
+	}
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "ser", feature = "de"))]
+mod tests {
+	use super::*;
+	use alloc::{vec, vec::Vec};
+	use semval::Validate;
+
+	#[test]
+	fn device_identifier_sets_device_identifiers() {
+		let sensor = Sensor::new("app/state")
+			.device_identifier("0xabcdef")
+			.device_identifier("serial-1234");
+
+		let json = serde_json::to_value(&sensor).expect("should serialize");
+		assert_eq!(
+			json["device"],
+			serde_json::json!({
+				"identifiers": ["0xabcdef", "serial-1234"],
+			})
+		);
+	}
+
+	#[test]
+	fn energy_sensor_is_valid() {
+		Sensor::new("meter/energy")
+			.device_class(DeviceClass::Energy)
+			.state_class(StateClass::TotalIncreasing)
+			.unit_of_measurement("kWh")
+			.validate()
+			.expect("should be valid");
+	}
+
+	#[test]
+	fn energy_measurement_sensor_is_invalid() {
+		let err: Vec<_> = Sensor::new("meter/energy")
+			.device_class(DeviceClass::Energy)
+			.state_class(StateClass::Measurement)
+			.unit_of_measurement("W")
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(
+			&*err,
+			&[
+				SensorInvalidity::StateClassIncompatibleWithDeviceClass,
+				SensorInvalidity::UnitIncompatibleWithDeviceClass
+			]
+		);
+	}
+
+	#[test]
+	fn energy_sensor_without_unit_is_invalid() {
+		let err: Vec<_> = Sensor::new("meter/energy")
+			.device_class(DeviceClass::Energy)
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(&*err, &[SensorInvalidity::MissingUnitOfMeasurement]);
+	}
+
+	#[test]
+	fn power_factor_sensor_with_or_without_unit_is_valid() {
+		Sensor::new("meter/power_factor")
+			.device_class(DeviceClass::PowerFactor)
+			.state_class(StateClass::Measurement)
+			.validate()
+			.expect("unitless power factor should be valid");
+
+		Sensor::new("meter/power_factor")
+			.device_class(DeviceClass::PowerFactor)
+			.state_class(StateClass::Measurement)
+			.unit_of_measurement("%")
+			.validate()
+			.expect("power factor in % should be valid");
+	}
+
+	#[test]
+	fn aqi_sensor_with_unit_is_invalid() {
+		let err: Vec<_> = Sensor::new("air/aqi")
+			.device_class(DeviceClass::AirQualityIndex)
+			.unit_of_measurement("%")
+			.validate()
+			.expect_err("should be invalid")
+			.into_iter()
+			.collect();
+
+		assert_eq!(&*err, &[SensorInvalidity::UnitIncompatibleWithDeviceClass]);
+	}
+
+	#[test]
+	fn timestamp_sensor_without_unit_or_state_class_is_valid() {
+		Sensor::new("app/last_boot")
+			.device_class(DeviceClass::Timestamp)
+			.validate()
+			.expect("should be valid");
+	}
+
+	#[cfg(feature = "pretty-json")]
+	#[test]
+	fn to_pretty_json() {
+		let sensor = Sensor::new("kitchen/temperature")
+			.device_class(DeviceClass::Temperature)
+			.state_class(StateClass::Measurement)
+			.unit_of_measurement("°C");
+
+		assert_eq!(
+			crate::Document::to_pretty_json(&sensor).expect("should serialize"),
+			r#"{
+  "device_class": "temperature",
+  "state_class": "measurement",
+  "state_topic": "kitchen/temperature",
+  "unit_of_measurement": "°C"
+}"#
+		);
+	}
+
+	#[cfg(feature = "pretty-json")]
+	#[test]
+	fn to_pretty_json_validates() {
+		let sensor = Sensor::new("meter/energy")
+			.device_class(DeviceClass::Energy)
+			.state_class(StateClass::Measurement)
+			.unit_of_measurement("kWh");
+
+		let err = crate::Document::to_pretty_json(&sensor).expect_err("should be invalid");
+		assert!(
+			err
+				.to_string()
+				.contains("StateClassIncompatibleWithDeviceClass"),
+			"{err}"
+		);
+	}
+}
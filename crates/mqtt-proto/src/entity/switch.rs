@@ -1,5 +1,6 @@
+use super::OnOff;
 use crate::{device_class::DeviceClass, payload::Payload, template::Template, topic::Topic};
-use hass_mqtt_macros::entity_document;
+use hass_mqtt_macros::{entity_document, state_document};
 
 /// The mqtt switch platform lets you control your MQTT enabled switches.
 ///
@@ -7,18 +8,22 @@ use hass_mqtt_macros::entity_document;
 #[entity_document]
 pub struct Switch<'a> {
 	/// The MQTT topic to publish commands to change the switch state.
-	#[serde(borrow)]
+	#[serde(borrow, alias = "cmd_t")]
 	pub command_topic: Topic<'a>,
 
 	/// The [type/class][device_class] of the switch to set the icon in the frontend.
 	///
 	/// [device_class]: https://www.home-assistant.io/integrations/switch/#device-class
-	#[serde(default, skip_serializing_if = "DeviceClass::is_none")]
+	#[serde(
+		default,
+		skip_serializing_if = "DeviceClass::is_none",
+		alias = "dev_cla"
+	)]
 	pub device_class: DeviceClass,
 
 	/// Flag that defines if switch works in optimistic mode.
 	/// Defaults to `true` if no `state_topic` defined, else `false`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "opt")]
 	pub optimistic: Option<bool>,
 
 	/// The payload that represents `off` state. If specified, will be
@@ -26,7 +31,12 @@ pub struct Switch<'a> {
 	/// `value_template` and `state_off` for details) and sending as
 	/// `off` command to the `command_topic`.
 	/// Defaults to `"OFF"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_off"
+	)]
 	pub payload_off: Option<Payload<'a>>,
 
 	/// The payload that represents `on` state. If specified, will be
@@ -34,30 +44,51 @@ pub struct Switch<'a> {
 	/// `value_template` and `state_on` for details) and sending as
 	/// `on` command to the `command_topic`.
 	/// Defaults to `"ON"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "pl_on"
+	)]
 	pub payload_on: Option<Payload<'a>>,
 
 	/// If the published message should have the retain flag on or not.
 	/// Defaults to `false`.
-	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[serde(default, skip_serializing_if = "Option::is_none", alias = "ret")]
+	#[entity(ha_default = "false")]
 	pub retain: Option<bool>,
 
 	/// The payload that represents the `off` state. Used when value that
 	/// represents `off` state in the `state_topic` is different from value that
 	/// should be sent to the `command_topic` to turn the device `off`.
 	/// Defaults to `payload_off` if defined, else `"OFF"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_off"
+	)]
 	pub state_off: Option<Payload<'a>>,
 
 	/// The payload that represents the `on` state. Used when value that
 	/// represents on state in the `state_topic` is different from value that
 	/// should be sent to the `command_topic` to turn the device `on`.
 	/// Defaults to `payload_on` if defined, else `"ON"`.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_on"
+	)]
 	pub state_on: Option<Payload<'a>>,
 
 	/// The MQTT topic subscribed to receive state updates.
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "stat_t"
+	)]
 	pub state_topic: Option<Topic<'a>>,
 
 	/// Defines a [template][template] to extract device’s state from the
@@ -65,6 +96,65 @@ pub struct Switch<'a> {
 	/// template will be compared to `state_on` and `state_off`.
 	///
 	/// [template]: https://www.home-assistant.io/docs/configuration/templating/#processing-incoming-data
-	#[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+	#[serde(
+		borrow,
+		default,
+		skip_serializing_if = "Option::is_none",
+		alias = "val_tpl"
+	)]
 	pub value_template: Option<Template<'a>>,
 }
+
+/// The state of a switch, for switches that publish their state as JSON.
+///
+/// Since [OnOff] converts from `bool`, `SwitchState::new(true)` creates the
+/// state of a switch that is on.
+#[state_document]
+pub struct SwitchState {
+	/// Additional attributes published alongside the state, for switches that
+	/// use the state topic as their `json_attributes_topic`.
+	#[cfg(feature = "extra-fields")]
+	#[serde(flatten, default)]
+	#[state(builder = false)]
+	pub attributes: crate::json::ExtraFields,
+
+	/// Current switch state.
+	pub state: OnOff,
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "ser", feature = "de"))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn state_serializes_to_on_off() {
+		let json = serde_json::to_value(SwitchState::new(true)).expect("should serialize");
+		assert_eq!(json, serde_json::json!({ "state": "ON" }));
+
+		let json = serde_json::to_value(SwitchState::new(false)).expect("should serialize");
+		assert_eq!(json, serde_json::json!({ "state": "OFF" }));
+	}
+
+	#[test]
+	fn state_deserializes_from_on_off() {
+		let state: SwitchState = serde_json::from_str(r#"{"state":"ON"}"#).expect("should parse");
+		assert_eq!(state, SwitchState::new(OnOff::On));
+	}
+
+	#[test]
+	#[cfg(feature = "extra-fields")]
+	fn attributes_are_flattened_into_the_state() {
+		let mut state = SwitchState::new(true);
+		state
+			.attributes
+			.insert("power".into(), serde_json::json!(12.5));
+
+		let json = serde_json::to_value(&state).expect("should serialize");
+		assert_eq!(json, serde_json::json!({ "state": "ON", "power": 12.5 }));
+		assert_eq!(
+			serde_json::from_value::<SwitchState>(json).expect("should parse"),
+			state
+		);
+	}
+}
@@ -0,0 +1,379 @@
+//! Parsing of discovery documents from JSON.
+
+use alloc::string::{String, ToString};
+use core::fmt;
+#[cfg(feature = "extra-fields")]
+use {
+	alloc::collections::BTreeMap,
+	core::ops::{Deref, DerefMut},
+	serde_json::Value,
+};
+
+/// The error returned when parsing a discovery document fails.
+#[derive(Debug)]
+pub enum DiscoveryJsonError {
+	/// The document is not valid JSON, or doesn't match the document type.
+	Parse(serde_json::Error),
+
+	/// A field identifying the kind of document (like the `schema` of lights) is missing,
+	/// or doesn't have the value expected by the document type.
+	Mismatch {
+		field: &'static str,
+		expected: &'static str,
+		actual: Option<String>,
+	},
+}
+
+impl fmt::Display for DiscoveryJsonError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Parse(_) => f.write_str("failed to parse discovery document"),
+			Self::Mismatch {
+				field,
+				expected,
+				actual: None,
+			} => write!(
+				f,
+				"expected '{field}' to be '{expected}', but it is missing"
+			),
+			Self::Mismatch {
+				field,
+				expected,
+				actual: Some(actual),
+			} => write!(
+				f,
+				"expected '{field}' to be '{expected}', but it is '{actual}'"
+			),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DiscoveryJsonError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Parse(source) => Some(source),
+			Self::Mismatch { .. } => None,
+		}
+	}
+}
+
+pub(crate) fn check_discriminator(
+	field: &'static str,
+	expected: &'static str,
+	actual: Option<&str>,
+) -> Result<(), DiscoveryJsonError> {
+	match actual {
+		Some(actual) if actual == expected => Ok(()),
+		actual => Err(DiscoveryJsonError::Mismatch {
+			field,
+			expected,
+			actual: actual.map(ToString::to_string),
+		}),
+	}
+}
+
+/// Fields of a discovery document that are not known by the document type.
+///
+/// They are kept when a document is deserialized, and written back when it's serialized,
+/// so documents can be round-tripped without losing fields added by newer versions of
+/// Home Assistant (or by other integrations). This is opt-in, through the `extra-fields`
+/// feature.
+#[cfg(feature = "extra-fields")]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct ExtraFields(pub BTreeMap<String, Value>);
+
+// json numbers are never NaN, so equality is reflexive
+#[cfg(feature = "extra-fields")]
+impl Eq for ExtraFields {}
+
+#[cfg(feature = "extra-fields")]
+impl ExtraFields {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Used by the serialization of documents, which write constant json properties (like
+	/// the `schema` of lights) themselves.
+	#[doc(hidden)]
+	pub fn serialize_without<S>(&self, skip: &[&str], serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		serializer.collect_map(
+			self
+				.0
+				.iter()
+				.filter(|(key, _)| !skip.contains(&key.as_str())),
+		)
+	}
+}
+
+#[cfg(feature = "extra-fields")]
+impl Deref for ExtraFields {
+	type Target = BTreeMap<String, Value>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+#[cfg(feature = "extra-fields")]
+impl DerefMut for ExtraFields {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.0
+	}
+}
+
+#[cfg(feature = "extra-fields")]
+impl From<BTreeMap<String, Value>> for ExtraFields {
+	fn from(value: BTreeMap<String, Value>) -> Self {
+		Self(value)
+	}
+}
+
+#[cfg(test)]
+#[cfg(feature = "ser")]
+mod tests {
+	use super::*;
+	use crate::{
+		entity::{BinarySensor, Button, Cover, DeviceTracker, Sensor, Switch},
+		DeviceTrigger,
+	};
+	use alloc::vec;
+	use serde_json::json;
+
+	/// Parses a document with abbreviated keys, and checks that it serializes to `expected`,
+	/// which uses the full keys.
+	fn assert_expands<'a, T: serde::Serialize>(
+		parse: fn(&'a str) -> Result<T, DiscoveryJsonError>,
+		abbreviated: &'a str,
+		expected: serde_json::Value,
+	) {
+		let doc = parse(abbreviated).expect("should parse");
+		assert_eq!(
+			serde_json::to_value(&doc).expect("should serialize"),
+			expected
+		);
+	}
+
+	#[test]
+	fn sensor_abbreviations() {
+		assert_expands(
+			Sensor::from_discovery_json,
+			r#"{
+				"uniq_id": "meter_power",
+				"stat_t": "meter/state",
+				"dev_cla": "power",
+				"stat_cla": "measurement",
+				"unit_of_meas": "W",
+				"val_tpl": "{{ value_json.power }}",
+				"exp_aft": 60,
+				"frc_upd": true,
+				"lrst_val_tpl": "{{ value_json.last_reset }}"
+			}"#,
+			json!({
+				"unique_id": "meter_power",
+				"state_topic": "meter/state",
+				"device_class": "power",
+				"state_class": "measurement",
+				"unit_of_measurement": "W",
+				"value_template": "{{ value_json.power }}",
+				"expire_after": 60,
+				"force_update": true,
+				"last_reset_value_template": "{{ value_json.last_reset }}"
+			}),
+		);
+	}
+
+	#[test]
+	fn binary_sensor_abbreviations() {
+		assert_expands(
+			BinarySensor::from_discovery_json,
+			r#"{
+				"stat_t": "remote/battery",
+				"dev_cla": "battery",
+				"exp_aft": 30,
+				"frc_upd": false,
+				"off_dly": 5,
+				"pl_on": "low",
+				"pl_off": "ok",
+				"val_tpl": "{{ value_json.battery }}"
+			}"#,
+			json!({
+				"state_topic": "remote/battery",
+				"device_class": "battery",
+				"expire_after": 30,
+				"force_update": false,
+				"off_delay": 5,
+				"payload_on": "low",
+				"payload_off": "ok",
+				"value_template": "{{ value_json.battery }}"
+			}),
+		);
+	}
+
+	#[test]
+	fn button_abbreviations() {
+		assert_expands(
+			Button::from_discovery_json,
+			r#"{
+				"cmd_t": "doorbell/press",
+				"cmd_tpl": "{{ value }}",
+				"pl_prs": "RING",
+				"ret": false
+			}"#,
+			json!({
+				"command_topic": "doorbell/press",
+				"command_template": "{{ value }}",
+				"payload_press": "RING",
+				"retain": false
+			}),
+		);
+	}
+
+	#[test]
+	fn cover_abbreviations() {
+		assert_expands(
+			Cover::from_discovery_json,
+			r#"{
+				"cmd_t": "blinds/set",
+				"opt": false,
+				"pl_open": "OPEN",
+				"pl_cls": "CLOSE",
+				"pl_stop": "STOP",
+				"pos_clsd": 0,
+				"pos_open": 100,
+				"pos_t": "blinds/position",
+				"pos_tpl": "{{ value_json.position }}",
+				"set_pos_t": "blinds/position/set",
+				"set_pos_tpl": "{{ position }}",
+				"ret": true,
+				"stat_t": "blinds/state",
+				"stat_open": "open",
+				"stat_opening": "opening",
+				"stat_clsd": "closed",
+				"stat_closing": "closing",
+				"stat_stopped": "stopped",
+				"tilt_cmd_t": "blinds/tilt/set",
+				"tilt_cmd_tpl": "{{ tilt_position }}",
+				"tilt_status_t": "blinds/tilt",
+				"tilt_status_tpl": "{{ value_json.tilt }}",
+				"tilt_min": 0,
+				"tilt_max": 100,
+				"tilt_clsd_val": 0,
+				"tilt_opnd_val": 100,
+				"val_tpl": "{{ value_json.state }}"
+			}"#,
+			json!({
+				"command_topic": "blinds/set",
+				"optimistic": false,
+				"payload_open": "OPEN",
+				"payload_close": "CLOSE",
+				"payload_stop": "STOP",
+				"position_closed": 0,
+				"position_open": 100,
+				"position_topic": "blinds/position",
+				"position_template": "{{ value_json.position }}",
+				"set_position_topic": "blinds/position/set",
+				"set_position_template": "{{ position }}",
+				"retain": true,
+				"state_topic": "blinds/state",
+				"state_open": "open",
+				"state_opening": "opening",
+				"state_closed": "closed",
+				"state_closing": "closing",
+				"state_stopped": "stopped",
+				"tilt_command_topic": "blinds/tilt/set",
+				"tilt_command_template": "{{ tilt_position }}",
+				"tilt_status_topic": "blinds/tilt",
+				"tilt_status_template": "{{ value_json.tilt }}",
+				"tilt_min": 0,
+				"tilt_max": 100,
+				"tilt_closed_value": 0,
+				"tilt_opened_value": 100,
+				"value_template": "{{ value_json.state }}"
+			}),
+		);
+	}
+
+	#[test]
+	fn device_tracker_abbreviations() {
+		assert_expands(
+			DeviceTracker::from_discovery_json,
+			r#"{
+				"stat_t": "phone/presence",
+				"pl_home": "home",
+				"pl_not_home": "away",
+				"src_type": "router",
+				"val_tpl": "{{ value_json.presence }}"
+			}"#,
+			json!({
+				"state_topic": "phone/presence",
+				"payload_home": "home",
+				"payload_not_home": "away",
+				"source_type": "router",
+				"value_template": "{{ value_json.presence }}"
+			}),
+		);
+	}
+
+	#[test]
+	fn switch_abbreviations() {
+		assert_expands(
+			Switch::from_discovery_json,
+			r#"{
+				"cmd_t": "plug/set",
+				"stat_t": "plug/state",
+				"dev_cla": "power",
+				"opt": false,
+				"pl_on": "1",
+				"pl_off": "0",
+				"stat_on": "1",
+				"stat_off": "0",
+				"ret": true,
+				"val_tpl": "{{ value_json.state }}"
+			}"#,
+			json!({
+				"command_topic": "plug/set",
+				"state_topic": "plug/state",
+				"device_class": "power",
+				"optimistic": false,
+				"payload_on": "1",
+				"payload_off": "0",
+				"state_on": "1",
+				"state_off": "0",
+				"retain": true,
+				"value_template": "{{ value_json.state }}"
+			}),
+		);
+	}
+
+	#[test]
+	fn device_trigger_abbreviations() {
+		assert_expands(
+			DeviceTrigger::from_discovery_json,
+			r#"{
+				"atype": "trigger",
+				"dev": { "ids": ["remote"] },
+				"pl": "press",
+				"qos": 1,
+				"stype": "button_1",
+				"t": "remote/action",
+				"type": "button_short_press",
+				"val_tpl": "{{ value_json.action }}"
+			}"#,
+			json!({
+				"automation_type": "trigger",
+				"device": { "identifiers": ["remote"] },
+				"payload": "press",
+				"qos": 1,
+				"subtype": "button_1",
+				"topic": "remote/action",
+				"type": "button_short_press",
+				"value_template": "{{ value_json.action }}"
+			}),
+		);
+	}
+}
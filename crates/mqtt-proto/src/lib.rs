@@ -13,9 +13,12 @@ pub mod availability;
 pub mod device;
 pub mod device_class;
 pub mod device_tracker_source_type;
+pub mod device_trigger;
 pub mod entity;
 pub mod entity_category;
 pub mod icon;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod name;
 pub mod payload;
 pub mod qos;
@@ -27,6 +30,9 @@ pub mod unique_id;
 
 #[doc(no_inline)]
 pub use availability::Availability;
+#[cfg(feature = "alloc")]
+#[doc(no_inline)]
+pub use availability::AvailabilityList;
 #[doc(no_inline)]
 pub use device::Device;
 #[doc(no_inline)]
@@ -34,7 +40,12 @@ pub use device_class::DeviceClass;
 #[doc(no_inline)]
 pub use device_tracker_source_type::DeviceTrackerSourceType;
 #[doc(no_inline)]
-pub use entity::{BinarySensor, Button, Cover, DeviceTracker, Light, Sensor, Switch};
+pub use device_trigger::DeviceTrigger;
+#[doc(no_inline)]
+pub use entity::{
+	BinarySensor, Button, Climate, Cover, DeviceTracker, Fan, Light, Lock, Number, Select, Sensor,
+	Switch,
+};
 #[doc(no_inline)]
 pub use entity_category::EntityCategory;
 #[doc(no_inline)]
@@ -74,6 +74,33 @@ impl<'a, T> HassItems<'a, T> {
 		s
 	}
 
+	/// Turns the items into a [Vec] (unless they already are one), and returns it.
+	#[cfg(feature = "alloc")]
+	fn make_vec(&mut self) -> &mut Vec<T>
+	where
+		T: Clone,
+	{
+		if !self.is_vec() {
+			*self = Self::Vec(match core::mem::replace(self, Self::Borrowed(&[])) {
+				Self::Borrowed(s) => s.to_vec(),
+				Self::Arc(s) => s.to_vec(),
+				Self::Vec(_) => unreachable!(),
+			});
+		}
+
+		let Self::Vec(s) = self else { unreachable!() };
+		s
+	}
+
+	/// Appends an item, turning the items into a [Vec] first unless they already are one.
+	#[cfg(feature = "alloc")]
+	pub fn push(&mut self, item: T)
+	where
+		T: Clone,
+	{
+		self.make_vec().push(item);
+	}
+
 	pub fn iter(&self) -> core::slice::Iter<T> {
 		self.as_slice().iter()
 	}
@@ -189,6 +216,14 @@ impl<'a, T> FromIterator<T> for HassItems<'a, T> {
 	}
 }
 
+/// Appends the items, turning the items into a [Vec] first unless they already are one.
+#[cfg(feature = "alloc")]
+impl<'a, T: Clone> Extend<T> for HassItems<'a, T> {
+	fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+		self.make_vec().extend(iter);
+	}
+}
+
 #[cfg(feature = "ser")]
 impl<'a, T: serde::Serialize> serde::Serialize for HassItems<'a, T> {
 	#[inline]
@@ -216,3 +251,34 @@ impl<'a, 'de: 'a, T: serde::Deserialize<'de>> serde::Deserialize<'de> for HassIt
 		<Vec<T> as serde::Deserialize>::deserialize(deserializer).map(|vec| Self::Arc(Arc::from(vec)))
 	}
 }
+
+#[cfg(test)]
+#[cfg(feature = "alloc")]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn push_turns_borrowed_into_vec() {
+		let mut items = HassItems::<u32>::default();
+		assert!(items.is_borrowed());
+
+		items.push(1);
+		items.push(2);
+		assert!(items.is_vec());
+		assert_eq!(items.as_slice(), [1, 2]);
+	}
+
+	#[test]
+	fn extend_preserves_order() {
+		let mut items = HassItems::Borrowed(&["a", "b"][..]);
+		items.extend(["c", "d"]);
+		items.push("e");
+		assert!(items.is_vec());
+		assert_eq!(items.as_slice(), ["a", "b", "c", "d", "e"]);
+
+		let mut items = HassItems::from(Arc::<[u32]>::from(&[1, 2][..]));
+		items.extend([3]);
+		assert!(items.is_vec());
+		assert_eq!(items.as_slice(), [1, 2, 3]);
+	}
+}
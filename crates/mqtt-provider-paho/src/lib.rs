@@ -1,18 +1,24 @@
 use async_trait::async_trait;
 use futures::{future::LocalBoxFuture, pin_mut, stream::FusedStream, FutureExt, Stream, StreamExt};
 use hass_dyn_error::DynError;
+#[cfg(feature = "ssl")]
+use hass_mqtt_provider::MqttTlsConfig;
 use hass_mqtt_provider::{
-	AsMqttOptions, MqttBuildableMessage, MqttClient, MqttDisconnectBuilder, MqttMessage,
-	MqttMessageBuilder, MqttOptions, MqttProvider, MqttProviderCreateError, MqttPublishBuilder,
-	MqttReceivedMessage, MqttRetainHandling, MqttSubscribeBuilder, MqttUnsubscribeBuilder,
-	MqttVersion, QosLevel,
+	AsMqttOptions, MessageSpanSampler, MqttBuildableMessage, MqttClient, MqttConnectionEvent,
+	MqttDisconnectBuilder, MqttMessage, MqttMessageBuilder, MqttOptions, MqttProvider,
+	MqttProviderCreateError, MqttPublishBuilder, MqttReceivedMessage, MqttRetainHandling,
+	MqttSubscribeBuilder, MqttTransport, MqttUnsubscribeBuilder, MqttVersion, QosLevel, ServerInfo,
+	Subscribed,
 };
 use opentelemetry::{trace::SpanContext, trace::TraceContextExt};
 use pin_project::pin_project;
 use std::{
-	cell::RefCell,
+	cell::{Cell, RefCell},
+	collections::HashMap,
 	convert::Infallible,
+	fmt,
 	future::{ready, IntoFuture},
+	net::SocketAddr,
 	pin::Pin,
 	sync::Arc,
 	task::{Context, Poll},
@@ -42,6 +48,10 @@ hass_metrics::metrics! {
 			"Number of messages received from the broker",
 			("topic": String),
 		),
+		invalid_topic: Counter(
+			"hass.mqtt.provider_paho.invalid_topic",
+			"Number of messages skipped because their topic is not valid UTF-8",
+		),
 		publish: Counter(
 			"hass.mqtt.provider_paho.publish",
 			"Number of messages published to the broker",
@@ -60,6 +70,146 @@ hass_metrics::metrics! {
 	}
 }
 
+/// Checks that the topic of a received message is valid UTF-8, since
+/// [paho_mqtt::Message::topic] panics otherwise. paho doesn't expose the raw topic, but
+/// formatting the message fails (instead of panicking) before writing anything when the
+/// topic is invalid.
+fn has_utf8_topic(message: &paho_mqtt::Message) -> bool {
+	struct FirstWrite(bool);
+
+	impl fmt::Write for FirstWrite {
+		fn write_str(&mut self, _: &str) -> fmt::Result {
+			self.0 = true;
+			// no need to format the rest of the message
+			Err(fmt::Error)
+		}
+	}
+
+	let mut writer = FirstWrite(false);
+	let _ = fmt::write(&mut writer, format_args!("{message}"));
+	writer.0
+}
+
+/// Reads the server properties from the properties of the `CONNACK`.
+fn server_info(properties: &paho_mqtt::Properties) -> ServerInfo {
+	use paho_mqtt::PropertyCode;
+
+	ServerInfo {
+		assigned_client_id: properties
+			.get_string(PropertyCode::AssignedClientIdentifer)
+			.map(Arc::from),
+		server_keep_alive: properties
+			.get_int(PropertyCode::ServerKeepAlive)
+			.and_then(|keep_alive| u16::try_from(keep_alive).ok()),
+		topic_alias_maximum: properties
+			.get_int(PropertyCode::TopicAliasMaximum)
+			.and_then(|max| u16::try_from(max).ok()),
+		retain_available: properties
+			.get_int(PropertyCode::RetainAvailable)
+			.map(|available| available != 0),
+	}
+}
+
+/// The MQTT5 topic aliases assigned to the topics the client publishes to. The broker
+/// forgets the aliases when the connection ends, so they're cleared whenever it does.
+/// The maximum comes from the CONNACK, which paho only hands out for the initial
+/// connection, so aliasing stays off after the connection is lost.
+#[derive(Default)]
+struct TopicAliases {
+	maximum: u16,
+	aliases: HashMap<String, u16>,
+}
+
+impl TopicAliases {
+	fn new(maximum: u16) -> Self {
+		Self {
+			maximum,
+			aliases: HashMap::new(),
+		}
+	}
+
+	fn clear(&mut self) {
+		self.aliases.clear();
+	}
+
+	/// Forgets the aliases and the maximum of the ended connection.
+	fn reset(&mut self) {
+		self.maximum = 0;
+		self.aliases.clear();
+	}
+
+	/// Adds a topic alias to `message`. The first publish to a topic assigns the alias
+	/// next to the full topic, and the ones after it only send the alias. Messages are
+	/// left as they are once all aliases the broker accepts are in use.
+	fn apply(&mut self, message: paho_mqtt::Message) -> paho_mqtt::Message {
+		// QoS 1 and 2 messages may be resent on a new connection that doesn't know the alias
+		if message.qos() != paho_mqtt::QOS_0 {
+			return message;
+		}
+
+		let (alias, topic) = match self.aliases.get(message.topic()) {
+			Some(alias) => (*alias, ""),
+			None if self.aliases.len() < usize::from(self.maximum) => {
+				let alias = self.aliases.len() as u16 + 1;
+				self.aliases.insert(message.topic().to_owned(), alias);
+				(alias, message.topic())
+			}
+			None => return message,
+		};
+
+		let mut properties = message.properties().clone();
+		properties
+			.push_int(paho_mqtt::PropertyCode::TopicAlias, alias.into())
+			.expect("topic alias is an integer property");
+
+		paho_mqtt::MessageBuilder::new()
+			.topic(topic)
+			.payload(message.payload())
+			.qos(message.qos())
+			.retained(message.retained())
+			.properties(properties)
+			.finalize()
+	}
+}
+
+/// Sets the retain flag of `message`.
+fn with_retain(message: paho_mqtt::Message, retain: bool) -> paho_mqtt::Message {
+	paho_mqtt::MessageBuilder::new()
+		.topic(message.topic())
+		.payload(message.payload())
+		.qos(message.qos())
+		.retained(retain)
+		.properties(message.properties().clone())
+		.finalize()
+}
+
+/// Whether the broker refused the connection because it doesn't support retained
+/// messages (MQTT 5), which it does when the last will is retained.
+fn is_retain_not_supported(error: &paho_mqtt::Error) -> bool {
+	let code = paho_mqtt::ReasonCode::RetainNotSupported as i32;
+	matches!(error, paho_mqtt::Error::Paho(rc) | paho_mqtt::Error::PahoDescr(rc, _) if *rc == code)
+}
+
+/// Adds the message expiry interval property to `message`, rounded up to whole seconds.
+fn with_message_expiry(message: paho_mqtt::Message, expiry: Duration) -> paho_mqtt::Message {
+	let secs = expiry.as_secs() + u64::from(expiry.subsec_nanos() > 0);
+	let mut properties = message.properties().clone();
+	properties
+		.push_u32(
+			paho_mqtt::PropertyCode::MessageExpiryInterval,
+			u32::try_from(secs).unwrap_or(u32::MAX),
+		)
+		.expect("message expiry interval is a four byte integer property");
+
+	paho_mqtt::MessageBuilder::new()
+		.topic(message.topic())
+		.payload(message.payload())
+		.qos(message.qos())
+		.retained(message.retained())
+		.properties(properties)
+		.finalize()
+}
+
 fn create_callback<F, Args, RetFut>(mut f: F) -> impl FnMut(Args) + Send + Sync
 where
 	F: FnMut(Args) -> RetFut + 'static,
@@ -110,6 +260,12 @@ pub enum PahoProviderConnectError {
 		#[cfg_attr(provide_any, backtrace)]
 		source: DynError,
 	},
+
+	#[error("invalid TLS options")]
+	Tls {
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
 }
 
 impl PahoProviderConnectError {
@@ -146,6 +302,13 @@ impl PahoProviderConnectError {
 			source: DynError::new(source),
 		}
 	}
+
+	#[cfg(feature = "ssl")]
+	fn tls(source: impl std::error::Error + Send + Sync + 'static) -> Self {
+		Self::Tls {
+			source: DynError::new(source),
+		}
+	}
 }
 
 impl MqttProviderCreateError for PahoProviderConnectError {
@@ -157,6 +320,93 @@ impl MqttProviderCreateError for PahoProviderConnectError {
 	}
 }
 
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum PahoPublishError {
+	#[error("failed to publish MQTT message to topic '{topic}'")]
+	Publish {
+		topic: String,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+}
+
+impl PahoPublishError {
+	fn publish(
+		topic: impl Into<String>,
+		source: impl std::error::Error + Send + Sync + 'static,
+	) -> Self {
+		Self::Publish {
+			topic: topic.into(),
+			source: DynError::new(source),
+		}
+	}
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum PahoSubscribeError {
+	#[error("already subscribed to MQTT topic '{topic}'")]
+	AlreadySubscribed { topic: Arc<str> },
+
+	#[error("failed to subscribe to MQTT topic '{topic}'")]
+	Subscribe {
+		topic: Arc<str>,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+}
+
+impl PahoSubscribeError {
+	fn subscribe(topic: Arc<str>, source: impl std::error::Error + Send + Sync + 'static) -> Self {
+		Self::Subscribe {
+			topic,
+			source: DynError::new(source),
+		}
+	}
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum PahoUnsubscribeError {
+	#[error("not subscribed to MQTT topic '{topic}'")]
+	NotSubscribed { topic: Arc<str> },
+
+	#[error("failed to unsubscribe from MQTT topic '{topic}'")]
+	Unsubscribe {
+		topic: Arc<str>,
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+}
+
+impl PahoUnsubscribeError {
+	fn unsubscribe(topic: Arc<str>, source: impl std::error::Error + Send + Sync + 'static) -> Self {
+		Self::Unsubscribe {
+			topic,
+			source: DynError::new(source),
+		}
+	}
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum PahoDisconnectError {
+	#[error("failed to disconnect from MQTT broker")]
+	Disconnect {
+		#[cfg_attr(provide_any, backtrace)]
+		source: DynError,
+	},
+}
+
+impl PahoDisconnectError {
+	fn disconnect(source: impl std::error::Error + Send + Sync + 'static) -> Self {
+		Self::Disconnect {
+			source: DynError::new(source),
+		}
+	}
+}
+
 pub struct PahoMqtt;
 
 #[async_trait(?Send)]
@@ -167,6 +417,12 @@ impl MqttProvider for PahoMqtt {
 	type Message = Message;
 	type Error = PahoProviderConnectError;
 
+	/// Creates a client and connects it to the broker.
+	///
+	/// This is cancellation safe: if the returned future is dropped before the client is
+	/// connected (or connecting fails), the callbacks registered on the paho client are
+	/// removed and the connection attempt is abandoned, so the tasks forwarding the
+	/// callbacks finish instead of lingering.
 	#[instrument(
 		level = Level::DEBUG,
 		name = "PahoMqtt::create",
@@ -189,13 +445,7 @@ impl MqttProvider for PahoMqtt {
 		let client = paho_mqtt::AsyncClient::new(as_create_options(&options, client_id)?)
 			.map_err(PahoProviderConnectError::client)?;
 
-		let mut builder = match &options.version {
-			MqttVersion::Default => paho_mqtt::ConnectOptionsBuilder::new(),
-			MqttVersion::V3 => paho_mqtt::ConnectOptionsBuilder::new_v3(),
-			MqttVersion::V5 => paho_mqtt::ConnectOptionsBuilder::new_v5(),
-		};
-
-		let hosts = lookup_host((&*options.host, options.port))
+		let broker_addrs = lookup_host((&*options.host, options.port))
 			.instrument(
 				span!(Level::DEBUG, "PahoMqtt::lookup_host", host = %options.host, port = options.port),
 			)
@@ -203,41 +453,42 @@ impl MqttProvider for PahoMqtt {
 			.map_err(|source| {
 				PahoProviderConnectError::resolve_host(&options.host, options.port, source)
 			})?
-			.map(|addr| format!("tcp://{addr}"))
+			.collect::<Vec<_>>();
+		let hosts = broker_addrs
+			.iter()
+			.map(|addr| server_uri(&options, addr))
 			.collect::<Vec<_>>();
 
-		builder
-			.server_uris(&hosts)
-			.automatic_reconnect(Duration::from_secs(5), Duration::from_secs(60 * 5));
-
-		#[cfg(feature = "tls")]
-		if options.tls {
-			builder.ssl_options(paho_mqtt::SslOptions::new());
-		}
-
-		if let Some(auth) = &options.auth {
-			builder.user_name(auth.username.clone());
-			builder.password(auth.password.clone());
-		}
+		let mut builder = as_connect_options(&options)?;
+		builder.server_uris(&hosts);
 
 		let span_cx = Span::current().context().span().span_context().clone();
 		let (message_sender, message_receiver) = flume::unbounded();
-		let inner = InnerClient::new(client.clone(), message_receiver);
+		let (event_sender, event_receiver) = flume::unbounded();
+		let inner = InnerClient::new(
+			client.clone(),
+			message_receiver,
+			event_receiver,
+			broker_addrs,
+			options.message_span_sampler.clone(),
+		);
 
-		builder.will_message(offline_message.message);
+		builder.will_message(offline_message.message.clone());
 
 		let mut connected_callback = create_callback({
 			let inner = inner.clone();
 			let span_cx = span_cx.clone();
+			let event_sender = event_sender.clone();
 			move |_: ()| {
 				Metrics::global().connected.add(1);
+				inner.topic_aliases.borrow_mut().clear();
+				let _ = event_sender.send(MqttConnectionEvent::Connected);
 				let client_id = inner.client.client_id();
 				let mqtt_version = inner.client.mqtt_version();
 				let span = span!(parent: None, Level::DEBUG, "PahoMqtt::connected", client.id = %client_id, client.mqtt.version = %mqtt_version);
 				span.add_link(span_cx.clone());
 
 				let inner = inner.clone();
-				let online_message = online_message.clone();
 				async move {
 					let client = &inner.client;
 					let subscriptions = inner.subscriptions.borrow();
@@ -265,16 +516,6 @@ impl MqttProvider for PahoMqtt {
 							);
 						}
 					}
-
-					if let Err(e) = client.publish(online_message.message).await {
-						event!(
-							Level::ERROR,
-							client.id = %client_id,
-							client.mqtt.version = %mqtt_version,
-							"failed to publish online message: {:#}",
-							e,
-						);
-					}
 				}
 				.instrument(span)
 			}
@@ -283,8 +524,11 @@ impl MqttProvider for PahoMqtt {
 		let mut connection_lost_callback = create_callback({
 			let span_cx = span_cx.clone();
 			let inner = inner.clone();
+			let event_sender = event_sender.clone();
 			move |_: ()| {
 				Metrics::global().connection_lost.add(1);
+				inner.topic_aliases.borrow_mut().reset();
+				let _ = event_sender.send(MqttConnectionEvent::ConnectionLost);
 				let span_cx = span_cx.clone();
 				let client_id = inner.client.client_id();
 				let mqtt_version = inner.client.mqtt_version();
@@ -307,6 +551,10 @@ impl MqttProvider for PahoMqtt {
 			let inner = inner.clone();
 			move |(reason,): (paho_mqtt::ReasonCode,)| {
 				Metrics::global().disconnected.add(1);
+				inner.topic_aliases.borrow_mut().reset();
+				let _ = event_sender.send(MqttConnectionEvent::Disconnected {
+					reason: reason.to_string().into(),
+				});
 				let span_cx = span_cx.clone();
 				let client_id = inner.client.client_id();
 				let mqtt_version = inner.client.mqtt_version();
@@ -334,12 +582,31 @@ impl MqttProvider for PahoMqtt {
 				let mqtt_version = inner.client.mqtt_version();
 				let message_sender = message_sender.clone();
 				if let Some(message) = message {
+					if !has_utf8_topic(&message) {
+						Metrics::global().invalid_topic.add(1);
+						event!(
+							Level::WARN,
+							client.id = %client_id,
+							"skipping message with a topic that is not valid UTF-8",
+						);
+						return ready(()).boxed();
+					}
+
 					Metrics::global().message.add(1, message.topic().to_owned());
-					let span = span!(parent: None, Level::DEBUG, "PahoMqtt::message", client.id = %client_id, client.mqtt.version = %mqtt_version, message.topic = %message.topic(), message.retained = message.retained(), message.qos = %message.qos(), message.payload.len = message.payload().len());
-					span.add_link(span_cx.clone());
+					let sampled = inner.message_span_sampler.sample();
+					let span = if sampled {
+						let span = span!(parent: None, Level::DEBUG, "PahoMqtt::message", client.id = %client_id, client.mqtt.version = %mqtt_version, message.topic = %message.topic(), message.retained = message.retained(), message.qos = %message.qos(), message.payload.len = message.payload().len());
+						span.add_link(span_cx.clone());
+						span
+					} else {
+						Span::none()
+					};
 
 					async move {
-						if let Err(e) = message_sender.send_async((message, span_cx.clone())).await {
+						if let Err(e) = message_sender
+							.send_async((message, span_cx.clone(), sampled))
+							.await
+						{
 							event!(
 								Level::ERROR,
 								client.id = %client_id,
@@ -360,17 +627,86 @@ impl MqttProvider for PahoMqtt {
 		client.set_connection_lost_callback(move |_| connection_lost_callback(()));
 		client.set_disconnected_callback(move |_, _props, reason| disconnected_callback((reason,)));
 		client.set_message_callback(move |_, message| message_callback((message,)));
+		let callbacks = CallbackGuard { client: &client };
 
-		client
+		let connect_span = span!(Level::DEBUG, "PahoMqtt::connect", client.id = %client_id);
+		let response = match client
 			.connect(builder.finalize())
-			.instrument(span!(Level::DEBUG, "PahoMqtt::connect", client.id = %client_id))
+			.instrument(connect_span.clone())
 			.await
-			.map_err(PahoProviderConnectError::connect)?;
+		{
+			// the broker told us it doesn't support retained messages, so the will is sent
+			// without retain instead
+			Err(e) if is_retain_not_supported(&e) && offline_message.message.retained() => {
+				builder.will_message(with_retain(offline_message.message, false));
+				client
+					.connect(builder.finalize())
+					.instrument(connect_span)
+					.await
+			}
+			result => result,
+		}
+		.map_err(PahoProviderConnectError::connect)?;
+
+		let max_packet_size = response
+			.properties()
+			.get_int(paho_mqtt::PropertyCode::MaximumPacketSize)
+			.and_then(|size| u32::try_from(size).ok());
+		inner.max_packet_size.set(max_packet_size);
+		let server_info = server_info(response.properties());
+		if options.topic_aliases {
+			let maximum = server_info.topic_alias_maximum.unwrap_or(0);
+			inner.topic_aliases.replace(TopicAliases::new(maximum));
+		}
+		let online_message = match server_info.retain_available {
+			Some(false) => with_retain(online_message.message, false),
+			_ => online_message.message,
+		};
+		inner.server_info.replace(server_info);
+		callbacks.disarm();
+
+		// only published on the initial connection, the client decides whether the node is
+		// still available when it reconnects
+		if let Err(e) = client.publish(online_message).await {
+			event!(
+				Level::ERROR,
+				client.id = %client_id,
+				client.mqtt.version = %client.mqtt_version(),
+				"failed to publish online message: {:#}",
+				e,
+			);
+		}
 
 		Ok(Client { inner })
 	}
 }
 
+/// Removes the callbacks of a client that is being created when dropped, unless it is
+/// disarmed once the client is connected. The callbacks keep the tasks spawned by
+/// [create_callback] (and the client they reference) alive, so they would linger if
+/// [PahoMqtt::create] is cancelled or fails.
+struct CallbackGuard<'a> {
+	client: &'a paho_mqtt::AsyncClient,
+}
+
+impl CallbackGuard<'_> {
+	fn disarm(self) {
+		std::mem::forget(self);
+	}
+}
+
+impl Drop for CallbackGuard<'_> {
+	fn drop(&mut self) {
+		self.client.remove_connected_callback();
+		self.client.remove_connection_lost_callback();
+		self.client.remove_disconnected_callback();
+		self.client.remove_message_callback();
+
+		// abandons a pending connect, and stops reconnecting
+		let _ = self.client.disconnect(None);
+	}
+}
+
 #[derive(Clone)]
 struct SubscriptionOptions {
 	topic: Arc<str>,
@@ -419,19 +755,34 @@ impl From<&SubscriptionOptions> for paho_mqtt::SubscribeOptions {
 
 struct InnerClient {
 	client: paho_mqtt::AsyncClient,
-	messages: flume::Receiver<(paho_mqtt::Message, SpanContext)>,
+	messages: flume::Receiver<(paho_mqtt::Message, SpanContext, bool)>,
+	connection_events: flume::Receiver<MqttConnectionEvent>,
 	subscriptions: RefCell<Vec<SubscriptionOptions>>,
+	max_packet_size: Cell<Option<u32>>,
+	server_info: RefCell<ServerInfo>,
+	topic_aliases: RefCell<TopicAliases>,
+	broker_addrs: Vec<SocketAddr>,
+	message_span_sampler: MessageSpanSampler,
 }
 
 impl InnerClient {
 	fn new(
 		client: paho_mqtt::AsyncClient,
-		messages: flume::Receiver<(paho_mqtt::Message, SpanContext)>,
+		messages: flume::Receiver<(paho_mqtt::Message, SpanContext, bool)>,
+		connection_events: flume::Receiver<MqttConnectionEvent>,
+		broker_addrs: Vec<SocketAddr>,
+		message_span_sampler: MessageSpanSampler,
 	) -> Arc<Self> {
 		Self {
 			client,
 			messages,
+			connection_events,
 			subscriptions: RefCell::default(),
+			max_packet_size: Cell::default(),
+			server_info: RefCell::default(),
+			topic_aliases: RefCell::default(),
+			broker_addrs,
+			message_span_sampler,
 		}
 		.into()
 	}
@@ -457,7 +808,7 @@ pub struct MessageStream {
 	client_id: String,
 	mqtt_version: u32,
 	#[pin]
-	inner: flume::r#async::RecvStream<'static, (paho_mqtt::Message, SpanContext)>,
+	inner: flume::r#async::RecvStream<'static, (paho_mqtt::Message, SpanContext, bool)>,
 }
 
 #[derive(Clone)]
@@ -473,42 +824,68 @@ impl From<paho_mqtt::Message> for Message {
 
 pub struct MessageBuilder {
 	builder: paho_mqtt::MessageBuilder,
+	// paho replaces the properties of the builder as a whole, so they're collected here
+	properties: paho_mqtt::Properties,
 }
 
 impl MessageBuilder {
 	fn new() -> Self {
 		Self {
 			builder: paho_mqtt::MessageBuilder::new(),
+			properties: paho_mqtt::Properties::new(),
 		}
 	}
-}
 
-impl From<paho_mqtt::MessageBuilder> for MessageBuilder {
-	fn from(builder: paho_mqtt::MessageBuilder) -> Self {
-		Self { builder }
+	fn map(self, f: impl FnOnce(paho_mqtt::MessageBuilder) -> paho_mqtt::MessageBuilder) -> Self {
+		Self {
+			builder: f(self.builder),
+			properties: self.properties,
+		}
 	}
 }
 
 impl Client {
-	#[instrument(
-		level = Level::DEBUG,
-		name = "PahoMqtt::publish",
-		skip_all,
-		fields(
-			client.id = %self.client_id(),
-			client.mqtt.version = %self.mqtt_version(),
-			message.topic = %builder.message.topic(),
-			message.retained = builder.message.retained(),
-			message.qos = %builder.message.qos(),
-			message.payload.len = builder.message.payload().len(),
-		),
-		err,
-	)]
-	async fn publish(&self, builder: PublishBuilder<'_>) -> Result<(), paho_mqtt::Error> {
-		let topic = builder.message.topic().to_owned();
-		self.inner.client.publish(builder.message.message).await?;
-		Metrics::global().publish.add(1, topic);
-		Ok(())
+	async fn publish(&self, builder: PublishBuilder<'_>) -> Result<(), PahoPublishError> {
+		let sampled = builder
+			.sampled
+			.unwrap_or_else(|| self.inner.message_span_sampler.sample());
+
+		// not using #[instrument] so that unsampled messages skip span creation
+		let span = MessageSpanSampler::span_if(sampled, || {
+			span!(
+				Level::DEBUG,
+				"PahoMqtt::publish",
+				client.id = %self.client_id(),
+				client.mqtt.version = %self.mqtt_version(),
+				message.topic = %builder.message.topic(),
+				message.retained = builder.message.retained(),
+				message.qos = %builder.message.qos(),
+				message.payload.len = builder.message.payload().len(),
+			)
+		});
+
+		async move {
+			let topic = builder.message.topic().to_owned();
+			let message = builder.into_message(self.mqtt_version());
+
+			// messages buffered while disconnected are sent on a new connection, which
+			// doesn't know the aliases of the current one
+			let message = if self.inner.client.is_connected() {
+				self.inner.topic_aliases.borrow_mut().apply(message)
+			} else {
+				message
+			};
+
+			if let Err(e) = self.inner.client.publish(message).await {
+				event!(Level::ERROR, error = %e);
+				return Err(PahoPublishError::publish(topic, e));
+			}
+
+			Metrics::global().publish.add(1, topic);
+			Ok(())
+		}
+		.instrument(span)
+		.await
 	}
 
 	#[instrument(
@@ -526,7 +903,7 @@ impl Client {
 	async fn subscribe(
 		&self,
 		builder: SubscribeBuilder<'_>,
-	) -> Result<SubscriptionKey, paho_mqtt::Error> {
+	) -> Result<Subscribed<SubscriptionKey>, PahoSubscribeError> {
 		let options = SubscriptionOptions::from(builder);
 		let key = {
 			let subscriptions = self.inner.subscriptions.borrow();
@@ -534,10 +911,9 @@ impl Client {
 				.iter()
 				.any(|s| Arc::ptr_eq(&s.topic, &options.topic))
 			{
-				return Err(paho_mqtt::Error::from(format!(
-					"Already subscribed to topic: '{}'",
-					options.topic
-				)));
+				return Err(PahoSubscribeError::AlreadySubscribed {
+					topic: options.topic.clone(),
+				});
 			}
 
 			SubscriptionKey {
@@ -546,7 +922,7 @@ impl Client {
 		};
 
 		let topic = options.topic.clone();
-		if options.is_empty() {
+		let response = if options.is_empty() {
 			self
 				.inner
 				.client
@@ -559,11 +935,21 @@ impl Client {
 				None,
 			)
 		}
-		.await?;
+		.await
+		.map_err(|source| PahoSubscribeError::subscribe(topic.clone(), source))?;
+
+		// the server responds with the granted QoS (or the reason code for MQTT v5,
+		// which doubles as the granted QoS on success)
+		let granted_qos = match response.subscribe_response() {
+			Some(paho_mqtt::QOS_0) => QosLevel::AtMostOnce,
+			Some(paho_mqtt::QOS_1) => QosLevel::AtLeastOnce,
+			Some(paho_mqtt::QOS_2) => QosLevel::ExactlyOnce,
+			_ => options.qos,
+		};
 
-		event!(Level::INFO, mqtt.topic = %topic, "subscribed to MQTT topic");
+		event!(Level::INFO, mqtt.topic = %topic, mqtt.granted_qos = %granted_qos, "subscribed to MQTT topic");
 		Metrics::global().subscribe.add(1, topic);
-		Ok(key)
+		Ok(Subscribed { key, granted_qos })
 	}
 
 	#[instrument(
@@ -576,7 +962,7 @@ impl Client {
 		),
 		err,
 	)]
-	async fn unsubscribe(&self, builder: UnsubscribeBuilder<'_>) -> Result<(), paho_mqtt::Error> {
+	async fn unsubscribe(&self, builder: UnsubscribeBuilder<'_>) -> Result<(), PahoUnsubscribeError> {
 		event!(
 			Level::INFO,
 			monotonic_counter.paho.unsubscribe = 1,
@@ -588,18 +974,20 @@ impl Client {
 				.iter()
 				.enumerate()
 				.find(|(_, s)| Arc::ptr_eq(&s.topic, &builder.key.key))
-				.ok_or_else(|| {
-					paho_mqtt::Error::from(format!(
-						"Subscription not found for topic: '{}'",
-						builder.key.key
-					))
+				.ok_or_else(|| PahoUnsubscribeError::NotSubscribed {
+					topic: builder.key.key.clone(),
 				})?;
 
 			subscriptions.swap_remove(idx)
 		};
 
 		let topic = opts.topic.clone();
-		self.inner.client.unsubscribe(opts.topic.as_ref()).await?;
+		self
+			.inner
+			.client
+			.unsubscribe(opts.topic.as_ref())
+			.await
+			.map_err(|source| PahoUnsubscribeError::unsubscribe(topic.clone(), source))?;
 
 		event!(Level::INFO, mqtt.topic = %topic, "unsubscribed to MQTT topic");
 		Metrics::global().unsubscribe.add(1, topic);
@@ -618,7 +1006,7 @@ impl Client {
 		),
 		err,
 	)]
-	async fn disconnect(&self, builder: DisconnectBuilder<'_>) -> Result<(), paho_mqtt::Error> {
+	async fn disconnect(&self, builder: DisconnectBuilder<'_>) -> Result<(), PahoDisconnectError> {
 		let mut opts = paho_mqtt::DisconnectOptionsBuilder::new();
 		if let Some(timeout) = builder.timeout {
 			opts.timeout(timeout);
@@ -630,6 +1018,7 @@ impl Client {
 		paho_mqtt::AsyncClient::disconnect(&self.inner.client, opts.finalize())
 			.await
 			.map(|_| ())
+			.map_err(PahoDisconnectError::disconnect)
 	}
 }
 
@@ -637,6 +1026,7 @@ impl MqttClient for Client {
 	type Provider = PahoMqtt;
 	type Message = Message;
 	type Messages = MessageStream;
+	type ConnectionEvents = flume::r#async::RecvStream<'static, MqttConnectionEvent>;
 	type SubscriptionKey = SubscriptionKey;
 	type PublishBuilder<'a> = PublishBuilder<'a>;
 	type SubscribeBuilder<'a> = SubscribeBuilder<'a>;
@@ -647,10 +1037,24 @@ impl MqttClient for Client {
 		self.inner.client.client_id().into()
 	}
 
+	fn max_packet_size(&self) -> Option<u32> {
+		self.inner.max_packet_size.get()
+	}
+
+	fn broker_addrs(&self) -> &[SocketAddr] {
+		&self.inner.broker_addrs
+	}
+
+	fn server_info(&self) -> ServerInfo {
+		self.inner.server_info.borrow().clone()
+	}
+
 	fn publish(&self, message: Message) -> Self::PublishBuilder<'_> {
 		PublishBuilder {
 			client: self,
 			message,
+			expiry: None,
+			sampled: None,
 		}
 	}
 
@@ -683,6 +1087,10 @@ impl MqttClient for Client {
 			inner: self.inner.messages.clone().into_stream(),
 		}
 	}
+
+	fn connection_events(&self) -> Self::ConnectionEvents {
+		self.inner.connection_events.clone().into_stream()
+	}
 }
 
 pub struct SubscriptionKey {
@@ -693,10 +1101,36 @@ pub struct SubscriptionKey {
 pub struct PublishBuilder<'a> {
 	client: &'a Client,
 	message: Message,
+	expiry: Option<Duration>,
+	sampled: Option<bool>,
+}
+
+impl<'a> PublishBuilder<'a> {
+	/// The message to send on a connection using `mqtt_version`. The message expiry
+	/// is an MQTT 5 property, so it is dropped on older connections.
+	fn into_message(self, mqtt_version: u32) -> paho_mqtt::Message {
+		match (self.expiry, mqtt_version) {
+			(Some(expiry), paho_mqtt::MQTT_VERSION_5) => {
+				with_message_expiry(self.message.message, expiry)
+			}
+			_ => self.message.message,
+		}
+	}
 }
 
 impl<'a> MqttPublishBuilder for PublishBuilder<'a> {
-	type Error = paho_mqtt::Error;
+	type Error = PahoPublishError;
+
+	/// Only used by MQTT5 connections.
+	fn expiry(mut self, ttl: Duration) -> Self {
+		self.expiry.replace(ttl);
+		self
+	}
+
+	fn sampled(mut self, sampled: bool) -> Self {
+		self.sampled.replace(sampled);
+		self
+	}
 }
 
 impl<'a> IntoFuture for PublishBuilder<'a> {
@@ -717,7 +1151,7 @@ pub struct SubscribeBuilder<'a> {
 }
 
 impl<'a> MqttSubscribeBuilder for SubscribeBuilder<'a> {
-	type Error = paho_mqtt::Error;
+	type Error = PahoSubscribeError;
 	type SubscriptionKey = SubscriptionKey;
 
 	fn no_local(mut self, on: bool) -> Self {
@@ -732,7 +1166,7 @@ impl<'a> MqttSubscribeBuilder for SubscribeBuilder<'a> {
 }
 
 impl<'a> IntoFuture for SubscribeBuilder<'a> {
-	type Output = Result<SubscriptionKey, <Self as MqttSubscribeBuilder>::Error>;
+	type Output = Result<Subscribed<SubscriptionKey>, <Self as MqttSubscribeBuilder>::Error>;
 	type IntoFuture = LocalBoxFuture<'a, Self::Output>;
 
 	fn into_future(self) -> Self::IntoFuture {
@@ -746,7 +1180,7 @@ pub struct UnsubscribeBuilder<'a> {
 }
 
 impl<'a> MqttUnsubscribeBuilder for UnsubscribeBuilder<'a> {
-	type Error = paho_mqtt::Error;
+	type Error = PahoUnsubscribeError;
 }
 
 impl<'a> IntoFuture for UnsubscribeBuilder<'a> {
@@ -765,7 +1199,7 @@ pub struct DisconnectBuilder<'a> {
 }
 
 impl<'a> MqttDisconnectBuilder for DisconnectBuilder<'a> {
-	type Error = paho_mqtt::Error;
+	type Error = PahoDisconnectError;
 
 	fn after(mut self, timeout: Duration) -> Self {
 		self.timeout.replace(timeout);
@@ -810,6 +1244,11 @@ impl MqttMessage for Message {
 			_ => unreachable!(),
 		}
 	}
+
+	fn dup(&self) -> bool {
+		// paho-mqtt doesn't expose the DUP flag of received messages
+		false
+	}
 }
 
 impl MqttBuildableMessage for Message {
@@ -825,30 +1264,47 @@ impl MqttMessageBuilder for MessageBuilder {
 	type Error = Infallible;
 
 	fn topic(self, topic: impl Into<String>) -> Self {
-		self.builder.topic(topic).into()
+		self.map(|builder| builder.topic(topic))
 	}
 
 	fn payload(self, payload: impl Into<Vec<u8>>) -> Self {
-		self.builder.payload(payload).into()
+		self.map(|builder| builder.payload(payload))
 	}
 
 	fn qos(self, qos: crate::QosLevel) -> Self {
-		self
-			.builder
-			.qos(match qos {
+		self.map(|builder| {
+			builder.qos(match qos {
 				crate::QosLevel::AtMostOnce => paho_mqtt::QOS_0,
 				crate::QosLevel::AtLeastOnce => paho_mqtt::QOS_1,
 				crate::QosLevel::ExactlyOnce => paho_mqtt::QOS_2,
 			})
-			.into()
+		})
 	}
 
 	fn retain(self, retain: bool) -> Self {
-		self.builder.retained(retain).into()
+		self.map(|builder| builder.retained(retain))
+	}
+
+	fn user_property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+		self
+			.properties
+			.push_string_pair(
+				paho_mqtt::PropertyCode::UserProperty,
+				&key.into(),
+				&value.into(),
+			)
+			.expect("user property is a string pair property");
+		self
 	}
 
 	fn build(self) -> Result<Self::Message, Self::Error> {
-		Ok(self.builder.finalize().into())
+		let builder = if self.properties.is_empty() {
+			self.builder
+		} else {
+			self.builder.properties(self.properties)
+		};
+
+		Ok(builder.finalize().into())
 	}
 }
 
@@ -857,7 +1313,10 @@ impl Stream for MessageStream {
 
 	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
 		match self.as_mut().project().inner.poll_next(cx) {
-			Poll::Ready(Some((message, client_cx))) => {
+			Poll::Ready(Some((message, _, false))) => {
+				Poll::Ready(Some(MqttReceivedMessage::new(message.into(), Span::none())))
+			}
+			Poll::Ready(Some((message, client_cx, true))) => {
 				let span = span!(
 					parent: None,
 					Level::DEBUG,
@@ -884,15 +1343,492 @@ impl FusedStream for MessageStream {
 	}
 }
 
+/// The URI paho connects to for a resolved broker address. paho picks the transport, and
+/// whether to use TLS, from the scheme.
+fn server_uri(options: &MqttOptions, addr: &SocketAddr) -> String {
+	match &options.transport {
+		MqttTransport::Tcp if options.tls => format!("ssl://{addr}"),
+		MqttTransport::Tcp => format!("tcp://{addr}"),
+		MqttTransport::WebSocket { path } if options.tls => format!("wss://{addr}{path}"),
+		MqttTransport::WebSocket { path } => format!("ws://{addr}{path}"),
+	}
+}
+
 fn as_create_options(
 	options: &MqttOptions,
 	client_id: &str,
 ) -> Result<paho_mqtt::CreateOptions, PahoProviderConnectError> {
 	let builder = paho_mqtt::CreateOptionsBuilder::new()
 		.client_id(client_id)
-		.send_while_disconnected(true);
+		.send_while_disconnected(true)
+		// paho always buffers QoS 0 messages in memory, this decides if they're persisted
+		.persist_qos0(options.buffer_qos0);
 
 	let builder = builder.persistence(options.persitence.clone());
 
 	Ok(builder.finalize())
 }
+
+/// The options used to connect to the broker, except for the server URIs and the last
+/// will, which are only known once the client is created.
+fn as_connect_options(
+	options: &MqttOptions,
+) -> Result<paho_mqtt::ConnectOptionsBuilder, PahoProviderConnectError> {
+	let mut builder = match &options.version {
+		MqttVersion::Default => paho_mqtt::ConnectOptionsBuilder::new(),
+		MqttVersion::V3 => paho_mqtt::ConnectOptionsBuilder::new_v3(),
+		MqttVersion::V5 => paho_mqtt::ConnectOptionsBuilder::new_v5(),
+	};
+
+	if let Some((min, max)) = options.reconnect_interval() {
+		builder.automatic_reconnect(min, max);
+	}
+
+	if let Some(keep_alive) = options.keep_alive {
+		builder.keep_alive_interval(keep_alive);
+	}
+
+	// paho switches the options to the other MQTT version when the flag of that version
+	// is set, so only the flag of the version in use is set
+	match (&options.version, options.clean_session) {
+		(MqttVersion::V5, Some(clean)) => {
+			builder.clean_start(clean);
+		}
+		(_, Some(clean)) => {
+			builder.clean_session(clean);
+		}
+		(_, None) => (),
+	}
+
+	if let (MqttVersion::V5, Some(expiry)) = (&options.version, options.session_expiry) {
+		let mut properties = paho_mqtt::Properties::new();
+		properties
+			.push_u32(
+				paho_mqtt::PropertyCode::SessionExpiryInterval,
+				u32::try_from(expiry.as_secs()).unwrap_or(u32::MAX),
+			)
+			.expect("session expiry interval is a four byte integer property");
+		builder.properties(properties);
+	}
+
+	#[cfg(feature = "ssl")]
+	if options.tls {
+		builder.ssl_options(as_ssl_options(&options.tls_config)?);
+	}
+
+	if let Some(auth) = &options.auth {
+		builder.user_name(auth.username.clone());
+		builder.password(auth.password.clone());
+	}
+
+	Ok(builder)
+}
+
+#[cfg(feature = "ssl")]
+fn as_ssl_options(
+	config: &MqttTlsConfig,
+) -> Result<paho_mqtt::SslOptions, PahoProviderConnectError> {
+	let mut builder = paho_mqtt::SslOptionsBuilder::new();
+	if let Some(ca_file) = &config.ca_file {
+		builder
+			.trust_store(ca_file)
+			.map_err(PahoProviderConnectError::tls)?;
+	}
+
+	if let Some(client_cert_file) = &config.client_cert_file {
+		builder
+			.key_store(client_cert_file)
+			.map_err(PahoProviderConnectError::tls)?;
+	}
+
+	if let Some(private_key_file) = &config.private_key_file {
+		builder
+			.private_key(private_key_file)
+			.map_err(PahoProviderConnectError::tls)?;
+	}
+
+	if let Some(password) = &config.private_key_password {
+		builder.private_key_password(password.clone());
+	}
+
+	if config.insecure_skip_verify {
+		builder.enable_server_cert_auth(false).verify(false);
+	}
+
+	Ok(builder.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{error::Error as _, ffi::CString, path::PathBuf};
+
+	#[test]
+	fn invalid_utf8_topics_are_detected() {
+		let message = |topic: &[u8]| {
+			paho_mqtt::Message::from_c_parts(
+				CString::new(topic).unwrap(),
+				&paho_mqtt_sys::MQTTAsync_message::default(),
+			)
+		};
+
+		assert!(has_utf8_topic(&message(b"app/light/set")));
+		assert!(has_utf8_topic(&message(b"")));
+		assert!(!has_utf8_topic(&message(b"app/\xff/set")));
+	}
+
+	#[test]
+	fn server_info_is_read_from_properties() {
+		let mut properties = paho_mqtt::Properties::new();
+		properties
+			.push_string(paho_mqtt::PropertyCode::AssignedClientIdentifer, "auto-1234")
+			.unwrap();
+		properties
+			.push_int(paho_mqtt::PropertyCode::RetainAvailable, 0)
+			.unwrap();
+
+		assert_eq!(
+			server_info(&properties),
+			ServerInfo {
+				assigned_client_id: Some("auto-1234".into()),
+				retain_available: Some(false),
+				..ServerInfo::default()
+			}
+		);
+		assert_eq!(
+			server_info(&paho_mqtt::Properties::new()),
+			ServerInfo::default()
+		);
+	}
+
+	#[test]
+	fn topic_alias_is_used_on_second_publish() {
+		let message = |topic: &str| paho_mqtt::Message::new(topic, "on", paho_mqtt::QOS_0);
+		let alias = |message: &paho_mqtt::Message| {
+			message
+				.properties()
+				.get_int(paho_mqtt::PropertyCode::TopicAlias)
+		};
+
+		let mut aliases = TopicAliases::new(1);
+		let first = aliases.apply(message("app/light/state"));
+		assert_eq!(first.topic(), "app/light/state");
+		assert_eq!(alias(&first), Some(1));
+
+		let second = aliases.apply(message("app/light/state"));
+		assert_eq!(second.topic(), "");
+		assert_eq!(second.payload(), b"on");
+		assert_eq!(second.qos(), paho_mqtt::QOS_0);
+		assert_eq!(alias(&second), Some(1));
+
+		// messages that may be retransmitted keep their topic
+		let acknowledged = aliases.apply(paho_mqtt::Message::new(
+			"app/light/state",
+			"on",
+			paho_mqtt::QOS_1,
+		));
+		assert_eq!(acknowledged.topic(), "app/light/state");
+		assert_eq!(alias(&acknowledged), None);
+
+		// the broker only accepts a single alias
+		let other = aliases.apply(message("app/switch/state"));
+		assert_eq!(other.topic(), "app/switch/state");
+		assert_eq!(alias(&other), None);
+
+		aliases.clear();
+		let reconnected = aliases.apply(message("app/switch/state"));
+		assert_eq!(reconnected.topic(), "app/switch/state");
+		assert_eq!(alias(&reconnected), Some(1));
+
+		// the maximum of a lost connection isn't carried over
+		aliases.reset();
+		let lost = aliases.apply(message("app/switch/state"));
+		assert_eq!(lost.topic(), "app/switch/state");
+		assert_eq!(alias(&lost), None);
+	}
+
+	#[test]
+	fn user_properties_are_attached_to_message() {
+		let message = Message::builder()
+			.topic("app/light/state")
+			.user_property("source", "hass-rs")
+			.payload("on")
+			.user_property("unit", "lux")
+			.build()
+			.unwrap();
+
+		let properties = message.message.properties();
+		assert_eq!(
+			properties.find_user_property("source").as_deref(),
+			Some("hass-rs")
+		);
+		assert_eq!(
+			properties.find_user_property("unit").as_deref(),
+			Some("lux")
+		);
+		assert_eq!(message.message.payload(), b"on");
+
+		// the user properties are kept when a topic alias is added
+		let mut aliases = TopicAliases::new(1);
+		let aliased = aliases.apply(message.message);
+		assert_eq!(
+			aliased.properties().find_user_property("unit").as_deref(),
+			Some("lux")
+		);
+
+		let message = Message::builder().topic("app/light/state").build().unwrap();
+		assert!(message.message.properties().is_empty());
+	}
+
+	#[test]
+	fn message_expiry_is_set_on_message() {
+		let message = Message::builder()
+			.topic("app/light/state")
+			.payload("on")
+			.qos(QosLevel::AtLeastOnce)
+			.retain(true)
+			.user_property("unit", "lux")
+			.build()
+			.unwrap();
+
+		let message = with_message_expiry(message.message, Duration::from_millis(1500));
+		assert_eq!(
+			message
+				.properties()
+				.get_int(paho_mqtt::PropertyCode::MessageExpiryInterval),
+			Some(2)
+		);
+		assert_eq!(
+			message.properties().find_user_property("unit").as_deref(),
+			Some("lux")
+		);
+		assert_eq!(message.topic(), "app/light/state");
+		assert_eq!(message.payload(), b"on");
+		assert_eq!(message.qos(), paho_mqtt::QOS_1);
+		assert!(message.retained());
+	}
+
+	fn client() -> Client {
+		let client = paho_mqtt::AsyncClient::new(
+			paho_mqtt::CreateOptionsBuilder::new()
+				.persistence(None)
+				.finalize(),
+		)
+		.unwrap();
+		let (_, messages) = flume::unbounded();
+		let (_, connection_events) = flume::unbounded();
+
+		Client {
+			inner: InnerClient::new(
+				client,
+				messages,
+				connection_events,
+				Vec::new(),
+				MessageSpanSampler::default(),
+			),
+		}
+	}
+
+	#[test]
+	fn message_expiry_is_only_published_on_mqtt5() {
+		let client = client();
+		let message = || Message::builder().topic("app/light/state").build().unwrap();
+		let expiry = |message: paho_mqtt::Message| {
+			message
+				.properties()
+				.get_int(paho_mqtt::PropertyCode::MessageExpiryInterval)
+		};
+
+		let builder = client.publish(message()).expiry(Duration::from_secs(30));
+		assert_eq!(
+			expiry(builder.into_message(paho_mqtt::MQTT_VERSION_5)),
+			Some(30)
+		);
+
+		let builder = client.publish(message()).expiry(Duration::from_secs(30));
+		assert_eq!(
+			expiry(builder.into_message(paho_mqtt::MQTT_VERSION_3_1_1)),
+			None
+		);
+
+		let builder = client.publish(message());
+		assert_eq!(
+			expiry(builder.into_message(paho_mqtt::MQTT_VERSION_5)),
+			None
+		);
+	}
+
+	#[test]
+	fn topic_aliases_are_disabled_without_maximum() {
+		let mut aliases = TopicAliases::default();
+		for _ in 0..2 {
+			let message = aliases.apply(paho_mqtt::Message::new("app/light/state", "on", 0));
+			assert_eq!(message.topic(), "app/light/state");
+			assert!(message.properties().is_empty());
+		}
+	}
+
+	#[test]
+	fn publish_error_wraps_paho_error() {
+		let err = PahoPublishError::publish("app/light/state", paho_mqtt::Error::from("timed out"));
+		assert!(matches!(
+			err,
+			PahoPublishError::Publish { ref topic, .. } if topic == "app/light/state"
+		));
+		assert_eq!(err.source().unwrap().to_string(), "timed out");
+	}
+
+	#[test]
+	fn subscribe_errors_wrap_paho_errors() {
+		let topic: Arc<str> = "app/light/set".into();
+		let err = PahoSubscribeError::subscribe(topic.clone(), paho_mqtt::Error::from("rejected"));
+		assert!(matches!(
+			err,
+			PahoSubscribeError::Subscribe { topic: ref t, .. } if Arc::ptr_eq(t, &topic)
+		));
+		assert_eq!(err.source().unwrap().to_string(), "rejected");
+
+		let err = PahoSubscribeError::AlreadySubscribed {
+			topic: topic.clone(),
+		};
+		assert!(err.source().is_none());
+		assert_eq!(
+			err.to_string(),
+			"already subscribed to MQTT topic 'app/light/set'"
+		);
+	}
+
+	#[test]
+	fn server_uri_matches_transport() {
+		let addr: SocketAddr = "127.0.0.1:1883".parse().unwrap();
+		let mut options = MqttOptions::new("127.0.0.1", std::env::temp_dir());
+		assert_eq!(server_uri(&options, &addr), "tcp://127.0.0.1:1883");
+
+		options.tls(true);
+		assert_eq!(server_uri(&options, &addr), "ssl://127.0.0.1:1883");
+
+		options.transport(MqttTransport::WebSocket {
+			path: "/mqtt".into(),
+		});
+		assert_eq!(server_uri(&options, &addr), "wss://127.0.0.1:1883/mqtt");
+
+		options.tls(false);
+		assert_eq!(server_uri(&options, &addr), "ws://127.0.0.1:1883/mqtt");
+
+		// resolved IPv6 addresses keep their brackets
+		let addr: SocketAddr = "[::1]:80".parse().unwrap();
+		assert_eq!(server_uri(&options, &addr), "ws://[::1]:80/mqtt");
+	}
+
+	#[test]
+	fn keep_alive_is_set_on_connect_options() {
+		// paho doesn't expose the keep alive interval of the connect options
+		let keep_alive = |options: &MqttOptions| {
+			let connect_options = format!("{:?}", as_connect_options(options).unwrap().finalize());
+			let (_, rest) = connect_options
+				.split_once("keepAliveInterval: ")
+				.expect("should format keep alive interval");
+			rest[..rest.find(',').unwrap()].parse::<i32>().unwrap()
+		};
+
+		let mut options = MqttOptions::new("127.0.0.1", std::env::temp_dir());
+		// the paho default
+		assert_eq!(keep_alive(&options), 60);
+
+		options.keep_alive(Duration::from_secs(15));
+		assert_eq!(keep_alive(&options), 15);
+	}
+
+	#[test]
+	fn clean_session_matches_mqtt_version() {
+		let mut options = MqttOptions::new("127.0.0.1", std::env::temp_dir());
+		options.clean_session(false);
+
+		options.version(MqttVersion::V3);
+		let connect_options = as_connect_options(&options).unwrap().finalize();
+		assert!(!connect_options.clean_session());
+		assert_ne!(connect_options.mqtt_version(), paho_mqtt::MQTT_VERSION_5);
+
+		options.version(MqttVersion::V5);
+		let connect_options = as_connect_options(&options).unwrap().finalize();
+		assert!(!connect_options.clean_start());
+		assert_eq!(connect_options.mqtt_version(), paho_mqtt::MQTT_VERSION_5);
+
+		// the paho defaults
+		let connect_options = as_connect_options(&MqttOptions::new("127.0.0.1", std::env::temp_dir()))
+			.unwrap()
+			.finalize();
+		assert!(connect_options.clean_session());
+	}
+
+	#[test]
+	#[cfg(feature = "ssl")]
+	fn tls_config_is_set_on_ssl_options() {
+		let ssl_options = as_ssl_options(&MqttTlsConfig {
+			ca_file: Some("/etc/mqtt/ca.pem".into()),
+			client_cert_file: Some("/etc/mqtt/client.pem".into()),
+			private_key_file: Some("/etc/mqtt/client.key".into()),
+			private_key_password: Some("secret".into()),
+			insecure_skip_verify: true,
+		})
+		.unwrap();
+		assert_eq!(ssl_options.trust_store(), PathBuf::from("/etc/mqtt/ca.pem"));
+		assert_eq!(
+			ssl_options.key_store(),
+			PathBuf::from("/etc/mqtt/client.pem")
+		);
+		assert_eq!(
+			ssl_options.private_key(),
+			PathBuf::from("/etc/mqtt/client.key")
+		);
+		assert!(!ssl_options.enable_server_cert_auth());
+
+		// the paho defaults
+		let ssl_options = as_ssl_options(&MqttTlsConfig::default()).unwrap();
+		assert_eq!(ssl_options.trust_store(), PathBuf::new());
+		assert!(ssl_options.enable_server_cert_auth());
+	}
+
+	#[tokio::test]
+	async fn dropped_create_leaves_no_callback_tasks() {
+		// accepts the connection, but never answers the CONNECT, so connecting hangs
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let mut options = MqttOptions::new(
+			"127.0.0.1",
+			std::env::temp_dir().join("hass-paho-dropped-create"),
+		);
+		options.port = listener.local_addr().unwrap().port();
+		let message = || {
+			Message::builder()
+				.topic("app/status")
+				.payload("online")
+				.build()
+				.unwrap()
+		};
+
+		let local = task::LocalSet::new();
+		local.spawn_local(async move {
+			let create = PahoMqtt::create(&options, "dropped-create", message(), message());
+			let result = tokio::time::timeout(Duration::from_millis(500), create).await;
+			assert!(result.is_err(), "should still be connecting");
+		});
+
+		// the local set completes once every task spawned on it has finished
+		tokio::time::timeout(Duration::from_secs(5), local)
+			.await
+			.expect("callback tasks should finish");
+		drop(listener);
+	}
+
+	#[test]
+	fn unsubscribe_and_disconnect_errors_wrap_paho_errors() {
+		let topic: Arc<str> = "app/light/set".into();
+		let err = PahoUnsubscribeError::unsubscribe(topic, paho_mqtt::Error::from("gone"));
+		assert!(matches!(err, PahoUnsubscribeError::Unsubscribe { .. }));
+		assert_eq!(err.source().unwrap().to_string(), "gone");
+
+		let err = PahoDisconnectError::disconnect(paho_mqtt::Error::from("timed out"));
+		assert!(matches!(err, PahoDisconnectError::Disconnect { .. }));
+		assert_eq!(err.source().unwrap().to_string(), "timed out");
+	}
+}
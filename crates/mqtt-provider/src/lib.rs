@@ -3,28 +3,36 @@ use futures::stream::Stream;
 use std::{
 	fmt::{self, Write},
 	future::IntoFuture,
+	net::SocketAddr,
 	path::PathBuf,
-	sync::Arc,
+	str::FromStr,
+	sync::{
+		atomic::{AtomicU32, Ordering},
+		Arc,
+	},
 	time::Duration,
 };
+use thiserror::Error;
 use tracing::{
 	span::{Entered, EnteredSpan},
 	Span,
 };
 
+/// The MQTT quality of service level. The discriminants are the QoS levels used on the
+/// wire.
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum QosLevel {
-	AtLeastOnce = 0,
-	AtMostOnce = 1,
+	AtMostOnce = 0,
+	AtLeastOnce = 1,
 	ExactlyOnce = 2,
 }
 
 impl fmt::Display for QosLevel {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
-			QosLevel::AtLeastOnce => f.write_char('0'),
-			QosLevel::AtMostOnce => f.write_char('1'),
+			QosLevel::AtMostOnce => f.write_char('0'),
+			QosLevel::AtLeastOnce => f.write_char('1'),
 			QosLevel::ExactlyOnce => f.write_char('2'),
 		}
 	}
@@ -42,6 +50,72 @@ impl From<QosLevel> for i32 {
 	}
 }
 
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum QosLevelError {
+	#[error("QoS level {value} is out of range, expected 0, 1 or 2")]
+	OutOfRange { value: u8 },
+
+	#[error(
+		"invalid QoS level '{value}', expected 0, 1, 2, at_most_once, at_least_once or exactly_once"
+	)]
+	Invalid { value: String },
+}
+
+impl TryFrom<u8> for QosLevel {
+	type Error = QosLevelError;
+
+	fn try_from(value: u8) -> Result<Self, Self::Error> {
+		match value {
+			0 => Ok(QosLevel::AtMostOnce),
+			1 => Ok(QosLevel::AtLeastOnce),
+			2 => Ok(QosLevel::ExactlyOnce),
+			value => Err(QosLevelError::OutOfRange { value }),
+		}
+	}
+}
+
+/// Parses a QoS level from its number (`0`, `1` or `2`), or its name (`at_most_once`,
+/// `at_least_once` or `exactly_once`).
+impl FromStr for QosLevel {
+	type Err = QosLevelError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"at_most_once" => Ok(QosLevel::AtMostOnce),
+			"at_least_once" => Ok(QosLevel::AtLeastOnce),
+			"exactly_once" => Ok(QosLevel::ExactlyOnce),
+			s => match s.parse::<u8>() {
+				Ok(value) => QosLevel::try_from(value),
+				Err(_) => Err(QosLevelError::Invalid { value: s.into() }),
+			},
+		}
+	}
+}
+
+#[cfg(feature = "proto")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "proto")))]
+impl From<hass_mqtt_proto::MqttQoS> for QosLevel {
+	fn from(qos: hass_mqtt_proto::MqttQoS) -> Self {
+		match qos {
+			hass_mqtt_proto::MqttQoS::AtMostOnce => QosLevel::AtMostOnce,
+			hass_mqtt_proto::MqttQoS::AtLeastOnce => QosLevel::AtLeastOnce,
+			hass_mqtt_proto::MqttQoS::ExactlyOnce => QosLevel::ExactlyOnce,
+		}
+	}
+}
+
+#[cfg(feature = "proto")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "proto")))]
+impl From<QosLevel> for hass_mqtt_proto::MqttQoS {
+	fn from(qos: QosLevel) -> Self {
+		match qos {
+			QosLevel::AtMostOnce => hass_mqtt_proto::MqttQoS::AtMostOnce,
+			QosLevel::AtLeastOnce => hass_mqtt_proto::MqttQoS::AtLeastOnce,
+			QosLevel::ExactlyOnce => hass_mqtt_proto::MqttQoS::ExactlyOnce,
+		}
+	}
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum MqttRetainHandling {
@@ -95,6 +169,22 @@ pub trait MqttProviderCreateError {
 	) -> Self;
 }
 
+/// A change of the connection state of a [MqttClient].
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum MqttConnectionEvent {
+	/// The connection to the broker was (re-)established.
+	Connected,
+
+	/// The connection to the broker was lost. The provider tries to reconnect on its own,
+	/// and emits [Connected](MqttConnectionEvent::Connected) once it succeeds.
+	ConnectionLost,
+
+	/// The broker disconnected the client (MQTT5 only), giving a `reason`. Like after
+	/// [ConnectionLost](MqttConnectionEvent::ConnectionLost), the provider tries to
+	/// reconnect on its own.
+	Disconnected { reason: Arc<str> },
+}
+
 #[async_trait(?Send)]
 pub trait MqttProvider {
 	const NAME: &'static str;
@@ -103,6 +193,13 @@ pub trait MqttProvider {
 	type Message: MqttBuildableMessage<Client = Self::Client>;
 	type Error: MqttProviderCreateError + std::error::Error + Send + Sync + 'static;
 
+	/// Connects to the broker, with `offline_message` as the last will. The
+	/// `online_message` is published once connected. It isn't published again when the
+	/// provider reconnects, since only the caller knows whether it's still online.
+	///
+	/// Both messages are only known to be retainable once the broker answered, so the
+	/// provider drops their retain flag when the broker says it doesn't support retained
+	/// messages (MQTT 5).
 	#[allow(clippy::too_many_arguments)]
 	async fn create(
 		options: &impl AsMqttOptions,
@@ -112,10 +209,28 @@ pub trait MqttProvider {
 	) -> Result<Self::Client, Self::Error>;
 }
 
+/// The properties the broker announced when the client connected (in the MQTT5
+/// `CONNACK`). Fields the broker didn't announce (or providers that don't support them)
+/// are `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerInfo {
+	/// The client id the broker assigned to the client, when the client connected
+	/// without one.
+	pub assigned_client_id: Option<Arc<str>>,
+	/// The keep alive (in seconds) the broker wants the client to use instead of the
+	/// configured one.
+	pub server_keep_alive: Option<u16>,
+	/// The highest topic alias the broker accepts.
+	pub topic_alias_maximum: Option<u16>,
+	/// Whether the broker supports retained messages.
+	pub retain_available: Option<bool>,
+}
+
 pub trait MqttClient: Sized {
 	type Provider: MqttProvider<Client = Self>;
 	type Message: MqttBuildableMessage<Client = Self>;
 	type Messages: Stream<Item = MqttReceivedMessage<Self>>;
+	type ConnectionEvents: Stream<Item = MqttConnectionEvent>;
 	type SubscriptionKey: Send + Sync + 'static;
 	type PublishBuilder<'a>: MqttPublishBuilder + 'a
 	where
@@ -132,8 +247,29 @@ pub trait MqttClient: Sized {
 
 	fn client_id(&self) -> Arc<str>;
 
+	/// The maximum packet size the broker accepts, if it announced one when the
+	/// client connected (MQTT5 `Maximum Packet Size`).
+	fn max_packet_size(&self) -> Option<u32> {
+		None
+	}
+
+	/// The addresses the host of the broker resolved to when the client connected. Empty
+	/// if the provider leaves resolving the host to the underlying MQTT library.
+	fn broker_addrs(&self) -> &[SocketAddr] {
+		&[]
+	}
+
+	/// The properties the broker announced when the client connected.
+	fn server_info(&self) -> ServerInfo {
+		ServerInfo::default()
+	}
+
 	fn messages(&self) -> Self::Messages;
 
+	/// The changes of the connection state of the client. Depending on the provider, the
+	/// initial connection might be reported as well.
+	fn connection_events(&self) -> Self::ConnectionEvents;
+
 	fn publish(&self, message: Self::Message) -> Self::PublishBuilder<'_>;
 
 	fn subscribe(&self, topic: impl Into<Arc<str>>, qos: QosLevel) -> Self::SubscribeBuilder<'_>;
@@ -145,10 +281,36 @@ pub trait MqttClient: Sized {
 
 pub trait MqttPublishBuilder: IntoFuture<Output = Result<(), Self::Error>> {
 	type Error: std::error::Error + Send + Sync + 'static;
+
+	/// Sets the MQTT 5 message expiry interval, after which the broker drops the message
+	/// instead of delivering it. Providers that don't support message expiry ignore it.
+	fn expiry(self, ttl: Duration) -> Self
+	where
+		Self: Sized,
+	{
+		let _ = ttl;
+		self
+	}
+
+	/// Sets whether the message is traced, when it was already sampled with the
+	/// [MessageSpanSampler] of the client (for instance because it is published on behalf
+	/// of an entity). The provider samples the message itself otherwise. This way all spans
+	/// of a message are either recorded or skipped together.
+	fn sampled(self, sampled: bool) -> Self;
+}
+
+/// The result of a successful subscription.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subscribed<K> {
+	/// The key used to unsubscribe from the topic again.
+	pub key: K,
+
+	/// The QoS level granted by the broker, which might be lower than the requested one.
+	pub granted_qos: QosLevel,
 }
 
 pub trait MqttSubscribeBuilder:
-	IntoFuture<Output = Result<Self::SubscriptionKey, Self::Error>>
+	IntoFuture<Output = Result<Subscribed<Self::SubscriptionKey>, Self::Error>>
 {
 	type SubscriptionKey: Send + Sync + 'static;
 	type Error: std::error::Error + Send + Sync + 'static;
@@ -175,6 +337,12 @@ pub trait MqttMessage {
 	fn payload(&self) -> &[u8];
 	fn retained(&self) -> bool;
 	fn qos(&self) -> QosLevel;
+
+	/// Whether the broker flagged the message as a possible redelivery of an earlier
+	/// message (the MQTT DUP flag). Providers that can't tell return `false`.
+	fn dup(&self) -> bool {
+		false
+	}
 }
 
 pub trait MqttBuildableMessage: MqttMessage {
@@ -210,6 +378,11 @@ impl<T: MqttClient> MqttMessage for MqttReceivedMessage<T> {
 	fn qos(&self) -> QosLevel {
 		MqttMessage::qos(&self.message)
 	}
+
+	#[inline]
+	fn dup(&self) -> bool {
+		MqttMessage::dup(&self.message)
+	}
 }
 
 impl<T: MqttClient> MqttReceivedMessage<T> {
@@ -251,6 +424,17 @@ pub trait MqttMessageBuilder {
 	fn payload(self, payload: impl Into<Vec<u8>>) -> Self;
 	fn qos(self, qos: QosLevel) -> Self;
 	fn retain(self, retain: bool) -> Self;
+
+	/// Adds an MQTT 5 user property to the message. Providers that don't support user
+	/// properties ignore them.
+	fn user_property(self, key: impl Into<String>, value: impl Into<String>) -> Self
+	where
+		Self: Sized,
+	{
+		let _ = (key, value);
+		self
+	}
+
 	fn build(self) -> Result<Self::Message, Self::Error>;
 }
 
@@ -272,6 +456,96 @@ where
 	}
 }
 
+/// Decides which messages get a tracing span. High-throughput users can use this to
+/// only trace 1 in every `rate` messages.
+///
+/// Unsampled messages skip span creation entirely, and use [Span::none] instead.
+#[derive(Clone, Debug)]
+pub struct MessageSpanSampler {
+	rate: u32,
+	counter: Arc<AtomicU32>,
+}
+
+impl MessageSpanSampler {
+	/// Creates a sampler that samples 1 in every `rate` messages. A rate of 0 disables
+	/// message spans, and a rate of 1 (the default) samples every message.
+	pub fn new(rate: u32) -> Self {
+		MessageSpanSampler {
+			rate,
+			counter: Arc::new(AtomicU32::new(0)),
+		}
+	}
+
+	pub fn rate(&self) -> u32 {
+		self.rate
+	}
+
+	/// Returns whether the next message should be traced.
+	pub fn sample(&self) -> bool {
+		match self.rate {
+			0 => false,
+			1 => true,
+			rate => self
+				.counter
+				.fetch_add(1, Ordering::Relaxed)
+				.is_multiple_of(rate),
+		}
+	}
+
+	/// Creates a span for the next message using `f` if it is sampled, or returns
+	/// [Span::none] otherwise.
+	#[inline]
+	pub fn span(&self, f: impl FnOnce() -> Span) -> Span {
+		Self::span_if(self.sample(), f)
+	}
+
+	/// Creates a span using `f` for a message that was already sampled, if `sampled` is
+	/// true, or returns [Span::none] otherwise.
+	#[inline]
+	pub fn span_if(sampled: bool, f: impl FnOnce() -> Span) -> Span {
+		if sampled {
+			f()
+		} else {
+			Span::none()
+		}
+	}
+}
+
+impl Default for MessageSpanSampler {
+	fn default() -> Self {
+		Self::new(1)
+	}
+}
+
+/// The transport used to connect to the broker.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MqttTransport {
+	#[default]
+	Tcp,
+	/// MQTT over WebSockets, using the given HTTP path (like `/mqtt`).
+	WebSocket { path: String },
+}
+
+/// The TLS settings used to connect to the broker. All files are in PEM format.
+#[cfg(feature = "tls")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "tls")))]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MqttTlsConfig {
+	/// The certificates of the certificate authorities the certificate of the broker is
+	/// checked against. Defaults to the trust store of the system.
+	pub ca_file: Option<PathBuf>,
+	/// The certificate (chain) of the client, for brokers that authenticate clients by
+	/// their certificate. The file may also contain the private key.
+	pub client_cert_file: Option<PathBuf>,
+	/// The private key of the client certificate, unless it's in `client_cert_file`.
+	pub private_key_file: Option<PathBuf>,
+	/// The password of the private key, if it's encrypted.
+	pub private_key_password: Option<String>,
+	/// Skips checking the certificate of the broker. This is insecure, and only meant for
+	/// testing against brokers with self-signed certificates.
+	pub insecure_skip_verify: bool,
+}
+
 #[derive(Clone)]
 pub struct MqttOptions {
 	pub host: String,
@@ -279,21 +553,69 @@ pub struct MqttOptions {
 	#[cfg(feature = "tls")]
 	#[cfg_attr(doc_cfg, doc(cfg(feature = "tls")))]
 	pub tls: bool,
+	#[cfg(feature = "tls")]
+	#[cfg_attr(doc_cfg, doc(cfg(feature = "tls")))]
+	pub tls_config: MqttTlsConfig,
 	pub auth: Option<MqttAuthOptions>,
 	pub persitence: PathBuf,
 	pub version: MqttVersion,
+	pub transport: MqttTransport,
+	/// Whether at-most-once messages buffered while disconnected are also written to
+	/// the persistence store, so they survive a restart. Providers that don't persist
+	/// their offline buffer ignore this. Defaults to `true`.
+	pub buffer_qos0: bool,
+	/// Whether repeated publishes to the same topic use MQTT5 topic aliases (up to the
+	/// `topic_alias_maximum` the broker announced), to save sending the full topic.
+	/// Defaults to `false`.
+	pub topic_aliases: bool,
+	/// Whether the client reconnects on its own after losing the connection to the broker.
+	/// Defaults to `true`.
+	pub automatic_reconnect: bool,
+	/// The delay before the first attempt to reconnect. The delay doubles after every
+	/// failed attempt, up to `reconnect_max`. Defaults to 5 seconds when `None`.
+	pub reconnect_min: Option<Duration>,
+	/// The longest delay between attempts to reconnect. Defaults to 5 minutes when `None`.
+	pub reconnect_max: Option<Duration>,
+	/// The longest time without communication between the client and the broker, after
+	/// which the client pings the broker. When `None`, the provider picks the interval
+	/// (60 seconds for paho and rumqttc).
+	pub keep_alive: Option<Duration>,
+	/// Whether the broker discards the session of the client (its subscriptions and
+	/// undelivered messages) when it connects. This is "clean session" for MQTT3, and
+	/// "clean start" for MQTT5. When `None`, the provider default applies (a clean
+	/// session for paho and rumqttc).
+	pub clean_session: Option<bool>,
+	/// How long the broker keeps the session of the client after it disconnects (MQTT5
+	/// only). When `None`, the session ends when the client disconnects.
+	pub session_expiry: Option<Duration>,
+	pub message_span_sampler: MessageSpanSampler,
 }
 
 impl MqttOptions {
+	pub const DEFAULT_RECONNECT_MIN: Duration = Duration::from_secs(5);
+	pub const DEFAULT_RECONNECT_MAX: Duration = Duration::from_secs(60 * 5);
+
 	pub fn new(host: impl Into<String>, persitence: PathBuf) -> Self {
 		MqttOptions {
 			host: host.into(),
 			port: 1883,
 			#[cfg(feature = "tls")]
 			tls: false,
+			#[cfg(feature = "tls")]
+			tls_config: MqttTlsConfig::default(),
 			auth: None,
 			persitence,
 			version: MqttVersion::Default,
+			transport: MqttTransport::Tcp,
+			buffer_qos0: true,
+			topic_aliases: false,
+			automatic_reconnect: true,
+			reconnect_min: None,
+			reconnect_max: None,
+			keep_alive: None,
+			clean_session: None,
+			session_expiry: None,
+			message_span_sampler: MessageSpanSampler::default(),
 		}
 	}
 
@@ -304,9 +626,20 @@ impl MqttOptions {
 			host: host.into(),
 			port: 8883,
 			tls: true,
+			tls_config: MqttTlsConfig::default(),
 			auth: None,
 			persitence,
 			version: MqttVersion::Default,
+			transport: MqttTransport::Tcp,
+			buffer_qos0: true,
+			topic_aliases: false,
+			automatic_reconnect: true,
+			reconnect_min: None,
+			reconnect_max: None,
+			keep_alive: None,
+			clean_session: None,
+			session_expiry: None,
+			message_span_sampler: MessageSpanSampler::default(),
 		}
 	}
 
@@ -322,6 +655,13 @@ impl MqttOptions {
 		self
 	}
 
+	#[cfg(feature = "tls")]
+	#[cfg_attr(doc_cfg, doc(cfg(feature = "tls")))]
+	pub fn tls_config(&mut self, config: MqttTlsConfig) -> &mut Self {
+		self.tls_config = config;
+		self
+	}
+
 	pub fn auth(&mut self, username: impl Into<String>, password: impl Into<String>) -> &mut Self {
 		self.auth = Some(MqttAuthOptions {
 			username: username.into(),
@@ -334,6 +674,68 @@ impl MqttOptions {
 		self.version = version;
 		self
 	}
+
+	pub fn transport(&mut self, transport: MqttTransport) -> &mut Self {
+		self.transport = transport;
+		self
+	}
+
+	pub fn buffer_qos0(&mut self, buffer: bool) -> &mut Self {
+		self.buffer_qos0 = buffer;
+		self
+	}
+
+	pub fn topic_aliases(&mut self, on: bool) -> &mut Self {
+		self.topic_aliases = on;
+		self
+	}
+
+	pub fn automatic_reconnect(&mut self, on: bool) -> &mut Self {
+		self.automatic_reconnect = on;
+		self
+	}
+
+	pub fn reconnect_min(&mut self, delay: Duration) -> &mut Self {
+		self.reconnect_min = Some(delay);
+		self
+	}
+
+	pub fn reconnect_max(&mut self, delay: Duration) -> &mut Self {
+		self.reconnect_max = Some(delay);
+		self
+	}
+
+	pub fn keep_alive(&mut self, interval: Duration) -> &mut Self {
+		self.keep_alive = Some(interval);
+		self
+	}
+
+	pub fn clean_session(&mut self, clean: bool) -> &mut Self {
+		self.clean_session = Some(clean);
+		self
+	}
+
+	pub fn session_expiry(&mut self, expiry: Duration) -> &mut Self {
+		self.session_expiry = Some(expiry);
+		self
+	}
+
+	/// The minimum and maximum delay between attempts to reconnect, with the defaults
+	/// filled in, or `None` if automatic reconnects are disabled.
+	pub fn reconnect_interval(&self) -> Option<(Duration, Duration)> {
+		if !self.automatic_reconnect {
+			return None;
+		}
+
+		let min = self.reconnect_min.unwrap_or(Self::DEFAULT_RECONNECT_MIN);
+		let max = self.reconnect_max.unwrap_or(Self::DEFAULT_RECONNECT_MAX);
+		Some((min, max.max(min)))
+	}
+
+	pub fn message_span_sampler(&mut self, sampler: MessageSpanSampler) -> &mut Self {
+		self.message_span_sampler = sampler;
+		self
+	}
 }
 
 #[derive(Clone)]
@@ -369,4 +771,182 @@ impl<T: MqttClient> MqttMessage for EnteredMessage<T> {
 	fn qos(&self) -> QosLevel {
 		MqttMessage::qos(&self.message)
 	}
+
+	#[inline]
+	fn dup(&self) -> bool {
+		MqttMessage::dup(&self.message)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn qos_converts_to_wire_values() {
+		let levels = [
+			(QosLevel::AtMostOnce, 0),
+			(QosLevel::AtLeastOnce, 1),
+			(QosLevel::ExactlyOnce, 2),
+		];
+
+		for (qos, wire) in levels {
+			assert_eq!(u8::from(qos), wire);
+			assert_eq!(i32::from(qos), i32::from(wire));
+			assert_eq!(qos.to_string(), wire.to_string());
+		}
+
+		assert!(QosLevel::AtMostOnce < QosLevel::AtLeastOnce);
+	}
+
+	#[test]
+	fn qos_parses_from_numbers_and_names() {
+		let levels = [
+			(0, "at_most_once", QosLevel::AtMostOnce),
+			(1, "at_least_once", QosLevel::AtLeastOnce),
+			(2, "exactly_once", QosLevel::ExactlyOnce),
+		];
+
+		for (value, name, qos) in levels {
+			assert_eq!(QosLevel::try_from(value), Ok(qos));
+			assert_eq!(value.to_string().parse(), Ok(qos));
+			assert_eq!(name.parse(), Ok(qos));
+		}
+	}
+
+	#[test]
+	fn qos_rejects_out_of_range_values() {
+		assert_eq!(
+			QosLevel::try_from(3),
+			Err(QosLevelError::OutOfRange { value: 3 })
+		);
+		assert_eq!(
+			"3".parse::<QosLevel>(),
+			Err(QosLevelError::OutOfRange { value: 3 })
+		);
+		assert_eq!(
+			"once".parse::<QosLevel>(),
+			Err(QosLevelError::Invalid {
+				value: "once".into()
+			})
+		);
+		assert_eq!(
+			"256".parse::<QosLevel>(),
+			Err(QosLevelError::Invalid {
+				value: "256".into()
+			})
+		);
+	}
+
+	#[test]
+	fn sampler_rate_zero_samples_nothing() {
+		let sampler = MessageSpanSampler::new(0);
+		assert!((0..10).all(|_| !sampler.sample()));
+	}
+
+	#[test]
+	fn sampler_samples_one_in_n() {
+		let sampler = MessageSpanSampler::new(3);
+		let sampled: Vec<_> = (0..6).map(|_| sampler.sample()).collect();
+		assert_eq!(sampled, [true, false, false, true, false, false]);
+	}
+
+	#[test]
+	#[cfg(feature = "proto")]
+	fn qos_converts_to_and_from_proto_qos() {
+		use hass_mqtt_proto::MqttQoS;
+
+		let levels = [
+			(MqttQoS::AtMostOnce, QosLevel::AtMostOnce),
+			(MqttQoS::AtLeastOnce, QosLevel::AtLeastOnce),
+			(MqttQoS::ExactlyOnce, QosLevel::ExactlyOnce),
+		];
+
+		for (proto, provider) in levels {
+			assert_eq!(QosLevel::from(proto), provider);
+			assert_eq!(MqttQoS::from(provider), proto);
+		}
+	}
+
+	#[test]
+	fn reconnect_interval_defaults() {
+		let options = MqttOptions::new("localhost", PathBuf::from("/tmp/hass-reconnect"));
+		assert_eq!(
+			options.reconnect_interval(),
+			Some((
+				MqttOptions::DEFAULT_RECONNECT_MIN,
+				MqttOptions::DEFAULT_RECONNECT_MAX
+			))
+		);
+	}
+
+	#[test]
+	fn reconnect_interval_is_configurable() {
+		let mut options = MqttOptions::new("localhost", PathBuf::from("/tmp/hass-reconnect"));
+		options
+			.reconnect_min(Duration::from_millis(500))
+			.reconnect_max(Duration::from_secs(30));
+		assert_eq!(
+			options.reconnect_interval(),
+			Some((Duration::from_millis(500), Duration::from_secs(30)))
+		);
+
+		// the maximum is never below the minimum
+		options.reconnect_min(Duration::from_secs(60));
+		assert_eq!(
+			options.reconnect_interval(),
+			Some((Duration::from_secs(60), Duration::from_secs(60)))
+		);
+
+		options.automatic_reconnect(false);
+		assert_eq!(options.reconnect_interval(), None);
+	}
+
+	#[test]
+	fn keep_alive_is_set() {
+		let mut options = MqttOptions::new("localhost", PathBuf::from("/tmp/hass-keep-alive"));
+		assert_eq!(options.keep_alive, None);
+
+		options.keep_alive(Duration::from_secs(30));
+		assert_eq!(options.keep_alive, Some(Duration::from_secs(30)));
+	}
+
+	#[test]
+	fn session_options_are_set() {
+		let mut options = MqttOptions::new("localhost", PathBuf::from("/tmp/hass-session"));
+		assert_eq!(options.clean_session, None);
+		assert_eq!(options.session_expiry, None);
+
+		options
+			.clean_session(false)
+			.session_expiry(Duration::from_secs(3600));
+		assert_eq!(options.clean_session, Some(false));
+		assert_eq!(options.session_expiry, Some(Duration::from_secs(3600)));
+	}
+
+	#[test]
+	#[cfg(feature = "tls")]
+	fn tls_config_is_set() {
+		let mut options = MqttOptions::new_tls("localhost", PathBuf::from("/tmp/hass-tls"));
+		assert_eq!(options.tls_config, MqttTlsConfig::default());
+
+		let config = MqttTlsConfig {
+			ca_file: Some("/etc/mqtt/ca.pem".into()),
+			client_cert_file: Some("/etc/mqtt/client.pem".into()),
+			private_key_file: Some("/etc/mqtt/client.key".into()),
+			private_key_password: Some("secret".into()),
+			insecure_skip_verify: false,
+		};
+		options.tls_config(config.clone());
+		assert_eq!(options.tls_config, config);
+	}
+
+	#[test]
+	fn sampler_clones_share_counter() {
+		let sampler = MessageSpanSampler::new(2);
+		let clone = sampler.clone();
+		assert!(sampler.sample());
+		assert!(!clone.sample());
+		assert!(sampler.sample());
+	}
 }
@@ -27,17 +27,11 @@ enum ApplicationError {
 	#[error("create entity")]
 	CreateEntity,
 
-	#[error("command topic")]
-	CommandTopic,
-
 	#[error("state topic")]
 	StateTopic,
 
-	#[error("serialize discovery document")]
-	SerializeDiscoveryDocument,
-
-	#[error("publish discovery document")]
-	PublishDiscoveryDocument,
+	#[error("activate entity")]
+	ActivateEntity,
 
 	#[error("serialize state document")]
 	SerializeStateDocument,
@@ -99,15 +93,6 @@ async fn main() -> error_stack::Result<(), ApplicationError> {
 		.into_report()
 		.change_context(ApplicationError::CreateEntity)?;
 
-	println!("creating command topic");
-	let mut command_topic = light_entity
-		.command_topic()
-		.topic(format!("{ROOT}/set"))
-		.qos(QosLevel::AtLeastOnce)
-		.await
-		.into_report()
-		.change_context(ApplicationError::CommandTopic)?;
-
 	println!("creating state topic");
 	let state_topic = light_entity
 		.state_topic()
@@ -116,20 +101,19 @@ async fn main() -> error_stack::Result<(), ApplicationError> {
 		.into_report()
 		.change_context(ApplicationError::StateTopic)?;
 
-	let light_discovery_document = Light::new(&command_topic)
+	let light_discovery_document = Light::new(format!("{ROOT}/set"))
 		.object_id("mqtt_light")
 		.name("MQTT Light")
 		.state_topic(&state_topic);
-	let light_discovery_document = serde_json::to_vec(&light_discovery_document)
-		.into_report()
-		.change_context(ApplicationError::SerializeDiscoveryDocument)?;
 
-	println!("publishing discovery document");
-	light_entity
-		.publish(light_discovery_document, true, QosLevel::AtLeastOnce)
+	// subscribes to the command topic before publishing the discovery document, so no
+	// command is missed
+	println!("activating entity");
+	let mut command_topic = light_entity
+		.activate(&light_discovery_document, QosLevel::AtLeastOnce)
 		.await
 		.into_report()
-		.change_context(ApplicationError::PublishDiscoveryDocument)?;
+		.change_context(ApplicationError::ActivateEntity)?;
 
 	let mut on = false;
 	let autoflip_duration = Duration::from_secs(5);
@@ -33,9 +33,6 @@ enum ApplicationError {
 	#[error("state topic")]
 	StateTopic,
 
-	#[error("serialize discovery document")]
-	SerializeDiscoveryDocument,
-
 	#[error("publish discovery document")]
 	PublishDiscoveryDocument,
 
@@ -114,13 +111,10 @@ async fn main() -> error_stack::Result<(), ApplicationError> {
 		.object_id("mqtt_light")
 		.name("MQTT Light")
 		.state_topic(&state_topic);
-	let light_discovery_document = serde_json::to_vec(&light_discovery_document)
-		.into_report()
-		.change_context(ApplicationError::SerializeDiscoveryDocument)?;
 
 	println!("publishing discovery document");
 	light_entity
-		.publish(light_discovery_document, true, QosLevel::AtLeastOnce)
+		.publish_discovery(&light_discovery_document)
 		.await
 		.into_report()
 		.change_context(ApplicationError::PublishDiscoveryDocument)?;